@@ -0,0 +1,54 @@
+//! Plain-text tree rendering shared by all trees.
+
+/// Renders the tree below `root` top-down, one node per line, with the left
+/// child above the right one:
+///
+/// ```text
+/// 5:0
+/// ├── 1:1
+/// │   ├── ·
+/// │   └── 2:3
+/// └── 6:4
+/// ```
+///
+/// `expand` returns the label and the children of a node, or `None` for an
+/// empty child, which is drawn as `·` unless both children are empty.
+/// Subtrees deeper than `max_depth` and all nodes after the first
+/// `max_nodes` are replaced by `...`.
+pub(crate) fn render<N, F>(root: N, max_depth: usize, max_nodes: usize, mut expand: F) -> String
+where
+    F: FnMut(&N) -> Option<(String, N, N)>,
+{
+    let mut out = String::new();
+    let mut shown = 0;
+    // (node, depth, prefix of its line, connector)
+    let mut stack = vec![(root, 0, String::new(), "")];
+    while let Some((node, depth, prefix, connector)) = stack.pop() {
+        out.push_str(&prefix);
+        out.push_str(connector);
+        let Some((label, left, right)) = expand(&node) else {
+            out.push_str("·\n");
+            continue;
+        };
+        if depth > max_depth || shown == max_nodes {
+            out.push_str("...\n");
+            continue;
+        }
+        shown += 1;
+        out.push_str(&label);
+        out.push('\n');
+
+        let children = [&left, &right].map(|child| expand(child).is_some());
+        if children == [false, false] {
+            continue;
+        }
+        let prefix = match connector {
+            "" => prefix,
+            "├── " => prefix + "│   ",
+            _ => prefix + "    ",
+        };
+        stack.push((right, depth + 1, prefix.clone(), "└── "));
+        stack.push((left, depth + 1, prefix, "├── "));
+    }
+    out
+}
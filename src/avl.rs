@@ -0,0 +1,363 @@
+//! AVL tree with the same interface as [`BST`](crate::bst::BST), as a
+//! deterministic balanced baseline for the treaps.
+//!
+//! Every node stores the height of its subtree. After each insert or remove
+//! the nodes on the path back to the root are rotated wherever the heights
+//! of their subtrees differ by more than one, so the tree never gets higher
+//! than about `1.44 * log2(n)`.
+
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+use crate::ascii;
+use crate::boxed_tree::boxed_tree_impls;
+use crate::validate::{self, Side, Trail, ValidationError, Violation};
+
+#[derive(Clone)]
+pub struct AVL<K, V>(Link<K, V>);
+
+impl<K, V> AVL<K, V> {
+    pub fn new() -> Self {
+        Self(None)
+    }
+}
+
+impl<K, V> Default for AVL<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+boxed_tree_impls!(AVL, AVLNode, 0, Index);
+
+impl<K: Ord, V> AVL<K, V> {
+    /// Inserts an entry unless the key is already present.
+    pub fn insert(&mut self, key: K, value: V) {
+        AVLNode::insert(&mut self.0, key, value);
+    }
+
+    pub fn find<'a>(&'a self, key: &K) -> Option<&'a V> {
+        let mut link = &self.0;
+        while let Some(node) = link {
+            link = match key.cmp(&node.key) {
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+                Ordering::Equal => return Some(&node.value),
+            };
+        }
+        None
+    }
+
+    /// Removes the entry for `key` and returns its value. A node with two
+    /// children is replaced by the smallest node of its right subtree.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        AVLNode::remove(&mut self.0, key)
+    }
+}
+
+impl<K: Ord, V> AVL<K, V> {
+    /// Checks that every key lies between the keys of its ancestors, that
+    /// every stored height is correct and that no node is out of balance.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut trail = Trail::default();
+        let mut stack = vec![(&self.0, 0, None, None, None)];
+        while let Some((node, depth, side, lower, upper)) = stack.pop() {
+            trail.enter(depth, side);
+            let Some(AVLNode {
+                key,
+                height,
+                left,
+                right,
+                ..
+            }) = node.as_deref() else { continue };
+            if !validate::in_bounds(key, lower, upper) {
+                return Err(trail.error(Violation::KeyOrder));
+            }
+            let (left_height, right_height) = (height_of(left), height_of(right));
+            if *height != left_height.max(right_height) + 1 {
+                return Err(trail.error(Violation::Height));
+            }
+            if left_height.abs_diff(right_height) > 1 {
+                return Err(trail.error(Violation::Unbalanced));
+            }
+            stack.push((right, depth + 1, Some(Side::Right), Some(key), upper));
+            stack.push((left, depth + 1, Some(Side::Left), lower, Some(key)));
+        }
+        Ok(())
+    }
+}
+
+impl<K, V> AVL<K, V> {
+    /// Keeps only the entries for which `f` returns `true`. Like
+    /// [`AVL::extract_if`], this takes the tree apart and builds it again.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.extract_if(|k, v| !f(k, v)).for_each(drop);
+    }
+
+    /// Returns an iterator that removes and yields every entry for which
+    /// `pred` returns `true`, in ascending key order.
+    ///
+    /// Unlike the treaps, this is not lazy: the whole tree is taken apart
+    /// into a sorted list as soon as `extract_if` is called, and a perfectly
+    /// balanced tree is built from the remaining entries when the iterator
+    /// is dropped, both in O(n). If it is dropped early, all entries not yet
+    /// visited are kept.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, F>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let mut entries = Vec::new();
+        let mut stack = Vec::new();
+        let mut link = self.0.take();
+        loop {
+            while let Some(mut node) = link {
+                link = node.left.take();
+                stack.push(node);
+            }
+            let Some(mut node) = stack.pop() else { break };
+            link = node.right.take();
+            entries.push((node.key, node.value));
+        }
+        ExtractIf {
+            avl: self,
+            entries: entries.into_iter(),
+            kept: Vec::new(),
+            pred,
+        }
+    }
+}
+
+/// Iterator returned by [`AVL::extract_if`].
+pub struct ExtractIf<'a, K, V, F> {
+    avl: &'a mut AVL<K, V>,
+    /// Entries not visited yet, in key order.
+    entries: std::vec::IntoIter<(K, V)>,
+    kept: Vec<(K, V)>,
+    pred: F,
+}
+
+impl<'a, K, V, F> Iterator for ExtractIf<'a, K, V, F>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (key, mut value) in self.entries.by_ref() {
+            if (self.pred)(&key, &mut value) {
+                return Some((key, value));
+            }
+            self.kept.push((key, value));
+        }
+        None
+    }
+}
+
+impl<'a, K, V, F> Drop for ExtractIf<'a, K, V, F> {
+    fn drop(&mut self) {
+        let mut kept = std::mem::take(&mut self.kept);
+        kept.extend(self.entries.by_ref());
+        let len = kept.len();
+        self.avl.0 = AVLNode::build(&mut kept.into_iter(), len);
+    }
+}
+
+impl<K: Display, V> AVL<K, V> {
+    /// Renders the tree as an indented text tree with one `key:height` line
+    /// per node. Subtrees below `max_depth` (the root has depth `0`) and all
+    /// nodes after the first `max_nodes` in pre-order are shown as `...`.
+    pub fn to_ascii(&self, max_depth: usize, max_nodes: usize) -> String {
+        ascii::render(&self.0, max_depth, max_nodes, |link| {
+            let node = link.as_ref()?;
+            Some((format!("{}:{}", node.key, node.height), &node.left, &node.right))
+        })
+    }
+}
+
+/// A subtree, `None` if it is empty.
+type Link<K, V> = Option<Box<AVLNode<K, V>>>;
+
+#[derive(Clone)]
+struct AVLNode<K, V> {
+    key: K,
+    value: V,
+    /// Number of levels of the subtree rooted here, `1` for a leaf. A `u8`
+    /// is plenty, since a tree of height 256 would hold more than `2^177`
+    /// nodes.
+    height: u8,
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+impl<K: Display, V: Display> AVLNode<K, V> {
+    fn dot_lines(&self) -> Vec<String> {
+        vec![format!("{}: {}", self.key, self.value), format!("h = {}", self.height)]
+    }
+}
+
+fn height_of<K, V>(link: &Link<K, V>) -> u8 {
+    link.as_ref().map_or(0, |node| node.height)
+}
+
+impl<K: Ord, V> AVLNode<K, V> {
+    fn insert(link: &mut Link<K, V>, key: K, value: V) {
+        let Some(node) = link else {
+            *link = Some(Box::new(AVLNode {
+                key,
+                value,
+                height: 1,
+                left: None,
+                right: None,
+            }));
+            return;
+        };
+        match key.cmp(&node.key) {
+            Ordering::Less => Self::insert(&mut node.left, key, value),
+            Ordering::Greater => Self::insert(&mut node.right, key, value),
+            Ordering::Equal => return,
+        }
+        Self::rebalance(link);
+    }
+
+    fn remove(link: &mut Link<K, V>, key: &K) -> Option<V> {
+        let node = link.as_mut()?;
+        let value = match key.cmp(&node.key) {
+            Ordering::Less => Self::remove(&mut node.left, key)?,
+            Ordering::Greater => Self::remove(&mut node.right, key)?,
+            Ordering::Equal => {
+                let mut node = link.take().unwrap();
+                *link = match (node.left.take(), node.right.take()) {
+                    (None, right) => right,
+                    (left, None) => left,
+                    (left, mut right) => {
+                        let mut min = Self::pop_min(&mut right);
+                        min.left = left;
+                        min.right = right;
+                        Some(min)
+                    }
+                };
+                node.value
+            }
+        };
+        Self::rebalance(link);
+        Some(value)
+    }
+}
+
+impl<K, V> AVLNode<K, V> {
+    /// Unlinks the smallest node of a non-empty subtree, rebalancing on the
+    /// way back up, and returns it.
+    fn pop_min(link: &mut Link<K, V>) -> Box<AVLNode<K, V>> {
+        let node = link.as_mut().unwrap();
+        if node.left.is_some() {
+            let min = Self::pop_min(&mut node.left);
+            Self::rebalance(link);
+            return min;
+        }
+        let mut node = link.take().unwrap();
+        *link = node.right.take();
+        node
+    }
+
+    /// Restores the height of the subtree root after one of its subtrees
+    /// grew or shrank by one level, rotating if they are out of balance.
+    fn rebalance(link: &mut Link<K, V>) {
+        let Some(node) = link else { return };
+        let (left, right) = (height_of(&node.left), height_of(&node.right));
+        if left > right + 1 {
+            let child = node.left.as_ref().unwrap();
+            if height_of(&child.left) < height_of(&child.right) {
+                Self::rotate_left(&mut node.left);
+            }
+            Self::rotate_right(link);
+        } else if right > left + 1 {
+            let child = node.right.as_ref().unwrap();
+            if height_of(&child.right) < height_of(&child.left) {
+                Self::rotate_right(&mut node.right);
+            }
+            Self::rotate_left(link);
+        } else {
+            node.update_height();
+        }
+    }
+
+    /// Lifts the left child of the subtree root above it.
+    fn rotate_right(link: &mut Link<K, V>) {
+        let mut z = link.take().unwrap();
+        let mut x = z.left.take().unwrap();
+        z.left = x.right.take();
+        z.update_height();
+        x.right = Some(z);
+        x.update_height();
+        *link = Some(x);
+    }
+
+    /// Lifts the right child of the subtree root above it.
+    fn rotate_left(link: &mut Link<K, V>) {
+        let mut z = link.take().unwrap();
+        let mut x = z.right.take().unwrap();
+        z.right = x.left.take();
+        z.update_height();
+        x.left = Some(z);
+        x.update_height();
+        *link = Some(x);
+    }
+
+    fn update_height(&mut self) {
+        self.height = height_of(&self.left).max(height_of(&self.right)) + 1;
+    }
+
+    /// Builds a tree from the next `len` entries of `entries`, which must be
+    /// sorted by key. The sizes of the two subtrees of every node differ by
+    /// at most one.
+    fn build<I>(entries: &mut I, len: usize) -> Link<K, V>
+    where
+        I: Iterator<Item = (K, V)>,
+    {
+        if len == 0 {
+            return None;
+        }
+        let left = Self::build(entries, len / 2);
+        let (key, value) = entries.next()?;
+        let right = Self::build(entries, len - len / 2 - 1);
+        let mut node = AVLNode {
+            key,
+            value,
+            height: 0,
+            left,
+            right,
+        };
+        node.update_height();
+        Some(Box::new(node))
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::{AVLNode, AVL};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    impl<K: Serialize, V: Serialize> Serialize for AVL<K, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(self.iter())
+        }
+    }
+
+    impl<'de, K, V> Deserialize<'de> for AVL<K, V>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+    {
+        /// Reads the entries of an ordered map and builds a balanced tree
+        /// from them without any rotations.
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let entries = BTreeMap::<K, V>::deserialize(deserializer)?;
+            let len = entries.len();
+            Ok(AVL(AVLNode::build(&mut entries.into_iter(), len)))
+        }
+    }
+}
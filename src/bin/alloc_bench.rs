@@ -0,0 +1,114 @@
+//! Counts the allocations per insert of `TreapRec` and `BST` and measures
+//! their insert and find times.
+//!
+//! ```sh
+//! cargo run --release --bin alloc_bench -- [ROUNDS]
+//! ```
+//!
+//! The keys are `u32`, either ascending or shuffled, and the treap weights
+//! random. The finds look up every key once, in shuffled order. The times
+//! are means over all rounds. The program only uses `new`, `insert` and
+//! `find`, so it also runs on older versions of the trees.
+
+#[path = "../counting_alloc.rs"]
+#[allow(dead_code)]
+mod counting_alloc;
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use treap_rust::{bst::BST, Treap as TreapRec};
+
+#[global_allocator]
+static ALLOC: counting_alloc::Counting = counting_alloc::Counting;
+
+fn main() {
+    let rounds = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse().expect("ROUNDS must be a number"))
+        .unwrap_or(3);
+
+    println!("| Baum | Eingabe | Allokationen pro insert | insert | find |");
+    println!("|---|---|---:|---:|---:|");
+    row("TreapRec", 58110, true, rounds, treap_rec);
+    row("TreapRec", 58110, false, rounds, treap_rec);
+    row("TreapRec", 1_000_000, false, rounds, treap_rec);
+    row("BST", 58110, false, rounds, bst);
+    row("BST", 1_000_000, false, rounds, bst);
+    // The degenerate BST takes quadratic time, so the sorted input is short.
+    row("BST", 5000, true, rounds, bst);
+}
+
+/// Result of one round: allocations, insert and find time per key.
+type Measurement = (f64, f64, f64);
+
+fn row(tree: &str, len: u32, sorted: bool, rounds: u32, run: fn(&[u32], u64) -> Measurement) {
+    let mut keys: Vec<u32> = (0..len).collect();
+    if !sorted {
+        fastrand::Rng::with_seed(0).shuffle(&mut keys);
+    }
+    let (mut allocations, mut insert, mut find) = (0.0, 0.0, 0.0);
+    for round in 0..rounds {
+        let (a, i, f) = run(&keys, u64::from(round) + 1);
+        allocations += a;
+        insert += i;
+        find += f;
+    }
+    let rounds = f64::from(rounds);
+    println!(
+        "| {} | {} {} | {:.2} | {:.1} ns | {:.1} ns |",
+        tree,
+        len,
+        if sorted { "sortiert" } else { "zufällig" },
+        allocations / rounds,
+        insert / rounds,
+        find / rounds
+    );
+}
+
+fn treap_rec(keys: &[u32], seed: u64) -> Measurement {
+    let rng = fastrand::Rng::with_seed(seed);
+    let weights: Vec<u32> = keys.iter().map(|_| rng.u32(..)).collect();
+    let queries = shuffled(keys, seed);
+    let mut treap = TreapRec::new();
+    let allocations = counting_alloc::allocations();
+    let start = Instant::now();
+    for (&key, &weight) in keys.iter().zip(&weights) {
+        treap.insert(key, weight, key);
+    }
+    let insert = start.elapsed();
+    let allocations = counting_alloc::allocations() - allocations;
+    let find = time(&queries, |key| treap.find(key).is_some());
+    let len = keys.len() as f64;
+    (allocations as f64 / len, insert.as_nanos() as f64 / len, find)
+}
+
+fn bst(keys: &[u32], seed: u64) -> Measurement {
+    let queries = shuffled(keys, seed);
+    let mut bst = BST::new();
+    let allocations = counting_alloc::allocations();
+    let start = Instant::now();
+    for &key in keys {
+        bst.insert(key, key);
+    }
+    let insert = start.elapsed();
+    let allocations = counting_alloc::allocations() - allocations;
+    let find = time(&queries, |key| bst.find(key).is_some());
+    let len = keys.len() as f64;
+    (allocations as f64 / len, insert.as_nanos() as f64 / len, find)
+}
+
+fn shuffled(keys: &[u32], seed: u64) -> Vec<u32> {
+    let mut queries = keys.to_vec();
+    fastrand::Rng::with_seed(seed).shuffle(&mut queries);
+    queries
+}
+
+/// Returns the mean time per lookup in nanoseconds.
+fn time(queries: &[u32], mut find: impl FnMut(&u32) -> bool) -> f64 {
+    let start = Instant::now();
+    for query in queries {
+        black_box(find(black_box(query)));
+    }
+    start.elapsed().as_nanos() as f64 / queries.len() as f64
+}
@@ -0,0 +1,83 @@
+//! Measures the memory of a `treap_vec::Treap` holding all 58110 words and
+//! the time to find each of them.
+//!
+//! ```sh
+//! cargo run --release --bin arena_bench -- [ROUNDS]
+//! ```
+//!
+//! The memory is what a counting allocator reports as still allocated
+//! after the inserts, including the contents of `String` keys and values.
+//! The finds look up every word once, in sorted or in random order. The
+//! program only uses `new`, `insert` and `find`, so it also runs on older
+//! versions of the treap.
+
+#[path = "../counting_alloc.rs"]
+#[allow(dead_code)]
+mod counting_alloc;
+#[path = "../wordlists.rs"]
+#[allow(dead_code)]
+mod wordlists;
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use treap_rust::treap_vec::Treap;
+
+#[global_allocator]
+static ALLOC: counting_alloc::Counting = counting_alloc::Counting;
+
+fn main() {
+    let rounds = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse().expect("ROUNDS must be a number"))
+        .unwrap_or(3u32);
+
+    let rng = fastrand::Rng::with_seed(0);
+    let words = wordlists::WORDS_UNSORTED;
+    let weights: Vec<i32> = words.iter().map(|_| rng.i32(..)).collect();
+
+    let before = counting_alloc::live_bytes();
+    let mut treap = Treap::new();
+    for (&word, &weight) in words.iter().zip(&weights) {
+        treap.insert(word, weight, 0u32);
+    }
+    let str_bytes = counting_alloc::live_bytes() - before;
+    drop(treap);
+
+    let before = counting_alloc::live_bytes();
+    let mut treap = Treap::new();
+    for (&word, &weight) in words.iter().zip(&weights) {
+        treap.insert(word.to_string(), weight, word.to_string());
+    }
+    let string_bytes = counting_alloc::live_bytes() - before;
+    drop(treap);
+
+    let mut treap = Treap::new();
+    for (&word, &weight) in words.iter().zip(&weights) {
+        treap.insert(word, weight, 0u32);
+    }
+    let mut shuffled = words.to_vec();
+    rng.shuffle(&mut shuffled);
+    let (mut sorted, mut unsorted) = (0.0, 0.0);
+    for _ in 0..rounds {
+        sorted += time(wordlists::WORDS_SORTED, |word| treap.find(word).is_some());
+        unsorted += time(&shuffled, |word| treap.find(word).is_some());
+    }
+    let rounds = f64::from(rounds);
+
+    println!("| Arena | {} Wörter |", words.len());
+    println!("|---|---:|");
+    println!("| `&str`/`u32`, Speicher | {} Byte |", str_bytes);
+    println!("| `String`/`String`, Speicher | {} Byte |", string_bytes);
+    println!("| `find`, sortierte Wörter | {:.0} ns |", sorted / rounds);
+    println!("| `find`, randomisierte Wörter | {:.0} ns |", unsorted / rounds);
+}
+
+/// Returns the mean time per lookup in nanoseconds.
+fn time(queries: &[&str], mut find: impl FnMut(&&str) -> bool) -> f64 {
+    let start = Instant::now();
+    for query in queries {
+        black_box(find(black_box(query)));
+    }
+    start.elapsed().as_nanos() as f64 / queries.len() as f64
+}
@@ -0,0 +1,144 @@
+//! Compares lookups in a live `treap_vec::Treap` with the same treap after
+//! `freeze()`, with and without prefetching.
+//!
+//! ```sh
+//! cargo run --release --bin frozen_bench -- [testdata/timed_find_*.txt]
+//! ```
+//!
+//! Each file is read like the REPL would: `load` inserts the next words of
+//! `WORDS_UNSORTED` and every `find` adds a query. The queries are repeated
+//! until about `LOOKUPS` lookups have been timed. A second table looks up
+//! all loaded words in random order, a third one all keys of treaps with
+//! random `u32` keys.
+
+#[path = "../wordlists.rs"]
+#[allow(dead_code)]
+mod wordlists;
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use treap_rust::treap_vec::Treap;
+
+const LOOKUPS: usize = 2_000_000;
+const RANDOM_SIZES: [usize; 3] = [58_110, 1_000_000, 4_000_000];
+const DEFAULT_FILES: [&str; 5] = [
+    "testdata/timed_find_10.txt",
+    "testdata/timed_find_100.txt",
+    "testdata/timed_find_1000.txt",
+    "testdata/timed_find_10000.txt",
+    "testdata/timed_find_58110.txt",
+];
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let files: Vec<&str> = if args.is_empty() {
+        DEFAULT_FILES.to_vec()
+    } else {
+        args.iter().map(String::as_str).collect()
+    };
+
+    let mut rows = Vec::new();
+    for file in files {
+        let script = std::fs::read_to_string(file).expect("could not read the query file");
+        let (words, queries) = parse(&script);
+        rows.push((file.to_string(), words, queries));
+    }
+
+    println!("| Datei | Wörter | live | frozen | frozen + prefetch |");
+    println!("|---|---:|---:|---:|---:|");
+    for (file, words, queries) in &rows {
+        row(file, words, queries);
+    }
+
+    println!();
+    println!("| Alle geladenen Wörter | Wörter | live | frozen | frozen + prefetch |");
+    println!("|---|---:|---:|---:|---:|");
+    let rng = fastrand::Rng::with_seed(1);
+    for (file, words, _) in &rows {
+        let mut queries = words.clone();
+        rng.shuffle(&mut queries);
+        row(file, words, &queries);
+    }
+
+    println!();
+    println!("| Zufällige u32 | live | frozen | frozen + prefetch |");
+    println!("|---:|---:|---:|---:|");
+    for len in RANDOM_SIZES {
+        random_row(len);
+    }
+}
+
+/// Returns the words loaded by the script and its queries.
+fn parse(script: &str) -> (Vec<String>, Vec<String>) {
+    let mut words = wordlists::WORDS_UNSORTED.iter();
+    let mut loaded = Vec::new();
+    let mut queries = Vec::new();
+    let mut lines = script.lines().map(str::trim);
+    while let Some(command) = lines.next() {
+        match command {
+            "load" => {
+                let num: usize = lines.next().unwrap().parse().unwrap();
+                loaded.extend(words.by_ref().take(num).map(|word| word.to_string()));
+            }
+            "find" => queries.push(lines.next().unwrap().to_string()),
+            _ => {}
+        }
+    }
+    (loaded, queries)
+}
+
+fn row(file: &str, words: &[String], queries: &[String]) {
+    if queries.is_empty() {
+        eprintln!(">> Skipping {}, there is nothing to look up", file);
+        return;
+    }
+    let rng = fastrand::Rng::with_seed(0);
+    let mut treap = Treap::new();
+    for word in words {
+        treap.insert(word.clone(), rng.i32(..), word.clone());
+    }
+    let live = time(queries, |query| treap.find(query).is_some());
+    let frozen = treap.freeze();
+    let plain = time(queries, |query| frozen.find(query).is_some());
+    let prefetch = time(queries, |query| frozen.find_with_prefetch(query).is_some());
+    println!(
+        "| {} | {} | {:.1} ns | {:.1} ns | {:.1} ns |",
+        file,
+        words.len(),
+        live,
+        plain,
+        prefetch
+    );
+}
+
+fn random_row(len: usize) {
+    let rng = fastrand::Rng::with_seed(3);
+    let keys: Vec<u32> = (0..len).map(|_| rng.u32(..)).collect();
+    let mut treap = Treap::with_capacity(len);
+    for &key in &keys {
+        treap.insert(key, rng.u32(..), key);
+    }
+    let mut queries = keys;
+    rng.shuffle(&mut queries);
+    let live = time(&queries, |query| treap.find(query).is_some());
+    let frozen = treap.freeze();
+    let plain = time(&queries, |query| frozen.find(query).is_some());
+    let prefetch = time(&queries, |query| frozen.find_with_prefetch(query).is_some());
+    println!(
+        "| {} | {:.1} ns | {:.1} ns | {:.1} ns |",
+        len, live, plain, prefetch
+    );
+}
+
+/// Returns the mean time per lookup in nanoseconds.
+fn time<K>(queries: &[K], mut find: impl FnMut(&K) -> bool) -> f64 {
+    let rounds = (LOOKUPS / queries.len()).max(1);
+    let start = Instant::now();
+    for _ in 0..rounds {
+        for query in queries {
+            black_box(find(black_box(query)));
+        }
+    }
+    start.elapsed().as_nanos() as f64 / (rounds * queries.len()) as f64
+}
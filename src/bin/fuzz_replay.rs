@@ -0,0 +1,108 @@
+//! Runs the fuzzing checks without libFuzzer.
+//!
+//! ```sh
+//! # replay every file below fuzzdata/ (the default) or the given paths
+//! cargo run --release --bin fuzz_replay -- [PATH]...
+//! # additionally try 100000 random inputs
+//! cargo run --release --bin fuzz_replay -- --random 100000 [--seed SEED]
+//! ```
+//!
+//! Inputs that make the checks fail are written to `fuzzdata/crashes/`, so
+//! they are replayed on every later run.
+
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const CORPUS_DIR: &str = "fuzzdata";
+const CRASH_DIR: &str = "fuzzdata/crashes";
+const MAX_RANDOM_LEN: usize = 512;
+
+fn main() -> ExitCode {
+    let mut paths = Vec::new();
+    let mut random = 0;
+    let mut seed = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--random" => random = parse_next(&mut args, "--random"),
+            "--seed" => seed = Some(parse_next(&mut args, "--seed")),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        paths.push(PathBuf::from(CORPUS_DIR));
+    }
+
+    let mut inputs = Vec::new();
+    for path in &paths {
+        collect_files(path, &mut inputs);
+    }
+    let mut failures = 0;
+    for file in &inputs {
+        let data = fs::read(file).expect("failed to read input");
+        if !passes(&data) {
+            eprintln!(">> FAILED: {}", file.display());
+            failures += 1;
+        }
+    }
+    eprintln!(">> Replayed {} inputs", inputs.len());
+
+    if random > 0 {
+        let seed = seed.unwrap_or_else(|| fastrand::u64(..));
+        eprintln!(">> Trying {} random inputs with --seed {}", random, seed);
+        let rng = fastrand::Rng::with_seed(seed);
+        for _ in 0..random {
+            let data: Vec<u8> = (0..rng.usize(..MAX_RANDOM_LEN)).map(|_| rng.u8(..)).collect();
+            if !passes(&data) {
+                let file = save_crash(&data);
+                eprintln!(">> FAILED: saved input to {}", file.display());
+                failures += 1;
+            }
+        }
+    }
+
+    if failures == 0 {
+        ExitCode::SUCCESS
+    } else {
+        eprintln!(">> {} failing inputs", failures);
+        ExitCode::FAILURE
+    }
+}
+
+fn parse_next<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> T {
+    args.next()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_else(|| panic!("{} expects a number", flag))
+}
+
+fn passes(data: &[u8]) -> bool {
+    panic::catch_unwind(AssertUnwindSafe(|| treap_rust::fuzzing::run(data))).is_ok()
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        let mut entries: Vec<_> = fs::read_dir(path)
+            .expect("failed to read corpus directory")
+            .map(|e| e.expect("failed to read corpus directory").path())
+            .collect();
+        entries.sort();
+        for entry in entries {
+            collect_files(&entry, files);
+        }
+    } else if path.is_file() {
+        files.push(path.to_owned());
+    }
+}
+
+fn save_crash(data: &[u8]) -> PathBuf {
+    // FNV-1a, so the same input always ends up in the same file.
+    let hash = data.iter().fold(0xcbf2_9ce4_8422_2325_u64, |h, b| {
+        (h ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+    });
+    fs::create_dir_all(CRASH_DIR).expect("failed to create crash directory");
+    let file = Path::new(CRASH_DIR).join(format!("crash-{:016x}", hash));
+    fs::write(&file, data).expect("failed to save crashing input");
+    file
+}
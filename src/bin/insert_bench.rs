@@ -0,0 +1,65 @@
+//! Compares the insert strategies of `treap_vec::Treap`.
+//!
+//! ```sh
+//! cargo run --release --bin insert_bench -- [ROUNDS]
+//! ```
+//!
+//! Every round builds a treap from both word lists and from one million
+//! random `u32` keys, once per strategy, with the same weights. The table
+//! shows the mean time per insert over all rounds.
+
+#[path = "../wordlists.rs"]
+#[allow(dead_code)]
+mod wordlists;
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use treap_rust::treap_vec::{InsertStrategy, Treap};
+
+const RANDOM_KEYS: usize = 1_000_000;
+const STRATEGIES: [InsertStrategy; 2] = [InsertStrategy::BottomUp, InsertStrategy::TopDown];
+
+fn main() {
+    let rounds = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse().expect("ROUNDS must be a number"))
+        .unwrap_or(5);
+
+    let rng = fastrand::Rng::with_seed(0);
+    let random: Vec<u32> = (0..RANDOM_KEYS).map(|_| rng.u32(..)).collect();
+
+    println!("| Eingabe | BottomUp | TopDown |");
+    println!("|---|---:|---:|");
+    row("WORDS_SORTED", wordlists::WORDS_SORTED, rounds);
+    row("WORDS_UNSORTED", wordlists::WORDS_UNSORTED, rounds);
+    row("1.000.000 zufällige u32", &random, rounds);
+}
+
+fn row<K: Ord + Copy>(name: &str, keys: &[K], rounds: u32) {
+    print!("| {} |", name);
+    for strategy in STRATEGIES {
+        let mut total = 0.0;
+        for round in 0..rounds {
+            total += run(strategy, keys, u64::from(round));
+        }
+        print!(" {:.1} ns |", total / f64::from(rounds));
+    }
+    println!();
+}
+
+/// Returns the mean time per insert in nanoseconds.
+fn run<K: Ord + Copy>(strategy: InsertStrategy, keys: &[K], seed: u64) -> f64 {
+    // Seed 0 generates the random keys; weights drawn from the same
+    // sequence would be sorted like the keys.
+    let rng = fastrand::Rng::with_seed(seed + 1);
+    let weights: Vec<u32> = keys.iter().map(|_| rng.u32(..)).collect();
+    let mut treap = Treap::with_capacity(keys.len());
+    let start = Instant::now();
+    for (&key, &weight) in keys.iter().zip(&weights) {
+        treap.insert_with(strategy, key, weight, ());
+    }
+    let elapsed = start.elapsed();
+    black_box(&treap);
+    elapsed.as_nanos() as f64 / keys.len() as f64
+}
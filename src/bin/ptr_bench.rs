@@ -0,0 +1,68 @@
+//! Compares `treap::Treap` (`Rc<RefCell<..>>`), `treap_ptr::Treap` (raw
+//! pointers, same algorithm) and the recursive `Treap`.
+//!
+//! ```sh
+//! cargo run --release --bin ptr_bench -- [ENTRIES]
+//! ```
+//!
+//! Every treap gets the same random keys and weights. Finds look up all
+//! keys again in a different random order. The trees are built and dropped
+//! one after the other, so only one of them is in memory at a time.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use treap_rust::{treap, treap_ptr, Treap as TreapRec};
+
+const DEFAULT_ENTRIES: usize = 1_000_000;
+
+fn main() {
+    let entries = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse().expect("ENTRIES must be a number"))
+        .unwrap_or(DEFAULT_ENTRIES);
+
+    let rng = fastrand::Rng::with_seed(0);
+    let inserts: Vec<(u32, i32)> = (0..entries).map(|_| (rng.u32(..), rng.i32(..))).collect();
+    let mut queries: Vec<u32> = inserts.iter().map(|&(key, _)| key).collect();
+    rng.shuffle(&mut queries);
+
+    println!("| {} Einträge | insert | find |", entries);
+    println!("|---|---:|---:|");
+
+    let mut rc = treap::Treap::new();
+    let insert = time(&inserts, |&(key, weight)| rc.insert(key, weight, key));
+    let find = time(&queries, |key| {
+        black_box(rc.find_with(key, |value| *value));
+    });
+    row("treap::Treap", insert, find);
+    drop(rc);
+
+    let mut ptr = treap_ptr::Treap::new();
+    let insert = time(&inserts, |&(key, weight)| ptr.insert(key, weight, key));
+    let find = time(&queries, |key| {
+        black_box(ptr.find(key));
+    });
+    row("treap_ptr::Treap", insert, find);
+    drop(ptr);
+
+    let mut rec = TreapRec::new();
+    let insert = time(&inserts, |&(key, weight)| rec.insert(key, weight, key));
+    let find = time(&queries, |key| {
+        black_box(rec.find(key));
+    });
+    row("Treap (rekursiv)", insert, find);
+}
+
+/// Returns the mean time per item in nanoseconds.
+fn time<T>(items: &[T], mut f: impl FnMut(&T)) -> f64 {
+    let start = Instant::now();
+    for item in items {
+        f(item);
+    }
+    start.elapsed().as_nanos() as f64 / items.len() as f64
+}
+
+fn row(name: &str, insert: f64, find: f64) {
+    println!("| {} | {:.0} ns | {:.0} ns |", name, insert, find);
+}
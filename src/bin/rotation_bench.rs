@@ -0,0 +1,117 @@
+//! Compares `rbtree::RBTree` with the recursive `Treap`: rotations per
+//! insert, height and the time for inserts and finds.
+//!
+//! ```sh
+//! cargo run --release --bin rotation_bench -- [ENTRIES]
+//! ```
+//!
+//! The inputs are the sorted and the unsorted word list and `ENTRIES`
+//! random `u32` keys (default 1M). Finds look up every key again in random
+//! order and are repeated until about `LOOKUPS` lookups have been timed.
+
+#[path = "../wordlists.rs"]
+#[allow(dead_code)]
+mod wordlists;
+
+use std::hint::black_box;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use treap_rust::rbtree::RBTree;
+use treap_rust::Treap as TreapRec;
+
+const DEFAULT_ENTRIES: usize = 1_000_000;
+const LOOKUPS: usize = 2_000_000;
+
+static TREAP_ROTATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Treap weight that counts every `<` that comes out `true`. The treap
+/// compares weights only to decide whether to rotate a child up, so this
+/// counts its rotations without touching the library.
+#[derive(PartialEq, Eq)]
+struct Weight(u32);
+
+impl PartialOrd for Weight {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+
+    fn lt(&self, other: &Self) -> bool {
+        let less = self.0 < other.0;
+        if less {
+            TREAP_ROTATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        less
+    }
+}
+
+impl Ord for Weight {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+fn main() {
+    let entries = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse().expect("ENTRIES must be a number"))
+        .unwrap_or(DEFAULT_ENTRIES);
+
+    println!("| Eingabe | Baum | Rotationen pro insert | Höhe | insert | find |");
+    println!("|---|---|---:|---:|---:|---:|");
+    let rng = fastrand::Rng::with_seed(0);
+    compare("WORDS_SORTED", wordlists::WORDS_SORTED.to_vec());
+    compare("WORDS_UNSORTED", wordlists::WORDS_UNSORTED.to_vec());
+    let keys: Vec<u32> = (0..entries).map(|_| rng.u32(..)).collect();
+    compare(&format!("{} zufällige u32", entries), keys);
+}
+
+fn compare<K: Ord + Copy>(input: &str, keys: Vec<K>) {
+    let rng = fastrand::Rng::with_seed(1);
+    let weights: Vec<u32> = keys.iter().map(|_| rng.u32(..)).collect();
+    let mut queries = keys.clone();
+    rng.shuffle(&mut queries);
+
+    let mut tree = RBTree::new();
+    let insert = time(&keys, |&key| tree.insert(key, ()));
+    let rotations = tree.rotations() as f64 / keys.len() as f64;
+    let find = time_finds(&queries, |key| tree.find(key).is_some());
+    row(input, "RBTree", rotations, tree.height(), insert, find);
+    drop(tree);
+
+    let mut treap = TreapRec::new();
+    let mut weights = weights.into_iter();
+    TREAP_ROTATIONS.store(0, Ordering::Relaxed);
+    let insert = time(&keys, |&key| treap.insert(key, Weight(weights.next().unwrap()), ()));
+    let rotations = TREAP_ROTATIONS.load(Ordering::Relaxed) as f64 / keys.len() as f64;
+    let find = time_finds(&queries, |key| treap.find(key).is_some());
+    row(input, "Treap (rekursiv)", rotations, treap.height(), insert, find);
+}
+
+/// Returns the mean time per item in nanoseconds.
+fn time<T>(items: &[T], mut f: impl FnMut(&T)) -> f64 {
+    let start = Instant::now();
+    for item in items {
+        f(item);
+    }
+    start.elapsed().as_nanos() as f64 / items.len() as f64
+}
+
+/// Like [`time`], but repeats small query lists.
+fn time_finds<K>(queries: &[K], mut find: impl FnMut(&K) -> bool) -> f64 {
+    let rounds = (LOOKUPS / queries.len()).max(1);
+    let start = Instant::now();
+    for _ in 0..rounds {
+        for query in queries {
+            black_box(find(black_box(query)));
+        }
+    }
+    start.elapsed().as_nanos() as f64 / (rounds * queries.len()) as f64
+}
+
+fn row(input: &str, tree: &str, rotations: f64, height: usize, insert: f64, find: f64) {
+    println!(
+        "| {} | {} | {:.2} | {} | {:.0} ns | {:.0} ns |",
+        input, tree, rotations, height, insert, find
+    );
+}
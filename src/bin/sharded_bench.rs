@@ -0,0 +1,93 @@
+//! Measures the throughput of `ShardedTreap` for a growing number of threads.
+//!
+//! ```sh
+//! cargo run --release --bin sharded_bench -- [MAX_THREADS]
+//! ```
+//!
+//! Every thread runs the same mix of operations on random keys: 60% finds,
+//! 25% inserts and 15% removes. A single shard behaves like one treap behind
+//! one `Mutex` and serves as the baseline.
+//!
+//! The table only shows scaling if the machine has at least as many cores as
+//! threads, so the number of cores is printed above it.
+
+use std::thread;
+use std::time::Instant;
+
+use treap_rust::sharded::ShardedTreap;
+
+const KEYS: u32 = 1 << 20;
+const PREFILL: u32 = KEYS / 2;
+const OPS_PER_THREAD: u32 = 1_000_000;
+const SHARD_COUNTS: [usize; 3] = [1, 16, 64];
+
+fn main() {
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    let max_threads = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse().expect("MAX_THREADS must be a number"))
+        .unwrap_or(cores);
+
+    println!(
+        "Gemessen mit {} Kern{}.",
+        cores,
+        if cores == 1 { "" } else { "en" }
+    );
+    if cores < max_threads {
+        eprintln!(
+            ">> Only {} cores for up to {} threads, the table shows no scaling",
+            cores, max_threads
+        );
+    }
+    println!();
+
+    print!("| Threads |");
+    for shards in SHARD_COUNTS {
+        print!(" {} Shard{} |", shards, if shards == 1 { "" } else { "s" });
+    }
+    println!();
+    println!("|---:|{}", "---:|".repeat(SHARD_COUNTS.len()));
+
+    let mut threads = 1;
+    while threads <= max_threads {
+        print!("| {} |", threads);
+        for shards in SHARD_COUNTS {
+            print!(" {:.2} Mops/s |", run(shards, threads));
+        }
+        println!();
+        threads *= 2;
+    }
+}
+
+/// Returns the throughput in million operations per second.
+fn run(shards: usize, threads: usize) -> f64 {
+    let map = ShardedTreap::with_shards(shards);
+    let rng = fastrand::Rng::with_seed(0);
+    for _ in 0..PREFILL {
+        map.insert(rng.u32(..KEYS), 0u32);
+    }
+
+    let start = Instant::now();
+    thread::scope(|s| {
+        for seed in 0..threads as u64 {
+            let map = &map;
+            s.spawn(move || {
+                let rng = fastrand::Rng::with_seed(seed + 1);
+                for _ in 0..OPS_PER_THREAD {
+                    let key = rng.u32(..KEYS);
+                    match rng.u8(..100) {
+                        0..=59 => {
+                            map.find_with(&key, |_| ());
+                        }
+                        60..=84 => map.insert(key, key),
+                        _ => {
+                            map.remove(&key);
+                        }
+                    }
+                }
+            });
+        }
+    });
+    let ops = f64::from(OPS_PER_THREAD) * threads as f64;
+    ops / start.elapsed().as_secs_f64() / 1e6
+}
@@ -0,0 +1,105 @@
+//! Compares lookups in `splay::Splay` and `treap_vec::Treap` when some
+//! words are asked for much more often than others.
+//!
+//! ```sh
+//! cargo run --release --bin splay_bench -- [LOOKUPS]
+//! ```
+//!
+//! Both trees hold all of `WORDS_UNSORTED`. The queries follow a Zipf
+//! distribution: the word of rank `r` is looked up with a probability
+//! proportional to `1 / r^s`. The first ranks are the most frequent English
+//! words in `HOT_WORDS`, the remaining words follow in list order. `s = 0`
+//! asks for every word equally often, Zipf's law for English text is about
+//! `s = 1`.
+
+#[path = "../wordlists.rs"]
+#[allow(dead_code)]
+mod wordlists;
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use treap_rust::splay::Splay;
+use treap_rust::treap_vec::Treap;
+
+const DEFAULT_LOOKUPS: usize = 2_000_000;
+const EXPONENTS: [f64; 5] = [0.0, 0.8, 1.0, 1.2, 1.5];
+
+/// The most frequent English words, most frequent first ("a" and "i" are
+/// missing from the word list).
+const HOT_WORDS: [&str; 98] = [
+    "the", "of", "and", "to", "in", "is", "you", "that", "it", "he", "was", "for", "on", "are",
+    "as", "with", "his", "they", "at", "be", "this", "have", "from", "or", "one", "had", "by",
+    "word", "but", "not", "what", "all", "were", "we", "when", "your", "can", "said", "there",
+    "use", "an", "each", "which", "she", "do", "how", "their", "if", "will", "up", "other",
+    "about", "out", "many", "then", "them", "these", "so", "some", "her", "would", "make",
+    "like", "him", "into", "time", "has", "look", "two", "more", "write", "go", "see", "number",
+    "no", "way", "could", "people", "my", "than", "first", "water", "been", "call", "who",
+    "oil", "its", "now", "find", "long", "down", "day", "did", "get", "come", "made", "may",
+    "part",
+];
+
+fn main() {
+    let lookups = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse().expect("LOOKUPS must be a number"))
+        .unwrap_or(DEFAULT_LOOKUPS);
+
+    let words: Vec<String> = wordlists::WORDS_UNSORTED.iter().map(|w| w.to_string()).collect();
+    let mut ranked: Vec<&String> = HOT_WORDS
+        .iter()
+        .map(|hot| words.iter().find(|word| word == hot).expect("hot word not in the list"))
+        .collect();
+    ranked.extend(words.iter().filter(|word| !HOT_WORDS.contains(&word.as_str())));
+
+    let rng = fastrand::Rng::with_seed(0);
+    let mut treap = Treap::new();
+    let mut splay = Splay::new();
+    for word in &words {
+        treap.insert(word.clone(), rng.i32(..), word.clone());
+        splay.insert(word.clone(), word.clone());
+    }
+
+    println!("| Verteilung | Anteil der 98 häufigsten | treap_vec | Splay |");
+    println!("|---|---:|---:|---:|");
+    for s in EXPONENTS {
+        let queries = zipf(&rng, &ranked, s, lookups);
+        let hot = queries.iter().filter(|q| HOT_WORDS.contains(&q.as_str())).count();
+        let treap_ns = time(&queries, |query| treap.find(query).is_some());
+        let splay_ns = time(&queries, |query| splay.find(query).is_some());
+        println!(
+            "| s = {} | {:.1} % | {:.1} ns | {:.1} ns |",
+            s,
+            100.0 * hot as f64 / queries.len() as f64,
+            treap_ns,
+            splay_ns
+        );
+    }
+}
+
+/// Draws `len` words, the one at index `r` with a probability proportional
+/// to `1 / (r + 1)^s`.
+fn zipf<'a>(rng: &fastrand::Rng, ranked: &[&'a String], s: f64, len: usize) -> Vec<&'a String> {
+    let mut cumulative = Vec::with_capacity(ranked.len());
+    let mut total = 0.0;
+    for rank in 1..=ranked.len() {
+        total += (rank as f64).powf(-s);
+        cumulative.push(total);
+    }
+    (0..len)
+        .map(|_| {
+            let x = rng.f64() * total;
+            let index = cumulative.partition_point(|&c| c <= x);
+            ranked[index.min(ranked.len() - 1)]
+        })
+        .collect()
+}
+
+/// Returns the mean time per lookup in nanoseconds.
+fn time<K>(queries: &[K], mut find: impl FnMut(&K) -> bool) -> f64 {
+    let start = Instant::now();
+    for query in queries {
+        black_box(find(black_box(query)));
+    }
+    start.elapsed().as_nanos() as f64 / queries.len() as f64
+}
@@ -0,0 +1,164 @@
+//! Impls shared by [`AVL`](crate::avl::AVL), [`RBTree`](crate::rbtree::RBTree)
+//! and [`Splay`](crate::splay::Splay), which only differ in how they
+//! rebalance.
+
+/// Implements `Debug`, the shape statistics, `iter`, `to_dot` and the
+/// comparison traits, which only compare the entries, for `$tree`, and
+/// defines its `Iter`. The tree keeps its root `Link` in the field `$root`,
+/// and `$node` has `key`, `value`, `left` and `right` fields and a
+/// `dot_lines` method for the DOT label. With `Index`, the tree is also
+/// indexed by key through its `find`.
+macro_rules! boxed_tree_impls {
+    ($tree:ident, $node:ident, $root:tt, Index) => {
+        $crate::boxed_tree::boxed_tree_impls!($tree, $node, $root);
+
+        impl<K: Ord, V> std::ops::Index<&K> for $tree<K, V> {
+            type Output = V;
+
+            fn index(&self, key: &K) -> &V {
+                self.find(key).expect("no entry found for key")
+            }
+        }
+    };
+    ($tree:ident, $node:ident, $root:tt) => {
+        impl<K: std::fmt::Debug, V: std::fmt::Debug> std::fmt::Debug for $tree<K, V> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_map().entries(self.iter()).finish()
+            }
+        }
+
+        impl<K, V> $tree<K, V> {
+            pub fn height(&self) -> usize {
+                self.depths().height()
+            }
+
+            pub fn average_depth(&self) -> f64 {
+                self.depths().average_depth()
+            }
+
+            pub fn depth_histogram(&self) -> Vec<usize> {
+                self.depths().into_histogram()
+            }
+
+            pub fn leaf_count(&self) -> usize {
+                self.depths().leaves()
+            }
+
+            fn depths(&self) -> $crate::stats::Depths {
+                let mut depths = $crate::stats::Depths::default();
+                let mut stack = vec![(&self.$root, 0)];
+                while let Some((node, depth)) = stack.pop() {
+                    let Some(node) = node else { continue };
+                    depths.add(depth, node.left.is_none() && node.right.is_none());
+                    stack.push((&node.left, depth + 1));
+                    stack.push((&node.right, depth + 1));
+                }
+                depths
+            }
+
+            /// Returns an iterator over all entries in ascending key order.
+            pub fn iter(&self) -> Iter<'_, K, V> {
+                let mut iter = Iter { stack: Vec::new() };
+                iter.push_left(&self.$root);
+                iter
+            }
+        }
+
+        #[doc = concat!("Iterator returned by [`", stringify!($tree), "::iter`].")]
+        pub struct Iter<'a, K, V> {
+            stack: Vec<&'a $node<K, V>>,
+        }
+
+        impl<'a, K, V> Iter<'a, K, V> {
+            fn push_left(&mut self, mut link: &'a Link<K, V>) {
+                while let Some(node) = link {
+                    self.stack.push(node);
+                    link = &node.left;
+                }
+            }
+        }
+
+        impl<'a, K, V> Iterator for Iter<'a, K, V> {
+            type Item = (&'a K, &'a V);
+
+            fn next(&mut self) -> Option<Self::Item> {
+                let node = self.stack.pop()?;
+                self.push_left(&node.right);
+                Some((&node.key, &node.value))
+            }
+        }
+
+        impl<'a, K, V> IntoIterator for &'a $tree<K, V> {
+            type Item = (&'a K, &'a V);
+            type IntoIter = Iter<'a, K, V>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.iter()
+            }
+        }
+
+        impl<K: std::fmt::Display, V: std::fmt::Display> $tree<K, V> {
+            /// Writes the tree in Graphviz DOT format. Empty children are drawn as
+            /// small grey dots.
+            pub fn to_dot<T: std::io::Write>(&self, writer: T) -> std::io::Result<()> {
+                self.to_dot_with_max_depth(writer, usize::MAX)
+            }
+
+            #[doc = concat!("Like [`", stringify!($tree), "::to_dot`], but replaces every")]
+            /// subtree below `max_depth` by a `...` node. The root has depth `0`.
+            pub fn to_dot_with_max_depth<T: std::io::Write>(
+                &self,
+                writer: T,
+                max_depth: usize,
+            ) -> std::io::Result<()> {
+                let mut dot = $crate::dot::Dot::begin(writer)?;
+                let mut stack = vec![(&self.$root, 0, None)];
+                while let Some((node, depth, parent)) = stack.pop() {
+                    match node {
+                        None => dot.empty(parent)?,
+                        Some(_) if depth > max_depth => dot.cut(parent)?,
+                        Some(node) => {
+                            let id = dot.node(parent, &node.dot_lines())?;
+                            stack.push((&node.right, depth + 1, Some(id)));
+                            stack.push((&node.left, depth + 1, Some(id)));
+                        }
+                    }
+                }
+                dot.end()
+            }
+        }
+
+        impl<K: PartialEq, V: PartialEq> PartialEq for $tree<K, V> {
+            fn eq(&self, other: &Self) -> bool {
+                self.iter().eq(other.iter())
+            }
+        }
+
+        impl<K: Eq, V: Eq> Eq for $tree<K, V> {}
+
+        impl<K: PartialOrd, V: PartialOrd> PartialOrd for $tree<K, V> {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                self.iter().partial_cmp(other.iter())
+            }
+        }
+
+        impl<K: Ord, V: Ord> Ord for $tree<K, V> {
+            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                self.iter().cmp(other.iter())
+            }
+        }
+
+        impl<K: std::hash::Hash, V: std::hash::Hash> std::hash::Hash for $tree<K, V> {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                let mut len = 0;
+                for entry in self {
+                    entry.hash(state);
+                    len += 1;
+                }
+                state.write_usize(len);
+            }
+        }
+    };
+}
+
+pub(crate) use boxed_tree_impls;
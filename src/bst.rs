@@ -1,77 +1,480 @@
-#[derive(Debug)]
-pub struct BST<K, V>(BSTNode<K, V>);
+use std::cmp::Ordering;
+use std::fmt::{self, Debug, Display};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::ops::Index;
+
+use crate::ascii;
+use crate::clone_tree::clone_tree;
+use crate::dot::Dot;
+use crate::stats::Depths;
+use crate::validate::{self, Side, Trail, ValidationError, Violation};
+
+pub struct BST<K, V>(Link<K, V>);
 
 impl<K, V> BST<K, V> {
     pub fn new() -> Self {
-        Self(BSTNode::Empty)
+        Self(None)
+    }
+}
+
+impl<K, V> Default for BST<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Drop for BST<K, V> {
+    fn drop(&mut self) {
+        let mut stack: Vec<_> = self.0.take().into_iter().collect();
+        while let Some(mut node) = stack.pop() {
+            let BSTNode(_, _, left, right) = &mut *node;
+            stack.extend(left.take());
+            stack.extend(right.take());
+        }
+    }
+}
+
+impl<K: Clone, V: Clone> Clone for BST<K, V> {
+    fn clone(&self) -> Self {
+        Self(clone_tree(
+            &self.0,
+            |BSTNode(k, v, _, _)| BSTNode(k.clone(), v.clone(), None, None),
+            |node| (&node.2, &node.3),
+            |node| (&mut node.2, &mut node.3),
+        ))
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for BST<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
     }
 }
 
 impl<K: Ord, V> BST<K, V> {
     pub fn insert(&mut self, key: K, value: V) {
-        self.0.insert(key, value);
+        BSTNode::insert(&mut self.0, key, value);
     }
 
-    pub fn find<'a, 'b>(&'a self, key: &'b K) -> Option<&'a V> {
-        self.0.find(key)
+    pub fn find<'a>(&'a self, key: &K) -> Option<&'a V> {
+        BSTNode::find(&self.0, key)
     }
 }
 
-#[derive(Debug)]
-enum BSTNode<K, V> {
-    Empty,
-    Node(K, V, Box<BSTNode<K, V>>, Box<BSTNode<K, V>>),
+impl<K, V> BST<K, V> {
+    pub fn height(&self) -> usize {
+        self.depths().height()
+    }
+
+    pub fn average_depth(&self) -> f64 {
+        self.depths().average_depth()
+    }
+
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        self.depths().into_histogram()
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.depths().leaves()
+    }
+
+    fn depths(&self) -> Depths {
+        let mut depths = Depths::default();
+        let mut stack = vec![(&self.0, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            let Some(BSTNode(_, _, left, right)) = node.as_deref() else { continue };
+            depths.add(depth, left.is_none() && right.is_none());
+            stack.push((left, depth + 1));
+            stack.push((right, depth + 1));
+        }
+        depths
+    }
 }
 
-impl<K: Ord, V> BSTNode<K, V> {
-    pub fn insert(&mut self, key: K, value: V) {
-        let mut current = self;
+impl<K: Ord, V> BST<K, V> {
+    /// Checks that every key lies between the keys of its ancestors.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut trail = Trail::default();
+        let mut stack = vec![(&self.0, 0, None, None, None)];
+        while let Some((node, depth, side, lower, upper)) = stack.pop() {
+            trail.enter(depth, side);
+            let Some(BSTNode(k, _, left, right)) = node.as_deref() else { continue };
+            if !validate::in_bounds(k, lower, upper) {
+                return Err(trail.error(Violation::KeyOrder));
+            }
+            stack.push((right, depth + 1, Some(Side::Right), Some(k), upper));
+            stack.push((left, depth + 1, Some(Side::Left), lower, Some(k)));
+        }
+        Ok(())
+    }
+}
+
+impl<K, V> BST<K, V> {
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.extract_if(|k, v| !f(k, v)).for_each(drop);
+    }
+
+    /// Returns an iterator that removes and yields every entry for which
+    /// `pred` returns `true`, in ascending key order.
+    ///
+    /// Entries are only visited as the iterator is advanced. If it is dropped
+    /// early, all entries not yet visited are kept.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, F>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let root = self.0.take();
+        ExtractIf {
+            bst: self,
+            stack: Vec::new(),
+            cursor: Cursor::Descend(root),
+            pred,
+        }
+    }
+}
+
+/// Iterator returned by [`BST::extract_if`].
+///
+/// The tree is taken apart while iterating: nodes on the current path are
+/// kept on an explicit stack and put back together on the way up.
+pub struct ExtractIf<'a, K, V, F> {
+    bst: &'a mut BST<K, V>,
+    stack: Vec<Frame<K, V>>,
+    cursor: Cursor<K, V>,
+    pred: F,
+}
+
+enum Frame<K, V> {
+    Left(Box<BSTNode<K, V>>),
+    Right(Box<BSTNode<K, V>>),
+    Extracted(Link<K, V>),
+}
+
+enum Cursor<K, V> {
+    Descend(Link<K, V>),
+    Ascend(Link<K, V>),
+    Finished,
+}
+
+impl<'a, K, V, F> Iterator for ExtractIf<'a, K, V, F>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
         loop {
-            if current.is_empty() {
-                *current = BSTNode::Node(
-                    key,
-                    value,
-                    Box::new(BSTNode::Empty),
-                    Box::new(BSTNode::Empty),
-                );
-                break;
-            } else {
-                let BSTNode::Node(k, _, left, right) = current else { unreachable!() };
-                if key < *k {
-                    current = left.as_mut();
-                } else if *k < key {
-                    current = right.as_mut();
-                } else {
-                    break;
+            // Every node stays reachable from `stack` or `cursor` while `pred`
+            // runs, so that `drop` can link it back in if `pred` panics.
+            match &mut self.cursor {
+                Cursor::Descend(link) => match link.take() {
+                    None => self.cursor = Cursor::Ascend(None),
+                    Some(mut node) => {
+                        let left = node.2.take();
+                        self.stack.push(Frame::Left(node));
+                        self.cursor = Cursor::Descend(left);
+                    }
+                },
+                Cursor::Ascend(done) => match self.stack.last_mut() {
+                    None => {
+                        self.bst.0 = done.take();
+                        self.cursor = Cursor::Finished;
+                        return None;
+                    }
+                    Some(Frame::Left(node)) => {
+                        node.2 = done.take();
+                        self.cursor = Cursor::Descend(node.3.take());
+                        // The node already waits for its right subtree while
+                        // `pred` looks at it.
+                        let Some(Frame::Left(node)) = self.stack.pop() else {
+                            unreachable!()
+                        };
+                        self.stack.push(Frame::Right(node));
+                        let Some(Frame::Right(node)) = self.stack.last_mut() else {
+                            unreachable!()
+                        };
+                        let BSTNode(k, v, _, _) = &mut **node;
+                        if (self.pred)(k, v) {
+                            let Some(Frame::Right(node)) = self.stack.pop() else {
+                                unreachable!()
+                            };
+                            let BSTNode(k, v, left, _) = *node;
+                            self.stack.push(Frame::Extracted(left));
+                            return Some((k, v));
+                        }
+                    }
+                    Some(Frame::Right(node)) => {
+                        node.3 = done.take();
+                        let Some(Frame::Right(node)) = self.stack.pop() else {
+                            unreachable!()
+                        };
+                        self.cursor = Cursor::Ascend(Some(node));
+                    }
+                    Some(Frame::Extracted(left)) => {
+                        BSTNode::join(left, done.take());
+                        let Some(Frame::Extracted(left)) = self.stack.pop() else {
+                            unreachable!()
+                        };
+                        self.cursor = Cursor::Ascend(left);
+                    }
+                },
+                Cursor::Finished => return None,
+            }
+        }
+    }
+}
+
+impl<'a, K, V, F> Drop for ExtractIf<'a, K, V, F> {
+    fn drop(&mut self) {
+        let mut done = match std::mem::replace(&mut self.cursor, Cursor::Finished) {
+            // `next` has returned `None` and put the tree back together.
+            Cursor::Finished if self.stack.is_empty() => return,
+            Cursor::Finished => None,
+            Cursor::Descend(link) | Cursor::Ascend(link) => link,
+        };
+        while let Some(frame) = self.stack.pop() {
+            match frame {
+                Frame::Left(mut node) => {
+                    node.2 = done;
+                    done = Some(node);
+                }
+                Frame::Right(mut node) => {
+                    node.3 = done;
+                    done = Some(node);
+                }
+                Frame::Extracted(mut left) => {
+                    BSTNode::join(&mut left, done);
+                    done = left;
                 }
             }
         }
+        self.bst.0 = done;
     }
+}
 
-    pub fn find<'a, 'b>(&'a self, key: &'b K) -> Option<&'a V> {
-        let mut current = self;
-        loop {
-            if current.is_empty() {
-                return None;
-            } else {
-                let BSTNode::Node(k, v, left, right) = current else { unreachable!() };
-                if *k == *key {
-                    return Some(v);
-                } else if *k < *key {
-                    current = right.as_ref();
-                } else {
-                    current = left.as_ref();
+impl<K, V> BST<K, V> {
+    /// Returns an iterator over all entries in ascending key order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter { stack: Vec::new() };
+        iter.push_left(&self.0);
+        iter
+    }
+}
+
+/// Iterator returned by [`BST::iter`].
+pub struct Iter<'a, K, V> {
+    stack: Vec<&'a BSTNode<K, V>>,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left(&mut self, mut link: &'a Link<K, V>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = &node.2;
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let BSTNode(k, v, _, right) = self.stack.pop()?;
+        self.push_left(right);
+        Some((k, v))
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a BST<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Display, V> BST<K, V> {
+    /// Renders the tree as an indented text tree with one `key` line per
+    /// node. Subtrees below `max_depth` (the root has depth `0`) and all
+    /// nodes after the first `max_nodes` in pre-order are shown as `...`.
+    pub fn to_ascii(&self, max_depth: usize, max_nodes: usize) -> String {
+        ascii::render(&self.0, max_depth, max_nodes, |link| {
+            let BSTNode(k, _, left, right) = link.as_deref()?;
+            Some((k.to_string(), left, right))
+        })
+    }
+}
+
+impl<K: Display, V: Display> BST<K, V> {
+    /// Writes the tree in Graphviz DOT format. Empty children are drawn as
+    /// small grey dots.
+    pub fn to_dot<T: Write>(&self, writer: T) -> io::Result<()> {
+        self.to_dot_with_max_depth(writer, usize::MAX)
+    }
+
+    /// Like [`BST::to_dot`], but replaces every subtree below `max_depth`
+    /// by a `...` node. The root has depth `0`.
+    pub fn to_dot_with_max_depth<T: Write>(&self, writer: T, max_depth: usize) -> io::Result<()> {
+        let mut dot = Dot::begin(writer)?;
+        let mut stack = vec![(&self.0, 0, None)];
+        while let Some((node, depth, parent)) = stack.pop() {
+            match node.as_deref() {
+                None => dot.empty(parent)?,
+                Some(_) if depth > max_depth => dot.cut(parent)?,
+                Some(BSTNode(k, v, left, right)) => {
+                    let id = dot.node(parent, &[format!("{}: {}", k, v)])?;
+                    stack.push((right, depth + 1, Some(id)));
+                    stack.push((left, depth + 1, Some(id)));
                 }
             }
         }
+        dot.end()
+    }
+}
+
+impl<K: PartialEq, V: PartialEq> PartialEq for BST<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Eq, V: Eq> Eq for BST<K, V> {}
+
+impl<K: PartialOrd, V: PartialOrd> PartialOrd for BST<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<K: Ord, V: Ord> Ord for BST<K, V> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<K: Hash, V: Hash> Hash for BST<K, V> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut len = 0;
+        for entry in self {
+            entry.hash(state);
+            len += 1;
+        }
+        state.write_usize(len);
+    }
+}
+
+impl<K: Ord, V> Index<&K> for BST<K, V> {
+    type Output = V;
+
+    fn index(&self, key: &K) -> &V {
+        self.find(key).expect("no entry found for key")
+    }
+}
+
+/// A subtree, `None` if it is empty.
+type Link<K, V> = Option<Box<BSTNode<K, V>>>;
+
+/// Key, value, left and right subtree.
+struct BSTNode<K, V>(K, V, Link<K, V>, Link<K, V>);
+
+impl<K: Ord, V> BSTNode<K, V> {
+    fn insert(link: &mut Link<K, V>, key: K, value: V) {
+        let mut current = link;
+        // Comparing before borrowing the node mutably lets the borrow
+        // checker see that `current` is free again once the loop ends.
+        while let Some(ordering) = current.as_ref().map(|node| key.cmp(&node.0)) {
+            let node = current.as_mut().unwrap();
+            current = match ordering {
+                Ordering::Less => &mut node.2,
+                Ordering::Greater => &mut node.3,
+                Ordering::Equal => return,
+            };
+        }
+        *current = Some(Box::new(BSTNode(key, value, None, None)));
+    }
+
+    fn find<'a>(mut link: &'a Link<K, V>, key: &K) -> Option<&'a V> {
+        while let Some(BSTNode(k, v, left, right)) = link.as_deref() {
+            link = match key.cmp(k) {
+                Ordering::Less => left,
+                Ordering::Greater => right,
+                Ordering::Equal => return Some(v),
+            };
+        }
+        None
     }
 }
 
 impl<K, V> BSTNode<K, V> {
-    fn is_empty(&self) -> bool {
-        match self {
-            BSTNode::Empty => true,
-            _ => false,
+    /// Joins `link` with `right`, whose keys must all be greater than the
+    /// keys in `link`. The smallest node of `right` becomes the new root.
+    fn join(link: &mut Link<K, V>, mut right: Link<K, V>) {
+        if link.is_none() {
+            *link = right;
+            return;
+        }
+        let Some(mut root) = Self::pop_min(&mut right) else { return };
+        root.2 = link.take();
+        root.3 = right;
+        *link = Some(root);
+    }
+
+    /// Unlinks the smallest node of the subtree, if there is one, and
+    /// returns it with both children empty.
+    fn pop_min(link: &mut Link<K, V>) -> Option<Box<BSTNode<K, V>>> {
+        let mut current = link;
+        while current.as_ref().is_some_and(|node| node.2.is_some()) {
+            current = &mut current.as_mut().unwrap().2;
+        }
+        let mut node = current.take()?;
+        *current = node.3.take();
+        Some(node)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::BST;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::{BTreeMap, VecDeque};
+
+    impl<K: Serialize, V: Serialize> Serialize for BST<K, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(self.iter())
+        }
+    }
+
+    impl<'de, K, V> Deserialize<'de> for BST<K, V>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+    {
+        /// Reads the entries of an ordered map. They are inserted median
+        /// first, so a serialized tree does not come back as a linked list
+        /// just because the map is sorted.
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let mut entries: Vec<_> = BTreeMap::<K, V>::deserialize(deserializer)?
+                .into_iter()
+                .map(Some)
+                .collect();
+            let mut bst = BST::new();
+            let mut ranges = VecDeque::from([(0, entries.len())]);
+            while let Some((lo, hi)) = ranges.pop_front() {
+                if lo == hi {
+                    continue;
+                }
+                let mid = lo + (hi - lo) / 2;
+                let (key, value) = entries[mid].take().unwrap();
+                bst.insert(key, value);
+                ranges.push_back((lo, mid));
+                ranges.push_back((mid + 1, hi));
+            }
+            Ok(bst)
         }
     }
 }
@@ -0,0 +1,62 @@
+//! Copying trees of boxed nodes without recursion, for the trees whose
+//! height is not bounded.
+
+type Link<N> = Option<Box<N>>;
+
+/// Copies the tree below `root` bottom-up with an explicit stack, so that a
+/// degenerate tree cannot overflow the call stack. `copy` copies a single
+/// node without its children, `children` and `children_mut` return the two
+/// subtrees of a node.
+pub(crate) fn clone_tree<N>(
+    root: &Link<N>,
+    copy: impl Fn(&N) -> N,
+    children: fn(&N) -> (&Link<N>, &Link<N>),
+    children_mut: fn(&mut N) -> (&mut Link<N>, &mut Link<N>),
+) -> Link<N> {
+    enum Visit<'a, N> {
+        Enter(&'a Link<N>),
+        Exit(&'a N),
+    }
+
+    let mut visits = vec![Visit::Enter(root)];
+    // Finished subtrees, freed without recursion if `copy` panics.
+    let mut built = Built {
+        links: Vec::new(),
+        children_mut,
+    };
+    while let Some(visit) = visits.pop() {
+        match visit {
+            Visit::Enter(None) => built.links.push(None),
+            Visit::Enter(Some(node)) => {
+                let (left, right) = children(node);
+                visits.push(Visit::Exit(node));
+                visits.push(Visit::Enter(right));
+                visits.push(Visit::Enter(left));
+            }
+            Visit::Exit(node) => {
+                let mut node = Box::new(copy(node));
+                let (left, right) = children_mut(&mut node);
+                *right = built.links.pop().unwrap();
+                *left = built.links.pop().unwrap();
+                built.links.push(Some(node));
+            }
+        }
+    }
+    built.links.pop().unwrap()
+}
+
+struct Built<N> {
+    links: Vec<Link<N>>,
+    children_mut: fn(&mut N) -> (&mut Link<N>, &mut Link<N>),
+}
+
+impl<N> Drop for Built<N> {
+    fn drop(&mut self) {
+        let mut stack: Vec<Box<N>> = self.links.drain(..).flatten().collect();
+        while let Some(mut node) = stack.pop() {
+            let (left, right) = (self.children_mut)(&mut node);
+            stack.extend(left.take());
+            stack.extend(right.take());
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! Graphviz DOT output shared by all trees.
+
+use std::io::{self, Write};
+
+/// Writes a `digraph` node by node. Nodes are numbered in the order they
+/// are written, and every node is connected to its parent as it is written,
+/// so the left child must be written before the right one.
+pub(crate) struct Dot<W: Write> {
+    out: W,
+    next_id: usize,
+}
+
+impl<W: Write> Dot<W> {
+    pub fn begin(mut out: W) -> io::Result<Self> {
+        writeln!(out, "digraph tree {{")?;
+        writeln!(out, "    graph [ordering=out];")?;
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];")?;
+        Ok(Self { out, next_id: 0 })
+    }
+
+    /// Writes a node with one label line per entry of `lines` and returns
+    /// its id.
+    pub fn node(&mut self, parent: Option<usize>, lines: &[String]) -> io::Result<usize> {
+        let label: Vec<_> = lines.iter().map(|line| escape(line)).collect();
+        let id = self.next(parent)?;
+        writeln!(self.out, "    n{} [label=\"{}\"];", id, label.join("\\n"))?;
+        Ok(id)
+    }
+
+    /// Writes an empty child as a small grey dot.
+    pub fn empty(&mut self, parent: Option<usize>) -> io::Result<()> {
+        let id = self.next(parent)?;
+        writeln!(self.out, "    n{} [shape=point, color=gray];", id)
+    }
+
+    /// Writes a placeholder for a subtree below the depth limit.
+    pub fn cut(&mut self, parent: Option<usize>) -> io::Result<()> {
+        let id = self.next(parent)?;
+        writeln!(self.out, "    n{} [label=\"...\", shape=plaintext];", id)
+    }
+
+    pub fn end(mut self) -> io::Result<()> {
+        writeln!(self.out, "}}")?;
+        self.out.flush()
+    }
+
+    fn next(&mut self, parent: Option<usize>) -> io::Result<usize> {
+        let id = self.next_id;
+        self.next_id += 1;
+        if let Some(parent) = parent {
+            writeln!(self.out, "    n{} -> n{};", parent, id)?;
+        }
+        Ok(id)
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,267 @@
+//! Operation sequences decoded from arbitrary bytes, shared by the
+//! cargo-fuzz target in `fuzz/` and the `fuzz_replay` binary.
+//!
+//! [`run`] applies the decoded operations to `BST`, `AVL`, `RBTree`,
+//! `Splay`, `Treap`, `treap::Treap`, `treap_vec::Treap`, `treap_ptr::Treap` and
+//! `treap_soa::Treap` and panics as soon as a result differs from a
+//! `BTreeMap` model, a tree fails its `validate` check, or two treaps end up
+//! with different shapes.
+
+use std::collections::BTreeMap;
+
+use crate::treap_vec::snapshot::SnapshotView;
+use crate::{avl::AVL, bst::BST, rbtree::RBTree, splay::Splay};
+use crate::{treap, treap_ptr, treap_soa, treap_vec, Treap};
+
+#[derive(Debug)]
+enum Op {
+    Insert { key: u8, weight: i8, value: u8 },
+    Find(u8),
+    Remove(u8),
+    Retain { modulus: u8, rem: u8 },
+    ExtractIf { modulus: u8, rem: u8, limit: usize },
+    Iter,
+    Snapshot,
+}
+
+/// Turns bytes into operations. Every byte sequence decodes to something;
+/// missing operand bytes at the end of the input read as `0`.
+fn decode(mut data: &[u8]) -> Vec<Op> {
+    let mut byte = move || -> Option<u8> {
+        let (&b, rest) = data.split_first()?;
+        data = rest;
+        Some(b)
+    };
+    let mut ops = Vec::new();
+    while let Some(code) = byte() {
+        let mut operand = || byte().unwrap_or(0);
+        let op = match code % 9 {
+            0..=2 => Op::Insert {
+                key: operand(),
+                weight: operand() as i8,
+                value: operand(),
+            },
+            3 | 4 => Op::Find(operand()),
+            5 => Op::Remove(operand()),
+            6 => {
+                let modulus = operand() % 7 + 2;
+                Op::Retain {
+                    modulus,
+                    rem: operand() % modulus,
+                }
+            }
+            7 => {
+                let modulus = operand() % 7 + 1;
+                Op::ExtractIf {
+                    modulus,
+                    rem: operand() % modulus,
+                    limit: usize::from(operand() % 16),
+                }
+            }
+            _ if operand() % 2 == 0 => Op::Iter,
+            _ => Op::Snapshot,
+        };
+        ops.push(op);
+    }
+    ops
+}
+
+#[derive(Default)]
+struct State {
+    model: BTreeMap<u8, u8>,
+    // `treap::Treap` cannot remove entries and only sees the inserts.
+    insert_model: BTreeMap<u8, u8>,
+    bst: BST<u8, u8>,
+    avl: AVL<u8, u8>,
+    rbtree: RBTree<u8, u8>,
+    splay: Splay<u8, u8>,
+    treap: treap::Treap<u8, i8, u8>,
+    treap_vec: treap_vec::Treap<u8, i8, u8>,
+    treap_rec: Treap<u8, i8, u8>,
+    treap_ptr: treap_ptr::Treap<u8, i8, u8>,
+    treap_soa: treap_soa::Treap<u8, i8, u8>,
+    removed_any: bool,
+}
+
+impl State {
+    fn apply(&mut self, op: &Op) {
+        match *op {
+            Op::Insert { key, weight, value } => {
+                self.model.entry(key).or_insert(value);
+                self.insert_model.entry(key).or_insert(value);
+                self.bst.insert(key, value);
+                self.avl.insert(key, value);
+                self.rbtree.insert(key, value);
+                self.splay.insert(key, value);
+                self.treap.insert(key, weight, value);
+                self.treap_vec.insert(key, weight, value);
+                self.treap_rec.insert(key, weight, value);
+                self.treap_ptr.insert(key, weight, value);
+                self.treap_soa.insert(key, weight, value);
+                self.check_invariants();
+            }
+            Op::Find(key) => {
+                let expected = self.model.get(&key);
+                assert_eq!(self.bst.find(&key), expected, "BST::find({})", key);
+                assert_eq!(self.avl.find(&key), expected, "AVL::find({})", key);
+                assert_eq!(self.rbtree.find(&key), expected, "RBTree::find({})", key);
+                assert_eq!(self.splay.find(&key), expected, "Splay::find({})", key);
+                assert_eq!(self.treap_vec.find(&key), expected, "treap_vec::find({})", key);
+                assert_eq!(self.treap_rec.find(&key), expected, "Treap::find({})", key);
+                assert_eq!(self.treap_ptr.find(&key), expected, "treap_ptr::find({})", key);
+                assert_eq!(self.treap_soa.find(&key), expected, "treap_soa::find({})", key);
+                let found = self.treap.find_cloned(&key);
+                let inserted = self.insert_model.get(&key).copied();
+                assert_eq!(found, inserted, "treap::Treap::find({})", key);
+            }
+            Op::Remove(key) => {
+                let expected = self.model.remove(&key);
+                let removed = self.avl.remove(&key);
+                assert_eq!(removed, expected, "AVL::remove({})", key);
+                let removed = self.rbtree.remove(&key);
+                assert_eq!(removed, expected, "RBTree::remove({})", key);
+                let removed = self.splay.remove(&key);
+                assert_eq!(removed, expected, "Splay::remove({})", key);
+                let removed = self.treap_vec.remove(&key);
+                assert_eq!(removed, expected, "treap_vec::Treap::remove({})", key);
+                let removed = self.treap_ptr.remove(&key);
+                assert_eq!(removed, expected, "treap_ptr::Treap::remove({})", key);
+                let removed = self.treap_soa.remove(&key);
+                assert_eq!(removed, expected, "treap_soa::Treap::remove({})", key);
+                // `BST` and `Treap` remove single keys through `extract_if`.
+                let removed = self.bst.extract_if(|k, _| *k == key).next();
+                assert_eq!(removed.map(|(_, v)| v), expected, "BST::remove({})", key);
+                let removed = self.treap_rec.extract_if(|k, _| *k == key).next();
+                assert_eq!(removed.map(|(_, v)| v), expected, "Treap::remove({})", key);
+                self.removed_any = true;
+                self.check_invariants();
+            }
+            Op::Retain { modulus, rem } => {
+                let keep = |k: &u8, _: &mut u8| k % modulus != rem;
+                self.model.retain(keep);
+                self.bst.retain(keep);
+                self.avl.retain(keep);
+                self.rbtree.retain(keep);
+                self.splay.retain(keep);
+                self.treap_vec.retain(keep);
+                self.treap_rec.retain(keep);
+                self.treap_soa.retain(keep);
+                // `treap_ptr::Treap` removes the same keys one by one.
+                let keys: Vec<u8> = self.treap_ptr.iter().map(|(k, _)| *k).collect();
+                for key in keys.iter().filter(|k| *k % modulus == rem) {
+                    self.treap_ptr.remove(key);
+                }
+                self.removed_any = true;
+                self.check_invariants();
+            }
+            Op::ExtractIf {
+                modulus,
+                rem,
+                limit,
+            } => {
+                let pred = |k: &u8, _: &mut u8| k % modulus == rem;
+                let expected: Vec<_> = self
+                    .model
+                    .iter()
+                    .filter(|(k, _)| *k % modulus == rem)
+                    .map(|(k, v)| (*k, *v))
+                    .take(limit)
+                    .collect();
+                for (k, _) in &expected {
+                    self.model.remove(k);
+                    self.treap_ptr.remove(k);
+                }
+                let bst: Vec<_> = self.bst.extract_if(pred).take(limit).collect();
+                assert_eq!(bst, expected, "BST::extract_if");
+                let avl: Vec<_> = self.avl.extract_if(pred).take(limit).collect();
+                assert_eq!(avl, expected, "AVL::extract_if");
+                let rbtree: Vec<_> = self.rbtree.extract_if(pred).take(limit).collect();
+                assert_eq!(rbtree, expected, "RBTree::extract_if");
+                let splay: Vec<_> = self.splay.extract_if(pred).take(limit).collect();
+                assert_eq!(splay, expected, "Splay::extract_if");
+                let treap_vec: Vec<_> = self.treap_vec.extract_if(pred).take(limit).collect();
+                assert_eq!(treap_vec, expected, "treap_vec::Treap::extract_if");
+                let treap_rec: Vec<_> = self.treap_rec.extract_if(pred).take(limit).collect();
+                assert_eq!(treap_rec, expected, "Treap::extract_if");
+                let treap_soa: Vec<_> = self.treap_soa.extract_if(pred).take(limit).collect();
+                assert_eq!(treap_soa, expected, "treap_soa::Treap::extract_if");
+                self.removed_any = true;
+                self.check_invariants();
+            }
+            Op::Iter => {
+                let expected: Vec<_> = self.model.iter().collect();
+                assert_eq!(self.bst.iter().collect::<Vec<_>>(), expected, "BST::iter");
+                assert_eq!(self.avl.iter().collect::<Vec<_>>(), expected, "AVL::iter");
+                let rbtree: Vec<_> = self.rbtree.iter().collect();
+                assert_eq!(rbtree, expected, "RBTree::iter");
+                assert_eq!(self.splay.iter().collect::<Vec<_>>(), expected, "Splay::iter");
+                let treap_vec: Vec<_> = self.treap_vec.iter().collect();
+                assert_eq!(treap_vec, expected, "treap_vec::Treap::iter");
+                let treap_rec: Vec<_> = self.treap_rec.iter().collect();
+                assert_eq!(treap_rec, expected, "Treap::iter");
+                let treap_ptr: Vec<_> = self.treap_ptr.iter().collect();
+                assert_eq!(treap_ptr, expected, "treap_ptr::Treap::iter");
+                let treap_soa: Vec<_> = self.treap_soa.iter().collect();
+                assert_eq!(treap_soa, expected, "treap_soa::Treap::iter");
+            }
+            Op::Snapshot => {
+                let mut bytes = Vec::new();
+                self.treap_vec.save_to(&mut bytes).unwrap();
+                let loaded = treap_vec::Treap::<u8, i8, u8>::load_from(&bytes[..]).unwrap();
+                assert_eq!(loaded.shape(), self.treap_vec.shape(), "snapshot shape");
+                let view = SnapshotView::<u8, i8, u8>::new(&bytes).unwrap();
+                for (key, value) in &self.model {
+                    assert_eq!(view.find(key), Some(*value), "SnapshotView::find({})", key);
+                }
+                self.treap_vec = loaded;
+                self.check_invariants();
+            }
+        }
+    }
+
+    fn check_invariants(&self) {
+        if let Err(err) = self.bst.validate() {
+            panic!("BST: {}", err);
+        }
+        if let Err(err) = self.avl.validate() {
+            panic!("AVL: {}", err);
+        }
+        if let Err(err) = self.rbtree.validate() {
+            panic!("RBTree: {}", err);
+        }
+        if let Err(err) = self.splay.validate() {
+            panic!("Splay: {}", err);
+        }
+        if let Err(err) = self.treap.validate() {
+            panic!("treap::Treap: {}", err);
+        }
+        if let Err(err) = self.treap_vec.validate() {
+            panic!("treap_vec::Treap: {}", err);
+        }
+        if let Err(err) = self.treap_rec.validate() {
+            panic!("Treap: {}", err);
+        }
+        if let Err(err) = self.treap_ptr.validate() {
+            panic!("treap_ptr::Treap: {}", err);
+        }
+        if let Err(err) = self.treap_soa.validate() {
+            panic!("treap_soa::Treap: {}", err);
+        }
+        let shape = self.treap_rec.shape();
+        assert_eq!(self.treap_vec.shape(), shape, "treap_vec::Treap shape");
+        assert_eq!(self.treap_ptr.shape(), shape, "treap_ptr::Treap shape");
+        assert_eq!(self.treap_soa.shape(), shape, "treap_soa::Treap shape");
+        if !self.removed_any {
+            assert_eq!(self.treap.shape(), shape, "treap::Treap shape");
+        }
+    }
+}
+
+/// Decodes `data` into operations and checks every tree while applying
+/// them. Panics on the first mismatch.
+pub fn run(data: &[u8]) {
+    let mut state = State::default();
+    for op in decode(data) {
+        state.apply(&op);
+    }
+}
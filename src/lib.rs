@@ -1,226 +1,639 @@
+mod ascii;
+pub mod avl;
 pub mod bst;
+mod boxed_tree;
+mod clone_tree;
+mod dot;
+#[doc(hidden)]
+pub mod fuzzing;
+pub mod rbtree;
+pub mod sharded;
+pub mod splay;
+mod stats;
 pub mod treap;
+pub mod treap_arc;
+pub mod treap_ptr;
+pub mod treap_soa;
 pub mod treap_vec;
+pub mod validate;
 
-#[derive(Debug)]
-pub struct Treap<K, W, V>(TreapNode<K, W, V>);
+use std::cmp::Ordering;
+use std::fmt::{self, Debug, Display};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::ops::Index;
+
+use clone_tree::clone_tree;
+use dot::Dot;
+use stats::Depths;
+use validate::{Side, Trail, ValidationError, Violation};
+
+/// Serde wrapper that stores a treap together with its weights.
+///
+/// Plain treaps are (de)serialized as ordered maps and get fresh random
+/// weights when read back. A `Weighted` treap is written as a sequence of
+/// `(key, weight, value)` tuples in pre-order instead. Inserting in that
+/// order never rotates, so reading a `Weighted<Treap>` back restores the
+/// exact shape of the original.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Weighted<T>(pub T);
+
+pub struct Treap<K, W, V>(Link<K, W, V>);
 
 impl<K, W, V> Treap<K, W, V> {
     pub fn new() -> Self {
-        Self(TreapNode::Empty)
+        Self(None)
+    }
+}
+
+impl<K, W, V> Default for Treap<K, W, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, W, V> Drop for Treap<K, W, V> {
+    fn drop(&mut self) {
+        let mut stack: Vec<_> = self.0.take().into_iter().collect();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+        }
+    }
+}
+
+impl<K: Clone, W: Clone, V: Clone> Clone for Treap<K, W, V> {
+    fn clone(&self) -> Self {
+        Self(clone_tree(
+            &self.0,
+            |node| TreapNode {
+                key: node.key.clone(),
+                weight: node.weight.clone(),
+                value: node.value.clone(),
+                left: None,
+                right: None,
+            },
+            |node| (&node.left, &node.right),
+            |node| (&mut node.left, &mut node.right),
+        ))
+    }
+}
+
+impl<K: Debug, W, V: Debug> Debug for Treap<K, W, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
     }
 }
 
 impl<K: Ord, W: Ord, V> Treap<K, W, V> {
     pub fn insert(&mut self, key: K, weight: W, value: V) {
-        self.0.insert(key, weight, value);
+        TreapNode::insert(&mut self.0, key, weight, value);
     }
 }
 
 impl<K: Ord, W, V> Treap<K, W, V> {
-    pub fn find<'a, 'b>(&'a self, key: &'b K) -> Option<&'a V> {
-        self.0.find(key)
+    pub fn find<'a>(&'a self, key: &K) -> Option<&'a V> {
+        TreapNode::find(&self.0, key)
     }
 }
 
-#[derive(Debug)]
-enum TreapNode<K, W, V> {
-    Empty,
-    Node {
-        key: K,
-        weight: W,
-        value: V,
-        left: Box<TreapNode<K, W, V>>,
-        right: Box<TreapNode<K, W, V>>,
-    },
+impl<K, W, V> Treap<K, W, V> {
+    pub fn height(&self) -> usize {
+        self.depths().height()
+    }
+
+    pub fn average_depth(&self) -> f64 {
+        self.depths().average_depth()
+    }
+
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        self.depths().into_histogram()
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.depths().leaves()
+    }
+
+    fn depths(&self) -> Depths {
+        let mut depths = Depths::default();
+        let mut stack = vec![(&self.0, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            let Some(node) = node else { continue };
+            depths.add(depth, node.left.is_none() && node.right.is_none());
+            stack.push((&node.left, depth + 1));
+            stack.push((&node.right, depth + 1));
+        }
+        depths
+    }
 }
 
-impl<K: Ord, W: Ord, V> TreapNode<K, W, V> {
-    pub fn insert(&mut self, key: K, weight: W, value: V) {
-        match self.take() {
-            TreapNode::Empty => {
-                *self = TreapNode::Node {
-                    key,
-                    weight,
-                    value,
-                    left: Box::new(TreapNode::Empty),
-                    right: Box::new(TreapNode::Empty),
+impl<K: Ord, W: Ord, V> Treap<K, W, V> {
+    /// Checks that the keys are in search tree order and that no node has a
+    /// smaller weight than its parent.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut trail = Trail::default();
+        let mut stack = vec![(&self.0, 0, None, None, None, None)];
+        while let Some((node, depth, side, parent_weight, lower, upper)) = stack.pop() {
+            trail.enter(depth, side);
+            let Some(TreapNode {
+                key,
+                weight,
+                left,
+                right,
+                ..
+            }) = node.as_deref() else { continue };
+            if !validate::in_bounds(key, lower, upper) {
+                return Err(trail.error(Violation::KeyOrder));
+            }
+            if parent_weight.is_some_and(|pw| weight < pw) {
+                return Err(trail.error(Violation::HeapOrder));
+            }
+            let child_depth = depth + 1;
+            stack.push((
+                right,
+                child_depth,
+                Some(Side::Right),
+                Some(weight),
+                Some(key),
+                upper,
+            ));
+            stack.push((
+                left,
+                child_depth,
+                Some(Side::Left),
+                Some(weight),
+                lower,
+                Some(key),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<K, W: Ord, V> Treap<K, W, V> {
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.extract_if(|k, v| !f(k, v)).for_each(drop);
+    }
+
+    /// Returns an iterator that removes and yields every entry for which
+    /// `pred` returns `true`, in ascending key order.
+    ///
+    /// Removed nodes are replaced by the merge of their subtrees, so the heap
+    /// property holds without further rotations. If the iterator is dropped
+    /// early, all entries not yet visited are kept.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, W, V, F>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let root = self.0.take();
+        ExtractIf {
+            treap: self,
+            stack: Vec::new(),
+            cursor: Cursor::Descend(root),
+            pred,
+        }
+    }
+}
+
+/// Iterator returned by [`Treap::extract_if`].
+pub struct ExtractIf<'a, K, W: Ord, V, F> {
+    treap: &'a mut Treap<K, W, V>,
+    stack: Vec<Frame<K, W, V>>,
+    cursor: Cursor<K, W, V>,
+    pred: F,
+}
+
+enum Frame<K, W, V> {
+    Left(Box<TreapNode<K, W, V>>),
+    Right(Box<TreapNode<K, W, V>>),
+    Extracted(Link<K, W, V>),
+}
+
+enum Cursor<K, W, V> {
+    Descend(Link<K, W, V>),
+    Ascend(Link<K, W, V>),
+    Finished,
+}
+
+impl<'a, K, W: Ord, V, F> Iterator for ExtractIf<'a, K, W, V, F>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // Every node stays reachable from `stack` or `cursor` while `pred`
+            // or `W::cmp` runs, so that `drop` can link it back in if they
+            // panic.
+            match &mut self.cursor {
+                Cursor::Descend(link) => match link.take() {
+                    None => self.cursor = Cursor::Ascend(None),
+                    Some(mut node) => {
+                        let left = node.left.take();
+                        self.stack.push(Frame::Left(node));
+                        self.cursor = Cursor::Descend(left);
+                    }
+                },
+                Cursor::Ascend(done) => match self.stack.last_mut() {
+                    None => {
+                        self.treap.0 = done.take();
+                        self.cursor = Cursor::Finished;
+                        return None;
+                    }
+                    Some(Frame::Left(node)) => {
+                        node.left = done.take();
+                        self.cursor = Cursor::Descend(node.right.take());
+                        // The node already waits for its right subtree while
+                        // `pred` looks at it.
+                        let Some(Frame::Left(node)) = self.stack.pop() else {
+                            unreachable!()
+                        };
+                        self.stack.push(Frame::Right(node));
+                        let Some(Frame::Right(node)) = self.stack.last_mut() else {
+                            unreachable!()
+                        };
+                        if (self.pred)(&node.key, &mut node.value) {
+                            let Some(Frame::Right(node)) = self.stack.pop() else {
+                                unreachable!()
+                            };
+                            let TreapNode {
+                                key, value, left, ..
+                            } = *node;
+                            self.stack.push(Frame::Extracted(left));
+                            return Some((key, value));
+                        }
+                    }
+                    Some(Frame::Right(node)) => {
+                        node.right = done.take();
+                        let Some(Frame::Right(node)) = self.stack.pop() else {
+                            unreachable!()
+                        };
+                        self.cursor = Cursor::Ascend(Some(node));
+                    }
+                    Some(Frame::Extracted(left)) => {
+                        TreapNode::merge(left, done);
+                        let Some(Frame::Extracted(left)) = self.stack.pop() else {
+                            unreachable!()
+                        };
+                        self.cursor = Cursor::Ascend(left);
+                    }
+                },
+                Cursor::Finished => return None,
+            }
+        }
+    }
+}
+
+impl<'a, K, W: Ord, V, F> Drop for ExtractIf<'a, K, W, V, F> {
+    fn drop(&mut self) {
+        let mut done = match std::mem::replace(&mut self.cursor, Cursor::Finished) {
+            // `next` has returned `None` and put the treap back together.
+            Cursor::Finished if self.stack.is_empty() => return,
+            Cursor::Finished => None,
+            Cursor::Descend(link) | Cursor::Ascend(link) => link,
+        };
+        while let Some(frame) = self.stack.pop() {
+            match frame {
+                Frame::Left(mut node) => {
+                    node.left = done;
+                    done = Some(node);
                 }
+                Frame::Right(mut node) => {
+                    node.right = done;
+                    done = Some(node);
+                }
+                Frame::Extracted(mut left) => {
+                    TreapNode::merge(&mut left, &mut done);
+                    done = left;
+                }
+            }
+        }
+        self.treap.0 = done;
+    }
+}
+
+impl<K, W, V> Treap<K, W, V> {
+    /// Returns an iterator over all entries in ascending key order.
+    pub fn iter(&self) -> Iter<'_, K, W, V> {
+        let mut iter = Iter { stack: Vec::new() };
+        iter.push_left(&self.0);
+        iter
+    }
+}
+
+impl<K, W, V> Treap<K, W, V> {
+    /// Returns the keys and weights in pre-order.
+    ///
+    /// A search tree is determined by its pre-order key sequence, so two
+    /// treaps have the same shape exactly if their `shape()` vectors are
+    /// equal. This holds across the treap implementations of this crate.
+    pub fn shape(&self) -> Vec<(K, W)>
+    where
+        K: Clone,
+        W: Clone,
+    {
+        self.preorder()
+            .map(|(key, weight, _)| (key.clone(), weight.clone()))
+            .collect()
+    }
+
+    fn preorder(&self) -> impl Iterator<Item = (&K, &W, &V)> {
+        let mut stack = vec![&self.0];
+        std::iter::from_fn(move || loop {
+            if let Some(node) = stack.pop()? {
+                stack.push(&node.right);
+                stack.push(&node.left);
+                return Some((&node.key, &node.weight, &node.value));
             }
-            TreapNode::Node {
-                key: ckey,
-                weight: cweight,
-                value: cvalue,
-                mut left,
-                mut right,
-            } => {
-                let should_restore = if key < ckey {
-                    left.insert(key, weight, value);
-                    ShouldRestore::Left
-                } else if ckey < key {
-                    right.insert(key, weight, value);
-                    ShouldRestore::Right
-                } else {
-                    ShouldRestore::No
-                };
-                *self = TreapNode::Node {
-                    key: ckey,
-                    weight: cweight,
-                    value: cvalue,
-                    left,
-                    right,
-                };
-                match should_restore {
-                    ShouldRestore::Left => self.restore_left(),
-                    ShouldRestore::Right => self.restore_right(),
-                    ShouldRestore::No => {}
+        })
+    }
+}
+
+/// Iterator returned by [`Treap::iter`].
+pub struct Iter<'a, K, W, V> {
+    stack: Vec<&'a TreapNode<K, W, V>>,
+}
+
+impl<'a, K, W, V> Iter<'a, K, W, V> {
+    fn push_left(&mut self, mut link: &'a Link<K, W, V>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a, K, W, V> Iterator for Iter<'a, K, W, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left(&node.right);
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<'a, K, W, V> IntoIterator for &'a Treap<K, W, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, W, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Display, W: Display, V> Treap<K, W, V> {
+    /// Renders the treap as an indented text tree with one `key:weight` line per
+    /// node. Subtrees below `max_depth` (the root has depth `0`) and all
+    /// nodes after the first `max_nodes` in pre-order are shown as `...`.
+    pub fn to_ascii(&self, max_depth: usize, max_nodes: usize) -> String {
+        ascii::render(&self.0, max_depth, max_nodes, |link| {
+            let node = link.as_ref()?;
+            Some((format!("{}:{}", node.key, node.weight), &node.left, &node.right))
+        })
+    }
+}
+
+impl<K: Display, W: Display, V: Display> Treap<K, W, V> {
+    /// Writes the treap in Graphviz DOT format. Empty children are drawn as
+    /// small grey dots.
+    pub fn to_dot<T: Write>(&self, writer: T) -> io::Result<()> {
+        self.to_dot_with_max_depth(writer, usize::MAX)
+    }
+
+    /// Like [`Treap::to_dot`], but replaces every subtree below `max_depth`
+    /// by a `...` node. The root has depth `0`.
+    pub fn to_dot_with_max_depth<T: Write>(&self, writer: T, max_depth: usize) -> io::Result<()> {
+        let mut dot = Dot::begin(writer)?;
+        let mut stack = vec![(&self.0, 0, None)];
+        while let Some((node, depth, parent)) = stack.pop() {
+            match node {
+                None => dot.empty(parent)?,
+                Some(_) if depth > max_depth => dot.cut(parent)?,
+                Some(node) => {
+                    let lines = [
+                        format!("{}: {}", node.key, node.value),
+                        format!("w = {}", node.weight),
+                    ];
+                    let id = dot.node(parent, &lines)?;
+                    stack.push((&node.right, depth + 1, Some(id)));
+                    stack.push((&node.left, depth + 1, Some(id)));
                 }
             }
         }
+        dot.end()
     }
+}
 
-    fn restore_left(&mut self) {
-        let zweight = self.weight();
-        let xweight = self.left().and_then(Self::weight);
-        let is_resorable = zweight
-            .zip(xweight)
-            .map(|(zw, xw)| xw < zw)
-            .unwrap_or(false);
-        if !is_resorable {
-            return;
+impl<K: PartialEq, W, V: PartialEq> PartialEq for Treap<K, W, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Eq, W, V: Eq> Eq for Treap<K, W, V> {}
+
+impl<K: PartialOrd, W, V: PartialOrd> PartialOrd for Treap<K, W, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<K: Ord, W, V: Ord> Ord for Treap<K, W, V> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<K: Hash, W, V: Hash> Hash for Treap<K, W, V> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut len = 0;
+        for entry in self {
+            entry.hash(state);
+            len += 1;
         }
+        state.write_usize(len);
+    }
+}
 
-        let TreapNode::Node {
-            key: zkey,
-            weight: zweight,
-            value: zvalue,
-            left: mut zleft,
-            right: zright
-        } = self.take() else { unreachable!() };
-        let TreapNode::Node {
-            key: xkey,
-            weight: xweight,
-            value: xvalue,
-            left: xleft,
-            right: xright
-        } = zleft.take() else { unreachable!() };
-        let z = TreapNode::Node {
-            key: zkey,
-            weight: zweight,
-            value: zvalue,
-            left: xright,
-            right: zright,
-        };
-        let x = TreapNode::Node {
-            key: xkey,
-            weight: xweight,
-            value: xvalue,
-            left: xleft,
-            right: Box::new(z),
+impl<K: Ord, W, V> Index<&K> for Treap<K, W, V> {
+    type Output = V;
+
+    fn index(&self, key: &K) -> &V {
+        self.find(key).expect("no entry found for key")
+    }
+}
+
+/// A subtree, `None` if it is empty.
+type Link<K, W, V> = Option<Box<TreapNode<K, W, V>>>;
+
+struct TreapNode<K, W, V> {
+    key: K,
+    weight: W,
+    value: V,
+    left: Link<K, W, V>,
+    right: Link<K, W, V>,
+}
+
+impl<K: Ord, W: Ord, V> TreapNode<K, W, V> {
+    fn insert(link: &mut Link<K, W, V>, key: K, weight: W, value: V) {
+        let Some(node) = link else {
+            *link = Some(Box::new(TreapNode {
+                key,
+                weight,
+                value,
+                left: None,
+                right: None,
+            }));
+            return;
         };
-        *self = x;
+        match key.cmp(&node.key) {
+            Ordering::Less => {
+                Self::insert(&mut node.left, key, weight, value);
+                Self::restore_left(link);
+            }
+            Ordering::Greater => {
+                Self::insert(&mut node.right, key, weight, value);
+                Self::restore_right(link);
+            }
+            Ordering::Equal => {}
+        }
     }
 
-    fn restore_right(&mut self) {
-        let zweight = self.weight();
-        let xweight = self.right().and_then(Self::weight);
-        let is_resorable = zweight
-            .zip(xweight)
-            .map(|(zw, xw)| xw < zw)
-            .unwrap_or(false);
-        if !is_resorable {
+    /// Rotates the left child of the subtree root above it if the child is
+    /// lighter. Only the links are moved, the nodes stay where they are.
+    fn restore_left(link: &mut Link<K, W, V>) {
+        let Some(z) = link else { return };
+        if !matches!(&z.left, Some(x) if x.weight < z.weight) {
             return;
         }
+        let mut x = z.left.take().unwrap();
+        z.left = x.right.take();
+        x.right = link.take();
+        *link = Some(x);
+    }
 
-        let TreapNode::Node {
-            key: zkey,
-            weight: zweight,
-            value: zvalue,
-            left: zleft,
-            right: mut zright
-        } = self.take() else { unreachable!() };
-        let TreapNode::Node {
-            key: xkey,
-            weight: xweight,
-            value: xvalue,
-            left: xleft,
-            right: xright
-        } = zright.take() else { unreachable!() };
-        let z = TreapNode::Node {
-            key: zkey,
-            weight: zweight,
-            value: zvalue,
-            left: zleft,
-            right: xleft,
-        };
-        let x = TreapNode::Node {
-            key: xkey,
-            weight: xweight,
-            value: xvalue,
-            left: Box::new(z),
-            right: xright,
-        };
-        *self = x;
+    /// Mirror image of [`TreapNode::restore_left`].
+    fn restore_right(link: &mut Link<K, W, V>) {
+        let Some(z) = link else { return };
+        if !matches!(&z.right, Some(x) if x.weight < z.weight) {
+            return;
+        }
+        let mut x = z.right.take().unwrap();
+        z.right = x.left.take();
+        x.left = link.take();
+        *link = Some(x);
     }
 }
 
-impl<K: Ord, W, V> TreapNode<K, W, V> {
-    pub fn find<'a, 'b>(&'a self, search_key: &'b K) -> Option<&'a V> {
-        let mut current: &TreapNode<K, W, V> = self;
-        loop {
-            match current {
-                TreapNode::Empty => {
-                    return None;
-                }
-                TreapNode::Node {
-                    key,
-                    value,
-                    left,
-                    right,
-                    ..
-                } => {
-                    if *key == *search_key {
-                        return Some(value);
-                    } else if *key < *search_key {
-                        current = right.as_ref();
-                    } else {
-                        current = left.as_ref()
-                    }
-                }
+impl<K, W: Ord, V> TreapNode<K, W, V> {
+    /// Merges `link` with `other`, whose keys must all be greater than the
+    /// keys in `link`, and leaves `other` empty. The root with the smaller
+    /// weight stays on top. All weights are compared before anything is
+    /// relinked, so a panicking `W::cmp` leaves both trees as they were.
+    fn merge(link: &mut Link<K, W, V>, other: &mut Link<K, W, V>) {
+        let mut other_on_top = Vec::new();
+        let (mut a, mut b) = (&*link, &*other);
+        while let (Some(x), Some(y)) = (a, b) {
+            let take_other = y.weight < x.weight;
+            other_on_top.push(take_other);
+            if take_other {
+                b = &y.left;
+            } else {
+                a = &x.right;
             }
         }
+
+        let (mut a, mut b) = (link.take(), other.take());
+        let mut slot = link;
+        for take_other in other_on_top {
+            slot = if take_other {
+                let mut node = b.unwrap();
+                b = node.left.take();
+                &mut slot.insert(node).left
+            } else {
+                let mut node = a.unwrap();
+                a = node.right.take();
+                &mut slot.insert(node).right
+            };
+        }
+        *slot = a.or(b);
+    }
+}
+
+impl<K: Ord, W, V> TreapNode<K, W, V> {
+    fn find<'a>(mut link: &'a Link<K, W, V>, search_key: &K) -> Option<&'a V> {
+        while let Some(node) = link {
+            link = match search_key.cmp(&node.key) {
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+                Ordering::Equal => return Some(&node.value),
+            };
+        }
+        None
     }
 }
 
-impl<K, W, V> TreapNode<K, W, V> {
-    fn take(&mut self) -> Self {
-        std::mem::replace(self, TreapNode::Empty)
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::{Treap, Weighted};
+    use rand::distributions::{Distribution, Standard};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    impl<K: Serialize, W, V: Serialize> Serialize for Treap<K, W, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(self.iter())
+        }
     }
 
-    fn weight(&self) -> Option<&W> {
-        match self {
-            TreapNode::Node { weight, .. } => Some(weight),
-            TreapNode::Empty => None,
+    impl<'de, K, W, V> Deserialize<'de> for Treap<K, W, V>
+    where
+        K: Deserialize<'de> + Ord,
+        W: Ord,
+        V: Deserialize<'de>,
+        Standard: Distribution<W>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let entries = BTreeMap::<K, V>::deserialize(deserializer)?;
+            let mut treap = Treap::new();
+            for (key, value) in entries {
+                treap.insert(key, rand::random(), value);
+            }
+            Ok(treap)
         }
     }
 
-    fn left(&self) -> Option<&TreapNode<K, W, V>> {
-        match self {
-            TreapNode::Node { left, .. } => Some(left.as_ref()),
-            TreapNode::Empty => None,
+    impl<K: Serialize, W: Serialize, V: Serialize> Serialize for Weighted<&Treap<K, W, V>> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.0.preorder())
         }
     }
 
-    fn right(&self) -> Option<&TreapNode<K, W, V>> {
-        match self {
-            TreapNode::Node { right, .. } => Some(right.as_ref()),
-            TreapNode::Empty => None,
+    impl<K: Serialize, W: Serialize, V: Serialize> Serialize for Weighted<Treap<K, W, V>> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Weighted(&self.0).serialize(serializer)
         }
     }
-}
 
-enum ShouldRestore {
-    No,
-    Left,
-    Right,
+    impl<'de, K, W, V> Deserialize<'de> for Weighted<Treap<K, W, V>>
+    where
+        K: Deserialize<'de> + Ord,
+        W: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let entries = Vec::<(K, W, V)>::deserialize(deserializer)?;
+            let mut treap = Treap::new();
+            for (key, weight, value) in entries {
+                treap.insert(key, weight, value);
+            }
+            Ok(Weighted(treap))
+        }
+    }
 }
@@ -1,22 +1,43 @@
+#[allow(dead_code)]
 mod wordlists;
 
-use std::io::{self, prelude::*};
+use std::fs::File;
+use std::io::{self, prelude::*, BufWriter};
 use std::time::Instant;
-use treap_rust::{bst::BST, treap::Treap, Treap as TreapRec};
+use treap_rust::treap_soa::Treap as TreapSoa;
+use treap_rust::treap_vec::Treap as TreapVec;
+use treap_rust::{avl::AVL, bst::BST, treap::Treap, Treap as TreapRec};
 use wordlists::*;
 
+const PRINT_MAX_DEPTH: usize = 5;
+const PRINT_MAX_NODES: usize = 100;
+const HISTOGRAM_MAX_DEPTHS: usize = 40;
+
 fn main() {
     let rng = fastrand::Rng::new();
-    let mut treap = treap_rust::treap_vec::Treap::<i32, i32, i32>::new();
-    let mut treap_rec = TreapRec::<i32, i32, i32>::new();
+    let mut treap = Treap::<String, i32, String>::new();
+    let mut treap_rec = TreapRec::<String, i32, String>::new();
+    let mut treap_vec = TreapVec::<String, i32, String>::new();
+    let mut treap_soa = TreapSoa::<String, i32, String>::new();
     let mut bst = BST::<String, String>::new();
+    let mut avl = AVL::<String, String>::new();
     let mut timer = TimingContext::new();
 
-    let mut words_iter = 1..100_000_000;
+    // `cargo run --release -- sorted` lädt die Wörter sortiert.
+    let words = match std::env::args().nth(1).as_deref() {
+        None | Some("unsorted") => WORDS_UNSORTED,
+        Some("sorted") => WORDS_SORTED,
+        Some(other) => {
+            eprintln!(">> ERR: unknown word list {:?}, expected sorted or unsorted", other);
+            std::process::exit(2);
+        }
+    };
+    let mut words_iter = words.iter();
 
     loop {
-        let command =
-            prompt_user("> Enter a command (insert | find | print | time | load | exit): ");
+        let command = prompt_user(
+            "> Enter a command (insert | find | print | time | load | stats | dot | exit): ",
+        );
         match &command[..] {
             "time" => {
                 eprintln!(">> The next operation will be timed");
@@ -24,48 +45,56 @@ fn main() {
             }
             "load" => {
                 let num = prompt_user("How many to load: ");
-                let mut num = num.parse::<usize>().unwrap();
+                let num = num.parse::<usize>().unwrap();
                 let mut count = 0;
                 let start = Instant::now();
-                while let Some(word) = words_iter.next() {
+                for word in words_iter.by_ref().take(num) {
                     let weight = rng.i32(..);
-                    // let word = word.to_owned();
-                    // let rword: String = word.chars().rev().collect();
-                    treap.insert(word, weight, word);
-                    // treap_rec.insert(word.clone(), weight, word.clone());
-                    // bst.insert(word.clone(), word.clone());
-                    // treap.insert(rword.clone(), weight, rword.clone());
-                    // treap_rec.insert(rword.clone(), weight, rword.clone());
-                    // bst.insert(rword.clone(), rword.clone());
+                    let word = word.to_string();
+                    treap.insert(word.clone(), weight, word.clone());
+                    treap_rec.insert(word.clone(), weight, word.clone());
+                    treap_vec.insert(word.clone(), weight, word.clone());
+                    treap_soa.insert(word.clone(), weight, word.clone());
+                    bst.insert(word.clone(), word.clone());
+                    avl.insert(word.clone(), word);
                     count += 1;
-                    num -= 1;
-                    if num == 0 {
-                        break;
-                    }
                 }
                 let time = Instant::now() - start;
-                dbg!(time.as_millis());
-                eprintln!(">> Loaded {} words", count);
+                eprintln!(">> Loaded {} words in {} ms", count, time.as_millis());
             }
             "insert" => {
                 let english = prompt_user("Enter english word: ");
-                let english = english.parse().unwrap();
+                let german = prompt_user("Enter german word: ");
                 let weight = rng.i32(..);
-                //let german = prompt_user("Enter german word: ");
-                //let (e2, g2) = (english.clone(), german.clone());
-                //let (e3, g3) = (english.clone(), german.clone());
+                let (e2, g2) = (english.clone(), german.clone());
+                let (e3, g3) = (english.clone(), german.clone());
+                let (e4, g4) = (english.clone(), german.clone());
+                let (e5, g5) = (english.clone(), german.clone());
+                let (e6, g6) = (english.clone(), german.clone());
 
                 timer.start();
-                treap.insert(english, weight, english);
+                treap.insert(english, weight, german);
                 timer.evaluate("Treap");
 
-                // timer.start();
-                // treap_rec.insert(e2, weight, g2);
-                // timer.evaluate("TreapRec");
+                timer.start();
+                treap_rec.insert(e2, weight, g2);
+                timer.evaluate("TreapRec");
+
+                timer.start();
+                treap_vec.insert(e3, weight, g3);
+                timer.evaluate("TreapVec");
+
+                timer.start();
+                treap_soa.insert(e4, weight, g4);
+                timer.evaluate("TreapSoa");
 
-                // timer.start();
-                // bst.insert(e3, g3);
-                // timer.evaluate("BST");
+                timer.start();
+                bst.insert(e5, g5);
+                timer.evaluate("BST");
+
+                timer.start();
+                avl.insert(e6, g6);
+                timer.evaluate("AVL");
 
                 timer.deactivate();
             }
@@ -74,41 +103,119 @@ fn main() {
                 break;
             }
             "print" => {
-                eprintln!("{:#?}", &treap);
-                dbg!(&treap_rec);
-                dbg!(&bst);
+                let depth = prompt_user("Enter max depth (empty for 5): ");
+                let max_depth = if depth.is_empty() {
+                    PRINT_MAX_DEPTH
+                } else {
+                    depth.parse::<usize>().unwrap()
+                };
+                eprintln!("Treap:\n{}", treap.to_ascii(max_depth, PRINT_MAX_NODES));
+                eprintln!("TreapRec:\n{}", treap_rec.to_ascii(max_depth, PRINT_MAX_NODES));
+                eprintln!("TreapVec:\n{}", treap_vec.to_ascii(max_depth, PRINT_MAX_NODES));
+                eprintln!("TreapSoa:\n{}", treap_soa.to_ascii(max_depth, PRINT_MAX_NODES));
+                eprintln!("BST:\n{}", bst.to_ascii(max_depth, PRINT_MAX_NODES));
+                eprintln!("AVL:\n{}", avl.to_ascii(max_depth, PRINT_MAX_NODES));
+            }
+            "stats" => {
+                print_stats(
+                    "Treap",
+                    treap.average_depth(),
+                    treap.leaf_count(),
+                    treap.depth_histogram(),
+                );
+                print_stats(
+                    "TreapRec",
+                    treap_rec.average_depth(),
+                    treap_rec.leaf_count(),
+                    treap_rec.depth_histogram(),
+                );
+                print_stats(
+                    "TreapVec",
+                    treap_vec.average_depth(),
+                    treap_vec.leaf_count(),
+                    treap_vec.depth_histogram(),
+                );
+                print_stats(
+                    "TreapSoa",
+                    treap_soa.average_depth(),
+                    treap_soa.leaf_count(),
+                    treap_soa.depth_histogram(),
+                );
+                print_stats("BST", bst.average_depth(), bst.leaf_count(), bst.depth_histogram());
+                print_stats("AVL", avl.average_depth(), avl.leaf_count(), avl.depth_histogram());
+            }
+            "dot" => {
+                let depth = prompt_user("Enter max depth (empty for all): ");
+                let max_depth = if depth.is_empty() {
+                    usize::MAX
+                } else {
+                    depth.parse::<usize>().unwrap()
+                };
+                write_dot("treap.dot", |w| treap.to_dot_with_max_depth(w, max_depth));
+                write_dot("treap_rec.dot", |w| treap_rec.to_dot_with_max_depth(w, max_depth));
+                write_dot("treap_vec.dot", |w| treap_vec.to_dot_with_max_depth(w, max_depth));
+                write_dot("treap_soa.dot", |w| treap_soa.to_dot_with_max_depth(w, max_depth));
+                write_dot("bst.dot", |w| bst.to_dot_with_max_depth(w, max_depth));
+                write_dot("avl.dot", |w| avl.to_dot_with_max_depth(w, max_depth));
+            }
+            "find" => {
+                let english = prompt_user("> Enter english word to find: ");
+
+                timer.start();
+                let result = treap.find(&english);
+                timer.evaluate("Treap");
+                if let Some(german) = result {
+                    println!("true {}", german.get());
+                } else {
+                    println!("false");
+                }
+
+                timer.start();
+                let result = treap_rec.find(&english);
+                timer.evaluate("TreapRec");
+                if let Some(german) = result {
+                    println!("true {}", german);
+                } else {
+                    println!("false");
+                }
+
+                timer.start();
+                let result = treap_vec.find(&english);
+                timer.evaluate("TreapVec");
+                if let Some(german) = result {
+                    println!("true {}", german);
+                } else {
+                    println!("false");
+                }
+
+                timer.start();
+                let result = treap_soa.find(&english);
+                timer.evaluate("TreapSoa");
+                if let Some(german) = result {
+                    println!("true {}", german);
+                } else {
+                    println!("false");
+                }
+
+                timer.start();
+                let result = bst.find(&english);
+                timer.evaluate("BST");
+                if let Some(german) = result {
+                    println!("true {}", german);
+                } else {
+                    println!("false");
+                }
+
+                timer.start();
+                let result = avl.find(&english);
+                timer.evaluate("AVL");
+                if let Some(german) = result {
+                    println!("true {}", german);
+                } else {
+                    println!("false");
+                }
+                timer.deactivate();
             }
-            // "find" => {
-            //     let english = prompt_user("> Enter english word to find: ");
-
-            //     timer.start();
-            //     let result = treap.find(&english);
-            //     timer.evaluate("Treap");
-            //     if let Some(german) = result {
-            //         println!("true {}", &*german);
-            //     } else {
-            //         println!("false");
-            //     }
-
-            //     timer.start();
-            //     let result = treap_rec.find(&english);
-            //     timer.evaluate("TreapRec");
-            //     if let Some(german) = result {
-            //         println!("true {}", german);
-            //     } else {
-            //         println!("false");
-            //     }
-
-            //     timer.start();
-            //     let result = bst.find(&english);
-            //     timer.evaluate("BST");
-            //     if let Some(german) = result {
-            //         println!("true {}", german);
-            //     } else {
-            //         println!("false");
-            //     }
-            //     timer.deactivate();
-            // }
             _ => println!(">> ERR: unrecognized command"),
         }
     }
@@ -153,6 +260,34 @@ impl TimingContext {
     }
 }
 
+fn print_stats(name: &str, average_depth: f64, leaf_count: usize, histogram: Vec<usize>) {
+    eprintln!(
+        "{}: height {}, average depth {:.2}, {} leaves",
+        name,
+        histogram.len(),
+        average_depth,
+        leaf_count
+    );
+    let shown = histogram.len().min(HISTOGRAM_MAX_DEPTHS);
+    let counts: Vec<String> = histogram[..shown].iter().map(|n| n.to_string()).collect();
+    let rest = histogram.len() - shown;
+    if rest > 0 {
+        eprintln!("  nodes per depth: {} ... ({} more depths)", counts.join(" "), rest);
+    } else {
+        eprintln!("  nodes per depth: {}", counts.join(" "));
+    }
+}
+
+fn write_dot<F>(path: &str, write: F)
+where
+    F: FnOnce(BufWriter<File>) -> io::Result<()>,
+{
+    match File::create(path).and_then(|file| write(BufWriter::new(file))) {
+        Ok(()) => eprintln!(">> Wrote {}", path),
+        Err(err) => eprintln!(">> ERR: could not write {}: {}", path, err),
+    }
+}
+
 fn prompt_user(prompt: &str) -> String {
     let mut result = String::new();
     eprintln!("{}", prompt);
@@ -0,0 +1,477 @@
+//! Left-leaning red-black tree with the same interface as
+//! [`BST`](crate::bst::BST), to compare the treaps against the kind of tree
+//! that ordered maps in standard libraries are traditionally built on.
+//!
+//! Every node is red or black. Red nodes are always left children and
+//! never have a red parent, and every path from the root to an empty child
+//! passes the same number of black nodes. Inserts and removes restore these
+//! rules on the way back up with rotations and colour flips, following
+//! Sedgewick's left-leaning variant. The tree counts its rotations, see
+//! [`RBTree::rotations`].
+
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+use crate::ascii;
+use crate::boxed_tree::boxed_tree_impls;
+use crate::validate::{self, Side, Trail, ValidationError, Violation};
+
+#[derive(Clone)]
+pub struct RBTree<K, V> {
+    root: Link<K, V>,
+    rotations: u64,
+}
+
+impl<K, V> RBTree<K, V> {
+    pub fn new() -> Self {
+        Self {
+            root: None,
+            rotations: 0,
+        }
+    }
+
+    /// Number of rotations done by all inserts and removes so far.
+    pub fn rotations(&self) -> u64 {
+        self.rotations
+    }
+}
+
+impl<K, V> Default for RBTree<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+boxed_tree_impls!(RBTree, RBNode, root, Index);
+
+impl<K: Ord, V> RBTree<K, V> {
+    /// Inserts an entry unless the key is already present.
+    pub fn insert(&mut self, key: K, value: V) {
+        RBNode::insert(&mut self.root, key, value, &mut self.rotations);
+        self.blacken_root();
+    }
+
+    pub fn find<'a>(&'a self, key: &K) -> Option<&'a V> {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            link = match key.cmp(&node.key) {
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+                Ordering::Equal => return Some(&node.value),
+            };
+        }
+        None
+    }
+
+    /// Removes the entry for `key` and returns its value.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        // The descent below reshapes the tree on the assumption that the
+        // key is present.
+        self.find(key)?;
+        let root = self.root.as_mut().unwrap();
+        if !is_red(&root.left) && !is_red(&root.right) {
+            root.red = true;
+        }
+        let value = RBNode::remove(&mut self.root, key, &mut self.rotations);
+        self.blacken_root();
+        Some(value)
+    }
+
+    fn blacken_root(&mut self) {
+        if let Some(root) = &mut self.root {
+            root.red = false;
+        }
+    }
+}
+
+impl<K: Ord, V> RBTree<K, V> {
+    /// Checks that every key lies between the keys of its ancestors and
+    /// that the colours follow the rules of a left-leaning red-black tree.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut trail = Trail::default();
+        let mut black_height = None;
+        // (node, depth, side, parent is red, black nodes above, bounds)
+        let mut stack = vec![(&self.root, 0, None, false, 0, None, None)];
+        while let Some((node, depth, side, red_parent, blacks, lower, upper)) = stack.pop() {
+            trail.enter(depth, side);
+            let Some(RBNode {
+                key,
+                red,
+                left,
+                right,
+                ..
+            }) = node.as_deref() else {
+                if *black_height.get_or_insert(blacks) != blacks {
+                    return Err(trail.error(Violation::BlackHeight));
+                }
+                continue;
+            };
+            if !validate::in_bounds(key, lower, upper) {
+                return Err(trail.error(Violation::KeyOrder));
+            }
+            if *red {
+                let violation = match side {
+                    None => Some(Violation::RedRoot),
+                    _ if red_parent => Some(Violation::RedRed),
+                    Some(Side::Right) => Some(Violation::RedRightChild),
+                    Some(Side::Left) => None,
+                };
+                if let Some(violation) = violation {
+                    return Err(trail.error(violation));
+                }
+            }
+            let blacks = blacks + usize::from(!red);
+            let child_depth = depth + 1;
+            stack.push((right, child_depth, Some(Side::Right), *red, blacks, Some(key), upper));
+            stack.push((left, child_depth, Some(Side::Left), *red, blacks, lower, Some(key)));
+        }
+        Ok(())
+    }
+}
+
+impl<K: Ord, V> RBTree<K, V> {
+    /// Keeps only the entries for which `f` returns `true`. Like
+    /// [`RBTree::extract_if`], this takes the tree apart and builds it again.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.extract_if(|k, v| !f(k, v)).for_each(drop);
+    }
+
+    /// Returns an iterator that removes and yields every entry for which
+    /// `pred` returns `true`, in ascending key order.
+    ///
+    /// The whole tree is taken apart into a sorted list right away, and a
+    /// new tree is built from the remaining entries in O(n) when the
+    /// iterator is dropped, without any rotations. If it is dropped early,
+    /// all entries not yet visited are kept.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, F>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let mut entries = Vec::new();
+        let mut stack = Vec::new();
+        let mut link = self.root.take();
+        loop {
+            while let Some(mut node) = link {
+                link = node.left.take();
+                stack.push(node);
+            }
+            let Some(mut node) = stack.pop() else { break };
+            link = node.right.take();
+            entries.push((node.key, node.value));
+        }
+        ExtractIf {
+            tree: self,
+            entries: entries.into_iter(),
+            kept: Vec::new(),
+            pred,
+        }
+    }
+}
+
+/// Iterator returned by [`RBTree::extract_if`].
+pub struct ExtractIf<'a, K: Ord, V, F> {
+    tree: &'a mut RBTree<K, V>,
+    /// Entries not visited yet, in key order.
+    entries: std::vec::IntoIter<(K, V)>,
+    kept: Vec<(K, V)>,
+    pred: F,
+}
+
+impl<'a, K: Ord, V, F> Iterator for ExtractIf<'a, K, V, F>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (key, mut value) in self.entries.by_ref() {
+            if (self.pred)(&key, &mut value) {
+                return Some((key, value));
+            }
+            self.kept.push((key, value));
+        }
+        None
+    }
+}
+
+impl<'a, K: Ord, V, F> Drop for ExtractIf<'a, K, V, F> {
+    fn drop(&mut self) {
+        let len = self.kept.len() + self.entries.len();
+        let kept = std::mem::take(&mut self.kept);
+        let mut entries = kept.into_iter().chain(self.entries.by_ref());
+        self.tree.root = RBNode::build(&mut entries, len, black_height(len));
+    }
+}
+
+impl<K: Display, V> RBTree<K, V> {
+    /// Renders the tree as an indented text tree with one `key:R` or
+    /// `key:B` line per red or black node. Subtrees below `max_depth` (the
+    /// root has depth `0`) and all nodes after the first `max_nodes` in
+    /// pre-order are shown as `...`.
+    pub fn to_ascii(&self, max_depth: usize, max_nodes: usize) -> String {
+        ascii::render(&self.root, max_depth, max_nodes, |link| {
+            let node = link.as_ref()?;
+            let colour = if node.red { "R" } else { "B" };
+            Some((format!("{}:{}", node.key, colour), &node.left, &node.right))
+        })
+    }
+}
+
+/// A subtree, `None` if it is empty.
+type Link<K, V> = Option<Box<RBNode<K, V>>>;
+
+#[derive(Clone)]
+struct RBNode<K, V> {
+    key: K,
+    value: V,
+    /// Colour of the node, or of the link from its parent.
+    red: bool,
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+impl<K: Display, V: Display> RBNode<K, V> {
+    fn dot_lines(&self) -> Vec<String> {
+        let colour = if self.red { "red" } else { "black" };
+        vec![format!("{}: {}", self.key, self.value), colour.to_string()]
+    }
+}
+
+fn is_red<K, V>(link: &Link<K, V>) -> bool {
+    link.as_ref().is_some_and(|node| node.red)
+}
+
+/// Whether the left child of the subtree root is red.
+fn is_left_red<K, V>(link: &Link<K, V>) -> bool {
+    link.as_ref().is_some_and(|node| is_red(&node.left))
+}
+
+impl<K: Ord, V> RBNode<K, V> {
+    fn insert(link: &mut Link<K, V>, key: K, value: V, rotations: &mut u64) {
+        let Some(node) = link else {
+            *link = Some(Box::new(RBNode {
+                key,
+                value,
+                red: true,
+                left: None,
+                right: None,
+            }));
+            return;
+        };
+        match key.cmp(&node.key) {
+            Ordering::Less => Self::insert(&mut node.left, key, value, rotations),
+            Ordering::Greater => Self::insert(&mut node.right, key, value, rotations),
+            Ordering::Equal => return,
+        }
+        Self::balance(link, rotations);
+    }
+
+    /// Removes `key`, which must be present below `link`. On the way down
+    /// the node or one of its children is kept red, so the node that is
+    /// finally unlinked is red and the black heights do not change.
+    fn remove(link: &mut Link<K, V>, key: &K, rotations: &mut u64) -> V {
+        let node = link.as_mut().unwrap();
+        if *key < node.key {
+            if !is_red(&node.left) && !is_left_red(&node.left) {
+                Self::move_red_left(link, rotations);
+            }
+            let node = link.as_mut().unwrap();
+            let value = Self::remove(&mut node.left, key, rotations);
+            Self::balance(link, rotations);
+            return value;
+        }
+        if is_red(&node.left) {
+            Self::rotate_right(link, rotations);
+        }
+        let node = link.as_mut().unwrap();
+        if *key == node.key && node.right.is_none() {
+            let node = link.take().unwrap();
+            debug_assert!(node.left.is_none());
+            return node.value;
+        }
+        if !is_red(&node.right) && !is_left_red(&node.right) {
+            Self::move_red_right(link, rotations);
+        }
+        let node = link.as_mut().unwrap();
+        let value = if *key == node.key {
+            let mut min = Self::remove_min(&mut node.right, rotations);
+            std::mem::swap(&mut node.key, &mut min.key);
+            std::mem::swap(&mut node.value, &mut min.value);
+            min.value
+        } else {
+            Self::remove(&mut node.right, key, rotations)
+        };
+        Self::balance(link, rotations);
+        value
+    }
+}
+
+/// Number of black nodes on every path of a tree that [`RBNode::build`]
+/// builds from `len` entries.
+fn black_height(len: usize) -> u32 {
+    (len + 1).ilog2()
+}
+
+impl<K, V> RBNode<K, V> {
+    /// Builds a tree from `len` entries in key order in which every path
+    /// has `black_height` black nodes. This needs at least
+    /// `2^black_height - 1` and at most `3^black_height - 1` entries. The
+    /// root is a single black node, or a black node with a red left child
+    /// if the entries do not fit below a single node.
+    fn build<I>(entries: &mut I, len: usize, black_height: u32) -> Link<K, V>
+    where
+        I: Iterator<Item = (K, V)>,
+    {
+        if len == 0 {
+            return None;
+        }
+        let below = black_height - 1;
+        let max_below = 3usize.pow(below) - 1;
+        let (red, right_len) = if len - 1 <= 2 * max_below {
+            (None, (len - 1) / 2)
+        } else {
+            let left_len = (len - 2) / 3;
+            let middle_len = (len - 2 - left_len) / 2;
+            let left = Self::build(entries, left_len, below);
+            let (key, value) = entries.next()?;
+            let right = Self::build(entries, middle_len, below);
+            let red = Self::new_node(key, value, true, left, right);
+            (Some(red), len - 2 - left_len - middle_len)
+        };
+        let left = match red {
+            Some(red) => Some(red),
+            None => Self::build(entries, len - 1 - right_len, below),
+        };
+        let (key, value) = entries.next()?;
+        let right = Self::build(entries, right_len, below);
+        Some(Self::new_node(key, value, false, left, right))
+    }
+
+    fn new_node(key: K, value: V, red: bool, left: Link<K, V>, right: Link<K, V>) -> Box<Self> {
+        Box::new(RBNode {
+            key,
+            value,
+            red,
+            left,
+            right,
+        })
+    }
+
+    /// Unlinks the smallest node of a non-empty subtree and returns it.
+    fn remove_min(link: &mut Link<K, V>, rotations: &mut u64) -> Box<RBNode<K, V>> {
+        let node = link.as_mut().unwrap();
+        if node.left.is_none() {
+            let node = link.take().unwrap();
+            debug_assert!(node.right.is_none());
+            return node;
+        }
+        if !is_red(&node.left) && !is_left_red(&node.left) {
+            Self::move_red_left(link, rotations);
+        }
+        let node = link.as_mut().unwrap();
+        let min = Self::remove_min(&mut node.left, rotations);
+        Self::balance(link, rotations);
+        min
+    }
+
+    /// Leans red right children to the left, splits two red links in a
+    /// row and pushes the red up when both children are red.
+    fn balance(link: &mut Link<K, V>, rotations: &mut u64) {
+        let node = link.as_mut().unwrap();
+        if is_red(&node.right) && !is_red(&node.left) {
+            Self::rotate_left(link, rotations);
+        }
+        if is_red(&link.as_ref().unwrap().left) && is_left_red(&link.as_ref().unwrap().left) {
+            Self::rotate_right(link, rotations);
+        }
+        let node = link.as_mut().unwrap();
+        if is_red(&node.left) && is_red(&node.right) {
+            node.flip_colours();
+        }
+    }
+
+    /// Makes the left child or one of its children red before descending
+    /// into it.
+    fn move_red_left(link: &mut Link<K, V>, rotations: &mut u64) {
+        let node = link.as_mut().unwrap();
+        node.flip_colours();
+        if is_left_red(&node.right) {
+            Self::rotate_right(&mut node.right, rotations);
+            Self::rotate_left(link, rotations);
+            link.as_mut().unwrap().flip_colours();
+        }
+    }
+
+    /// Mirror image of [`RBNode::move_red_left`].
+    fn move_red_right(link: &mut Link<K, V>, rotations: &mut u64) {
+        let node = link.as_mut().unwrap();
+        node.flip_colours();
+        if is_left_red(&node.left) {
+            Self::rotate_right(link, rotations);
+            link.as_mut().unwrap().flip_colours();
+        }
+    }
+
+    /// Lifts the right child of the subtree root above it. The child takes
+    /// over the colour of the old root, which becomes red.
+    fn rotate_left(link: &mut Link<K, V>, rotations: &mut u64) {
+        let mut h = link.take().unwrap();
+        let mut x = h.right.take().unwrap();
+        h.right = x.left.take();
+        x.red = h.red;
+        h.red = true;
+        x.left = Some(h);
+        *link = Some(x);
+        *rotations += 1;
+    }
+
+    /// Mirror image of [`RBNode::rotate_left`].
+    fn rotate_right(link: &mut Link<K, V>, rotations: &mut u64) {
+        let mut h = link.take().unwrap();
+        let mut x = h.left.take().unwrap();
+        h.left = x.right.take();
+        x.red = h.red;
+        h.red = true;
+        x.right = Some(h);
+        *link = Some(x);
+        *rotations += 1;
+    }
+
+    fn flip_colours(&mut self) {
+        self.red = !self.red;
+        for child in [&mut self.left, &mut self.right].into_iter().flatten() {
+            child.red = !child.red;
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::RBTree;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    impl<K: Serialize, V: Serialize> Serialize for RBTree<K, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(self.iter())
+        }
+    }
+
+    impl<'de, K, V> Deserialize<'de> for RBTree<K, V>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let entries = BTreeMap::<K, V>::deserialize(deserializer)?;
+            let mut tree = RBTree::new();
+            for (key, value) in entries {
+                tree.insert(key, value);
+            }
+            Ok(tree)
+        }
+    }
+}
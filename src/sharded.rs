@@ -0,0 +1,175 @@
+//! A concurrent map that spreads its keys over several treaps.
+//!
+//! Every key is hashed to one of N [`treap_vec::Treap`] shards, each behind
+//! its own `Mutex`, so threads working on different shards never wait for
+//! each other. The treap weights are chosen at random on insert.
+//!
+//! If a thread panics while it holds a shard, for example in the `Ord`
+//! implementation of the keys, every later operation on that shard panics
+//! as well.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::hash_map::RandomState;
+use std::collections::binary_heap::{BinaryHeap, PeekMut};
+use std::hash::{BuildHasher, Hash};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::treap_vec;
+
+type Shard<K, V> = treap_vec::Treap<K, u32, V>;
+
+pub struct ShardedTreap<K, V> {
+    shards: Box<[Mutex<Shard<K, V>>]>,
+    hasher: RandomState,
+}
+
+impl<K, V> ShardedTreap<K, V> {
+    pub const DEFAULT_SHARDS: usize = 16;
+
+    pub fn new() -> Self {
+        Self::with_shards(Self::DEFAULT_SHARDS)
+    }
+
+    /// Panics if `shards` is `0`.
+    pub fn with_shards(shards: usize) -> Self {
+        assert!(shards > 0, "a ShardedTreap needs at least one shard");
+        Self {
+            shards: (0..shards).map(|_| Mutex::new(Shard::new())).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Locks all shards in order, so that their entries can be iterated as
+    /// one sorted map. Other threads block on every operation until the
+    /// returned guard is dropped.
+    pub fn lock_all(&self) -> Shards<'_, K, V> {
+        Shards(self.shards.iter().map(lock).collect())
+    }
+}
+
+impl<K, V> Default for ShardedTreap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Ord, V> ShardedTreap<K, V> {
+    /// Inserts an entry unless the key is already present.
+    pub fn insert(&self, key: K, value: V) {
+        self.shard(&key).insert(key, fastrand::u32(..), value);
+    }
+
+    /// Calls `f` with the value stored for `key` and returns its result.
+    pub fn find_with<R, F>(&self, key: &K, f: F) -> Option<R>
+    where
+        F: FnOnce(&V) -> R,
+    {
+        self.shard(key).find(key).map(f)
+    }
+
+    pub fn find_cloned(&self, key: &K) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.find_with(key, V::clone)
+    }
+
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.shard(key).remove(key)
+    }
+
+    fn shard(&self, key: &K) -> MutexGuard<'_, Shard<K, V>> {
+        let idx = self.hasher.hash_one(key) % self.shards.len() as u64;
+        lock(&self.shards[idx as usize])
+    }
+}
+
+/// Panics if another thread panicked while it held the lock, for example in
+/// the `Ord` implementation of the keys. An insert compares keys while it
+/// relinks nodes, so the shard may be left half updated.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(_) => panic!("a shard of the ShardedTreap is poisoned"),
+    }
+}
+
+/// All shards of a [`ShardedTreap`], locked by [`ShardedTreap::lock_all`].
+pub struct Shards<'a, K, V>(Vec<MutexGuard<'a, Shard<K, V>>>);
+
+impl<K: Ord, V> Shards<'_, K, V> {
+    /// Returns an iterator over the entries of all shards in ascending key
+    /// order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut shards: Vec<_> = self.0.iter().map(|shard| shard.iter()).collect();
+        let heads = shards
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(shard, iter)| Some(Reverse(Head { entry: iter.next()?, shard })))
+            .collect();
+        Iter { shards, heads }
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a Shards<'_, K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator returned by [`Shards::iter`]. Merges the sorted iterators of the
+/// shards through a heap of their next entries.
+pub struct Iter<'a, K, V> {
+    shards: Vec<treap_vec::Iter<'a, K, u32, V>>,
+    heads: BinaryHeap<Reverse<Head<'a, K, V>>>,
+}
+
+/// The next entry of a shard, ordered by its key. The shards never share a
+/// key, so no two heads are equal.
+struct Head<'a, K, V> {
+    entry: (&'a K, &'a V),
+    shard: usize,
+}
+
+impl<K: Ord, V> PartialEq for Head<'_, K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.entry.0 == other.entry.0
+    }
+}
+
+impl<K: Ord, V> Eq for Head<'_, K, V> {}
+
+impl<K: Ord, V> PartialOrd for Head<'_, K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord, V> Ord for Head<'_, K, V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.entry.0.cmp(other.entry.0)
+    }
+}
+
+impl<'a, K: Ord, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut head = self.heads.peek_mut()?;
+        let Head { entry, shard } = head.0;
+        match self.shards[shard].next() {
+            Some(next) => head.0.entry = next,
+            None => {
+                PeekMut::pop(head);
+            }
+        }
+        Some(entry)
+    }
+}
@@ -0,0 +1,361 @@
+//! Splay tree with the same interface as [`BST`](crate::bst::BST), to
+//! compare the treaps against a tree that adapts to the access pattern.
+//!
+//! Every `insert`, `find` and `remove` moves the node it looked for, or the
+//! last node on the search path, to the root. Keys that are looked up often
+//! therefore stay close to the root, while a treap keeps the same shape no
+//! matter which keys are asked for. [`Splay::iter`] leaves the tree as it
+//! is. The splaying is done top-down in a single pass (Sleator and Tarjan),
+//! without parent pointers or recursion.
+
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+use crate::ascii;
+use crate::boxed_tree::boxed_tree_impls;
+use crate::clone_tree::clone_tree;
+use crate::validate::{self, Side, Trail, ValidationError, Violation};
+
+pub struct Splay<K, V>(Link<K, V>);
+
+impl<K, V> Splay<K, V> {
+    pub fn new() -> Self {
+        Self(None)
+    }
+}
+
+impl<K, V> Default for Splay<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Clone, V: Clone> Clone for Splay<K, V> {
+    fn clone(&self) -> Self {
+        Self(clone_tree(
+            &self.0,
+            |node| SplayNode {
+                key: node.key.clone(),
+                value: node.value.clone(),
+                left: None,
+                right: None,
+            },
+            |node| (&node.left, &node.right),
+            |node| (&mut node.left, &mut node.right),
+        ))
+    }
+}
+
+impl<K, V> Drop for Splay<K, V> {
+    fn drop(&mut self) {
+        let mut stack: Vec<_> = self.0.take().into_iter().collect();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+        }
+    }
+}
+
+boxed_tree_impls!(Splay, SplayNode, 0);
+
+impl<K: Ord, V> Splay<K, V> {
+    /// Inserts an entry unless the key is already present. Either way the
+    /// entry for `key` ends up at the root.
+    pub fn insert(&mut self, key: K, value: V) {
+        let mut node = Box::new(SplayNode {
+            key,
+            value,
+            left: None,
+            right: None,
+        });
+        if let Some(root) = self.0.take() {
+            let (mut root, ordering) = SplayNode::splay(root, &node.key);
+            match ordering {
+                Ordering::Less => {
+                    node.left = root.left.take();
+                    node.right = Some(root);
+                }
+                Ordering::Greater => {
+                    node.right = root.right.take();
+                    node.left = Some(root);
+                }
+                Ordering::Equal => {
+                    self.0 = Some(root);
+                    return;
+                }
+            }
+        }
+        self.0 = Some(node);
+    }
+
+    /// Looks up `key` and moves it to the root. Takes `&mut self` because
+    /// every lookup reshapes the tree.
+    pub fn find<'a>(&'a mut self, key: &K) -> Option<&'a V> {
+        let (root, ordering) = SplayNode::splay(self.0.take()?, key);
+        let root = self.0.insert(root);
+        (ordering == Ordering::Equal).then_some(&root.value)
+    }
+
+    /// Removes the entry for `key` and returns its value.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let (mut root, ordering) = SplayNode::splay(self.0.take()?, key);
+        if ordering != Ordering::Equal {
+            self.0 = Some(root);
+            return None;
+        }
+        // Splaying the left subtree for a key larger than all of its keys
+        // brings its maximum to the top, which leaves no right child.
+        self.0 = match root.left.take() {
+            None => root.right.take(),
+            Some(left) => {
+                let (mut max, _) = SplayNode::splay(left, key);
+                max.right = root.right.take();
+                Some(max)
+            }
+        };
+        Some(root.value)
+    }
+}
+
+impl<K: Ord, V> Splay<K, V> {
+    /// Checks that every key lies between the keys of its ancestors.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut trail = Trail::default();
+        let mut stack = vec![(&self.0, 0, None, None, None)];
+        while let Some((node, depth, side, lower, upper)) = stack.pop() {
+            trail.enter(depth, side);
+            let Some(SplayNode {
+                key, left, right, ..
+            }) = node.as_deref() else { continue };
+            if !validate::in_bounds(key, lower, upper) {
+                return Err(trail.error(Violation::KeyOrder));
+            }
+            stack.push((right, depth + 1, Some(Side::Right), Some(key), upper));
+            stack.push((left, depth + 1, Some(Side::Left), lower, Some(key)));
+        }
+        Ok(())
+    }
+}
+
+impl<K, V> Splay<K, V> {
+    /// Keeps only the entries for which `f` returns `true`. Like
+    /// [`Splay::extract_if`], this takes the tree apart and builds it again.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.extract_if(|k, v| !f(k, v)).for_each(drop);
+    }
+
+    /// Returns an iterator that removes and yields every entry for which
+    /// `pred` returns `true`, in ascending key order.
+    ///
+    /// Unlike the treaps, this is not lazy: the whole tree is taken apart
+    /// into a sorted list as soon as `extract_if` is called, and a balanced
+    /// tree is built from the remaining entries when the iterator is
+    /// dropped, both in O(n). The shape that earlier accesses have splayed
+    /// into the tree is lost. If the iterator is dropped early, all entries
+    /// not yet visited are kept.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, F>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let mut entries = Vec::new();
+        let mut stack = Vec::new();
+        let mut link = self.0.take();
+        loop {
+            while let Some(mut node) = link {
+                link = node.left.take();
+                stack.push(node);
+            }
+            let Some(mut node) = stack.pop() else { break };
+            link = node.right.take();
+            let SplayNode { key, value, .. } = *node;
+            entries.push((key, value));
+        }
+        ExtractIf {
+            tree: self,
+            entries: entries.into_iter(),
+            kept: Vec::new(),
+            pred,
+        }
+    }
+}
+
+/// Iterator returned by [`Splay::extract_if`].
+pub struct ExtractIf<'a, K, V, F> {
+    tree: &'a mut Splay<K, V>,
+    /// Entries not visited yet, in key order.
+    entries: std::vec::IntoIter<(K, V)>,
+    kept: Vec<(K, V)>,
+    pred: F,
+}
+
+impl<'a, K, V, F> Iterator for ExtractIf<'a, K, V, F>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (key, mut value) in self.entries.by_ref() {
+            if (self.pred)(&key, &mut value) {
+                return Some((key, value));
+            }
+            self.kept.push((key, value));
+        }
+        None
+    }
+}
+
+impl<'a, K, V, F> Drop for ExtractIf<'a, K, V, F> {
+    fn drop(&mut self) {
+        let len = self.kept.len() + self.entries.len();
+        let kept = std::mem::take(&mut self.kept);
+        let mut entries = kept.into_iter().chain(self.entries.by_ref());
+        self.tree.0 = SplayNode::build(&mut entries, len);
+    }
+}
+
+impl<K: Display, V> Splay<K, V> {
+    /// Renders the tree as an indented text tree with one `key` line per
+    /// node. Subtrees below `max_depth` (the root has depth `0`) and all
+    /// nodes after the first `max_nodes` in pre-order are shown as `...`.
+    pub fn to_ascii(&self, max_depth: usize, max_nodes: usize) -> String {
+        ascii::render(&self.0, max_depth, max_nodes, |link| {
+            let node = link.as_ref()?;
+            Some((node.key.to_string(), &node.left, &node.right))
+        })
+    }
+}
+
+/// A subtree, `None` if it is empty.
+type Link<K, V> = Option<Box<SplayNode<K, V>>>;
+
+struct SplayNode<K, V> {
+    key: K,
+    value: V,
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+impl<K: Display, V: Display> SplayNode<K, V> {
+    fn dot_lines(&self) -> Vec<String> {
+        vec![format!("{}: {}", self.key, self.value)]
+    }
+}
+
+impl<K: Ord, V> SplayNode<K, V> {
+    /// Returns the subtree rearranged so that the node for `key` is the
+    /// root. Without such a node, the last node on the search path becomes
+    /// the root instead. The ordering is that of `key` against the new root,
+    /// so it is `Equal` if the key was found.
+    ///
+    /// On the way down, the nodes left of the search path are hung into the
+    /// `smaller` tree, each as the right child of the one before, and the
+    /// nodes right of the path into `larger` as left children. At the end
+    /// the two trees become the children of the new root.
+    fn splay(mut root: Box<Self>, key: &K) -> (Box<Self>, Ordering) {
+        let mut smaller = None;
+        let mut larger = None;
+        // The empty links where the next node of each tree goes.
+        let mut smaller_hole = &mut smaller;
+        let mut larger_hole = &mut larger;
+        let mut ordering = key.cmp(&root.key);
+        loop {
+            match ordering {
+                Ordering::Less => {
+                    let Some(mut child) = root.left.take() else { break };
+                    ordering = key.cmp(&child.key);
+                    if ordering == Ordering::Less {
+                        // Zig-zig: rotate right first, so the path is halved.
+                        root.left = child.right.take();
+                        child.right = Some(root);
+                        root = child;
+                        let Some(next) = root.left.take() else { break };
+                        let parent = larger_hole.insert(root);
+                        larger_hole = &mut parent.left;
+                        root = next;
+                        ordering = key.cmp(&root.key);
+                    } else {
+                        let parent = larger_hole.insert(root);
+                        larger_hole = &mut parent.left;
+                        root = child;
+                    }
+                }
+                Ordering::Greater => {
+                    let Some(mut child) = root.right.take() else { break };
+                    ordering = key.cmp(&child.key);
+                    if ordering == Ordering::Greater {
+                        root.right = child.left.take();
+                        child.left = Some(root);
+                        root = child;
+                        let Some(next) = root.right.take() else { break };
+                        let parent = smaller_hole.insert(root);
+                        smaller_hole = &mut parent.right;
+                        root = next;
+                        ordering = key.cmp(&root.key);
+                    } else {
+                        let parent = smaller_hole.insert(root);
+                        smaller_hole = &mut parent.right;
+                        root = child;
+                    }
+                }
+                Ordering::Equal => break,
+            }
+        }
+        *smaller_hole = root.left.take();
+        *larger_hole = root.right.take();
+        root.left = smaller;
+        root.right = larger;
+        (root, ordering)
+    }
+}
+
+impl<K, V> SplayNode<K, V> {
+    /// Builds a tree from the next `len` entries of `entries`, which must be
+    /// sorted by key. The sizes of the two subtrees of every node differ by
+    /// at most one.
+    fn build<I>(entries: &mut I, len: usize) -> Link<K, V>
+    where
+        I: Iterator<Item = (K, V)>,
+    {
+        if len == 0 {
+            return None;
+        }
+        let left = Self::build(entries, len / 2);
+        let (key, value) = entries.next()?;
+        let right = Self::build(entries, len - len / 2 - 1);
+        Some(Box::new(SplayNode {
+            key,
+            value,
+            left,
+            right,
+        }))
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::{Splay, SplayNode};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    impl<K: Serialize, V: Serialize> Serialize for Splay<K, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(self.iter())
+        }
+    }
+
+    impl<'de, K, V> Deserialize<'de> for Splay<K, V>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let entries = BTreeMap::<K, V>::deserialize(deserializer)?;
+            let len = entries.len();
+            Ok(Splay(SplayNode::build(&mut entries.into_iter(), len)))
+        }
+    }
+}
@@ -0,0 +1,41 @@
+//! Shape statistics shared by all trees.
+
+/// Node depths collected by a walk over a tree, with the root at depth `0`.
+#[derive(Default)]
+pub(crate) struct Depths {
+    histogram: Vec<usize>,
+    leaves: usize,
+}
+
+impl Depths {
+    pub fn add(&mut self, depth: usize, is_leaf: bool) {
+        if self.histogram.len() <= depth {
+            self.histogram.resize(depth + 1, 0);
+        }
+        self.histogram[depth] += 1;
+        if is_leaf {
+            self.leaves += 1;
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        self.histogram.len()
+    }
+
+    pub fn average_depth(&self) -> f64 {
+        let nodes: usize = self.histogram.iter().sum();
+        if nodes == 0 {
+            return 0.0;
+        }
+        let total: usize = self.histogram.iter().enumerate().map(|(d, n)| d * n).sum();
+        total as f64 / nodes as f64
+    }
+
+    pub fn into_histogram(self) -> Vec<usize> {
+        self.histogram
+    }
+
+    pub fn leaves(&self) -> usize {
+        self.leaves
+    }
+}
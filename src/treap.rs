@@ -1,11 +1,18 @@
-use std::cell::RefCell;
+use std::cell::{Ref, RefCell};
 use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt::{self, Debug, Display};
+use std::io::{self, Write};
+use std::marker::PhantomData;
 use std::rc::{Rc, Weak};
 
-#[derive(Debug)]
+use crate::ascii;
+use crate::dot::Dot;
+use crate::stats::Depths;
+use crate::validate::{self, Side, Trail, ValidationError, Violation};
+
 pub struct Treap<K, W, V>(Rc<RefCell<TreapNode<K, W, V>>>);
 
-#[derive(Debug)]
 enum TreapNode<K, W, V> {
     Empty(Weak<RefCell<TreapNode<K, W, V>>>),
     Node {
@@ -64,17 +71,9 @@ impl<K: Ord, W: Ord, V> Treap<K, W, V> {
                     break;
                 };
                 let restored = parent.borrow_mut().restore_heap_property();
-                let parent_clone = Rc::downgrade(&parent);
                 match restored {
-                    ShouldRestore::Left => {
-                        let TreapNode::Node { right, .. } = &*parent.borrow() else { unreachable!() };
-                        let TreapNode::Node { parent, ..} = &mut *right.borrow_mut() else { unreachable!() };
-                        let _ = std::mem::replace(parent, parent_clone);
-                    }
-                    ShouldRestore::Right => {
-                        let TreapNode::Node { left, .. } = &*parent.borrow() else { unreachable!() };
-                        let TreapNode::Node { parent, ..} = &mut *left.borrow_mut() else { unreachable!() };
-                        let _ = std::mem::replace(parent, parent_clone);
+                    ShouldRestore::Left | ShouldRestore::Right => {
+                        TreapNode::relink_children(&parent);
                     }
                     ShouldRestore::Nothing => {}
                 };
@@ -83,55 +82,346 @@ impl<K: Ord, W: Ord, V> Treap<K, W, V> {
     }
 }
 
-pub struct Find<'a, V> {
-    p: *const V,
-    _phantom: std::marker::PhantomData<&'a V>,
+impl<K, W, V> Treap<K, W, V> {
+    pub fn height(&self) -> usize {
+        self.depths().height()
+    }
+
+    pub fn average_depth(&self) -> f64 {
+        self.depths().average_depth()
+    }
+
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        self.depths().into_histogram()
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.depths().leaves()
+    }
+
+    fn depths(&self) -> Depths {
+        let mut depths = Depths::default();
+        let mut stack = vec![(Rc::clone(&self.0), 0)];
+        while let Some((node, depth)) = stack.pop() {
+            let TreapNode::Node { left, right, .. } = &*node.borrow() else { continue };
+            depths.add(depth, left.borrow().is_empty() && right.borrow().is_empty());
+            stack.push((Rc::clone(left), depth + 1));
+            stack.push((Rc::clone(right), depth + 1));
+        }
+        depths
+    }
+}
+
+impl<K: Ord, W: Ord, V> Treap<K, W, V> {
+    /// Checks the search tree order of the keys, the heap order of the
+    /// weights and that the `Weak` parent link of every node, empty ones
+    /// included, points to the node it hangs off.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        type Link<K, W, V> = Rc<RefCell<TreapNode<K, W, V>>>;
+        // Node, depth, side, parent and the nodes holding the key bounds.
+        type Frame<K, W, V> = (
+            Link<K, W, V>,
+            usize,
+            Option<Side>,
+            Option<Link<K, W, V>>,
+            Option<Link<K, W, V>>,
+            Option<Link<K, W, V>>,
+        );
+        let mut visited = HashSet::new();
+        let mut trail = Trail::default();
+        let mut stack: Vec<Frame<K, W, V>> = vec![(Rc::clone(&self.0), 0, None, None, None, None)];
+        while let Some((node, depth, side, parent, lower, upper)) = stack.pop() {
+            trail.enter(depth, side);
+            if !visited.insert(Rc::as_ptr(&node)) {
+                return Err(trail.error(Violation::SharedNode));
+            }
+            let node_ref = node.borrow();
+            let parent_ok = match (node_ref.parent(), &parent) {
+                (None, None) => true,
+                (Some(linked), Some(parent)) => Rc::ptr_eq(&linked, parent),
+                _ => false,
+            };
+            if !parent_ok {
+                return Err(trail.error(Violation::ParentLink));
+            }
+            let TreapNode::Node {
+                key,
+                weight,
+                left,
+                right,
+                ..
+            } = &*node_ref else { continue };
+            let lower_ref = lower.as_ref().map(|n| n.borrow());
+            let upper_ref = upper.as_ref().map(|n| n.borrow());
+            let lower_key = lower_ref.as_deref().and_then(TreapNode::key);
+            let upper_key = upper_ref.as_deref().and_then(TreapNode::key);
+            if !validate::in_bounds(key, lower_key, upper_key) {
+                return Err(trail.error(Violation::KeyOrder));
+            }
+            let parent_ref = parent.as_ref().map(|n| n.borrow());
+            if parent_ref.as_deref().and_then(TreapNode::weight).is_some_and(|pw| weight < pw) {
+                return Err(trail.error(Violation::HeapOrder));
+            }
+            let child_depth = depth + 1;
+            stack.push((
+                Rc::clone(right),
+                child_depth,
+                Some(Side::Right),
+                Some(Rc::clone(&node)),
+                Some(Rc::clone(&node)),
+                upper.clone(),
+            ));
+            stack.push((
+                Rc::clone(left),
+                child_depth,
+                Some(Side::Left),
+                Some(Rc::clone(&node)),
+                lower.clone(),
+                Some(Rc::clone(&node)),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<K: Clone, W: Clone, V> Treap<K, W, V> {
+    /// Returns the keys and weights in pre-order.
+    ///
+    /// Comparable with [`crate::Treap::shape`].
+    pub fn shape(&self) -> Vec<(K, W)> {
+        let mut shape = Vec::new();
+        let mut stack = vec![Rc::clone(&self.0)];
+        while let Some(node) = stack.pop() {
+            if let TreapNode::Node {
+                key,
+                weight,
+                left,
+                right,
+                ..
+            } = &*node.borrow()
+            {
+                shape.push((key.clone(), weight.clone()));
+                stack.push(Rc::clone(right));
+                stack.push(Rc::clone(left));
+            }
+        }
+        shape
+    }
+}
+
+/// A found entry, returned by [`Treap::find`].
+///
+/// The guard keeps the treap borrowed, so it cannot be modified while the
+/// guard is alive. The value itself is borrowed with [`Find::get`].
+pub struct Find<'a, K, W, V> {
+    node: Rc<RefCell<TreapNode<K, W, V>>>,
+    _treap: PhantomData<&'a Treap<K, W, V>>,
+}
+
+impl<K, W, V> Find<'_, K, W, V> {
+    pub fn get(&self) -> Ref<'_, V> {
+        Ref::map(self.node.borrow(), |node| match node {
+            TreapNode::Node { value, .. } => value,
+            TreapNode::Empty(_) => unreachable!(),
+        })
+    }
 }
 
 impl<K: Ord, W, V> Treap<K, W, V> {
-    pub fn find<'a, 'b>(&'a self, find_key: &'b K) -> Option<Find<'a, V>> {
+    pub fn find(&self, key: &K) -> Option<Find<'_, K, W, V>> {
+        Some(Find {
+            node: self.find_node(key)?,
+            _treap: PhantomData,
+        })
+    }
+
+    /// Calls `f` with the value stored for `key` and returns its result.
+    pub fn find_with<R, F>(&self, key: &K, f: F) -> Option<R>
+    where
+        F: FnOnce(&V) -> R,
+    {
+        self.find(key).map(|found| f(&found.get()))
+    }
+
+    pub fn find_cloned(&self, key: &K) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.find_with(key, V::clone)
+    }
+
+    fn find_node(&self, find_key: &K) -> Option<Rc<RefCell<TreapNode<K, W, V>>>> {
         let mut current = Rc::clone(&self.0);
-            loop {
-                let cb = current.borrow();
-                let next;
-                match &*cb {
-                    TreapNode::Empty(_) => return None,
-                    TreapNode::Node {
-                        key,
-                        left,
-                        right,
-                        value,
-                        ..
-                    } => {
-                        next = if *find_key < *key {
-                            Rc::clone(left)
-                        } else if *key < *find_key {
-                            Rc::clone(right)
-                        } else {
-                            return Some(Find {
-                                p: value,
-                                _phantom: std::marker::PhantomData,
-                            });
-                        }
-                    }
+        loop {
+            let next = match &*current.borrow() {
+                TreapNode::Empty(_) => return None,
+                TreapNode::Node {
+                    key, left, right, ..
+                } => match find_key.cmp(key) {
+                    Ordering::Less => Rc::clone(left),
+                    Ordering::Greater => Rc::clone(right),
+                    Ordering::Equal => break,
+                },
+            };
+            current = next;
+        }
+        Some(current)
+    }
+}
+
+impl<K, W, V> Treap<K, W, V> {
+    pub fn new() -> Self {
+        Self(Rc::new(RefCell::new(TreapNode::Empty(Weak::new()))))
+    }
+}
+
+impl<K: Display, W: Display, V> Treap<K, W, V> {
+    /// Renders the treap as an indented text tree with one `key:weight` line per
+    /// node. Subtrees below `max_depth` (the root has depth `0`) and all
+    /// nodes after the first `max_nodes` in pre-order are shown as `...`.
+    pub fn to_ascii(&self, max_depth: usize, max_nodes: usize) -> String {
+        ascii::render(Rc::clone(&self.0), max_depth, max_nodes, |node| {
+            match &*node.borrow() {
+                TreapNode::Empty(_) => None,
+                TreapNode::Node {
+                    key,
+                    weight,
+                    left,
+                    right,
+                    ..
+                } => Some((format!("{}:{}", key, weight), Rc::clone(left), Rc::clone(right))),
+            }
+        })
+    }
+}
+
+impl<K: Display, W: Display, V: Display> Treap<K, W, V> {
+    /// Writes the treap in Graphviz DOT format. Empty children are drawn as
+    /// small grey dots.
+    pub fn to_dot<T: Write>(&self, writer: T) -> io::Result<()> {
+        self.to_dot_with_max_depth(writer, usize::MAX)
+    }
+
+    /// Like [`Treap::to_dot`], but replaces every subtree below `max_depth`
+    /// by a `...` node. The root has depth `0`.
+    pub fn to_dot_with_max_depth<T: Write>(&self, writer: T, max_depth: usize) -> io::Result<()> {
+        let mut dot = Dot::begin(writer)?;
+        let mut stack = vec![(Rc::clone(&self.0), 0, None)];
+        while let Some((node, depth, parent)) = stack.pop() {
+            match &*node.borrow() {
+                TreapNode::Empty(_) => dot.empty(parent)?,
+                TreapNode::Node { .. } if depth > max_depth => dot.cut(parent)?,
+                TreapNode::Node {
+                    key,
+                    weight,
+                    value,
+                    left,
+                    right,
+                    ..
+                } => {
+                    let lines = [format!("{}: {}", key, value), format!("w = {}", weight)];
+                    let id = dot.node(parent, &lines)?;
+                    stack.push((Rc::clone(right), depth + 1, Some(id)));
+                    stack.push((Rc::clone(left), depth + 1, Some(id)));
                 }
-                drop(cb);
-                current = next;
             }
+        }
+        dot.end()
     }
 }
 
-impl<'a, V> std::ops::Deref for Find<'a, V> {
-    type Target = V;
+impl<K, W, V> Default for Treap<K, W, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-    fn deref(&self) -> &'a V {
-        unsafe { &*self.p }
+impl<K, W, V> Drop for Treap<K, W, V> {
+    fn drop(&mut self) {
+        let mut stack = vec![self.0.borrow_mut().take()];
+        while let Some(node) = stack.pop() {
+            if let TreapNode::Node { left, right, .. } = node {
+                stack.push(left.borrow_mut().take());
+                stack.push(right.borrow_mut().take());
+            }
+        }
+    }
+}
+
+impl<K: Debug, W, V: Debug> Debug for Treap<K, W, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        self.try_for_each(|key, value| {
+            map.entry(key, value);
+            Ok(())
+        })?;
+        map.finish()
     }
 }
 
 impl<K, W, V> Treap<K, W, V> {
-    pub fn new() -> Self {
-        Self(Rc::new(RefCell::new(TreapNode::Empty(Weak::new()))))
+    /// Calls `f` on every entry in key order and stops at the first error.
+    fn try_for_each<E, F>(&self, mut f: F) -> Result<(), E>
+    where
+        F: FnMut(&K, &V) -> Result<(), E>,
+    {
+        let mut stack = Vec::new();
+        let mut current = Rc::clone(&self.0);
+        loop {
+            while !current.borrow().is_empty() {
+                let left = match &*current.borrow() {
+                    TreapNode::Node { left, .. } => Rc::clone(left),
+                    TreapNode::Empty(_) => unreachable!(),
+                };
+                stack.push(current);
+                current = left;
+            }
+            let Some(node) = stack.pop() else { return Ok(()) };
+            let node_ref = node.borrow();
+            let TreapNode::Node {
+                key, value, right, ..
+            } = &*node_ref else { unreachable!() };
+            f(key, value)?;
+            current = Rc::clone(right);
+        }
+    }
+}
+
+impl<K: Clone, W: Clone, V: Clone> Clone for Treap<K, W, V> {
+    fn clone(&self) -> Self {
+        // Every node is first created as an empty child and then filled in,
+        // so the parent links of the copy point to the copied nodes.
+        let copy = Treap::new();
+        let mut stack = vec![(Rc::clone(&self.0), Rc::clone(&copy.0))];
+        while let Some((source, target)) = stack.pop() {
+            let source = source.borrow();
+            let TreapNode::Node {
+                key,
+                weight,
+                value,
+                left,
+                right,
+                ..
+            } = &*source else { continue };
+            let empty = || Rc::new(RefCell::new(TreapNode::Empty(Rc::downgrade(&target))));
+            let (new_left, new_right) = (empty(), empty());
+            let parent = match &*target.borrow() {
+                TreapNode::Empty(parent) => Weak::clone(parent),
+                TreapNode::Node { .. } => unreachable!("targets start out empty"),
+            };
+            let node = TreapNode::Node {
+                parent,
+                key: key.clone(),
+                weight: weight.clone(),
+                value: value.clone(),
+                left: Rc::clone(&new_left),
+                right: Rc::clone(&new_right),
+            };
+            *target.borrow_mut() = node;
+            stack.push((Rc::clone(right), new_right));
+            stack.push((Rc::clone(left), new_left));
+        }
+        copy
     }
 }
 
@@ -150,9 +440,9 @@ impl<K, W: Ord, V> TreapNode<K, W, V> {
                     let rb = right.borrow();
                     let left_weight = lb.weight();
                     let right_weight = rb.weight();
-                    if left_weight.map_or(false, |w| w < weight) {
+                    if left_weight.is_some_and(|w| w < weight) {
                         ShouldRestore::Left
-                    } else if right_weight.map_or(false, |w| w < weight) {
+                    } else if right_weight.is_some_and(|w| w < weight) {
                         ShouldRestore::Right
                     } else {
                         ShouldRestore::Nothing
@@ -250,17 +540,7 @@ enum ShouldRestore {
 
 impl<K, W, V> TreapNode<K, W, V> {
     pub fn is_empty(&self) -> bool {
-        match self {
-            TreapNode::Empty(_) => true,
-            _ => false,
-        }
-    }
-
-    pub fn left(&self) -> Option<Rc<RefCell<Self>>> {
-        match self {
-            TreapNode::Empty(_) => None,
-            TreapNode::Node { left, .. } => Some(Rc::clone(left)),
-        }
+        matches!(self, TreapNode::Empty(_))
     }
 
     pub fn take(&mut self) -> Self {
@@ -278,6 +558,35 @@ impl<K, W, V> TreapNode<K, W, V> {
         }
     }
 
+    pub fn key(&self) -> Option<&K> {
+        match self {
+            TreapNode::Empty(_) => None,
+            TreapNode::Node { key, .. } => Some(key),
+        }
+    }
+
+    /// Points the parent links of the children of `this` and of their
+    /// children back at them. A rotation moves nodes between cells, so all
+    /// of these may be stale afterwards.
+    fn relink_children(this: &Rc<RefCell<Self>>) {
+        let node = this.borrow();
+        let TreapNode::Node { left, right, .. } = &*node else { return };
+        for child in [left, right] {
+            *child.borrow_mut().parent_mut() = Rc::downgrade(this);
+            if let TreapNode::Node { left, right, .. } = &*child.borrow() {
+                *left.borrow_mut().parent_mut() = Rc::downgrade(child);
+                *right.borrow_mut().parent_mut() = Rc::downgrade(child);
+            }
+        }
+    }
+
+    fn parent_mut(&mut self) -> &mut Weak<RefCell<Self>> {
+        match self {
+            TreapNode::Empty(p) => p,
+            TreapNode::Node { parent, .. } => parent,
+        }
+    }
+
     pub fn parent(&self) -> Option<Rc<RefCell<Self>>> {
         match self {
             TreapNode::Empty(p) => Weak::upgrade(p),
@@ -285,3 +594,85 @@ impl<K, W, V> TreapNode<K, W, V> {
         }
     }
 }
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::{Treap, TreapNode};
+    use crate::Weighted;
+    use rand::distributions::{Distribution, Standard};
+    use serde::ser::{SerializeMap, SerializeSeq};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+    use std::rc::Rc;
+
+    impl<K: Serialize, W, V: Serialize> Serialize for Treap<K, W, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut map = serializer.serialize_map(None)?;
+            self.try_for_each(|key, value| map.serialize_entry(key, value))?;
+            map.end()
+        }
+    }
+
+    impl<'de, K, W, V> Deserialize<'de> for Treap<K, W, V>
+    where
+        K: Deserialize<'de> + Ord,
+        W: Ord,
+        V: Deserialize<'de>,
+        Standard: Distribution<W>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let entries = BTreeMap::<K, V>::deserialize(deserializer)?;
+            let mut treap = Treap::new();
+            for (key, value) in entries {
+                treap.insert(key, rand::random(), value);
+            }
+            Ok(treap)
+        }
+    }
+
+    impl<K: Serialize, W: Serialize, V: Serialize> Serialize for Weighted<&Treap<K, W, V>> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(None)?;
+            let mut stack = vec![Rc::clone(&(self.0).0)];
+            while let Some(node) = stack.pop() {
+                let node_ref = node.borrow();
+                if let TreapNode::Node {
+                    key,
+                    weight,
+                    value,
+                    left,
+                    right,
+                    ..
+                } = &*node_ref
+                {
+                    seq.serialize_element(&(key, weight, value))?;
+                    stack.push(Rc::clone(right));
+                    stack.push(Rc::clone(left));
+                }
+            }
+            seq.end()
+        }
+    }
+
+    impl<K: Serialize, W: Serialize, V: Serialize> Serialize for Weighted<Treap<K, W, V>> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Weighted(&self.0).serialize(serializer)
+        }
+    }
+
+    impl<'de, K, W, V> Deserialize<'de> for Weighted<Treap<K, W, V>>
+    where
+        K: Deserialize<'de> + Ord,
+        W: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let entries = Vec::<(K, W, V)>::deserialize(deserializer)?;
+            let mut treap = Treap::new();
+            for (key, weight, value) in entries {
+                treap.insert(key, weight, value);
+            }
+            Ok(Weighted(treap))
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! A treap that many threads can read while another thread updates it.
+//!
+//! Nodes are immutable and shared through `Arc`. An insert copies the path
+//! from the root down to the new node and then publishes the new root, so a
+//! reader holding a [`Snapshot`] keeps seeing the version it started with.
+//! Readers only lock the root long enough to clone one `Arc`.
+
+use std::cmp::Ordering;
+use std::fmt::{self, Debug};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+use crate::validate::{self, Side, Trail, ValidationError, Violation};
+
+pub struct Treap<K, W, V> {
+    root: RwLock<Link<K, W, V>>,
+    /// Serializes inserts, so that no update is built from a stale root.
+    writer: Mutex<()>,
+}
+
+type Link<K, W, V> = Option<Arc<Node<K, W, V>>>;
+
+struct Node<K, W, V> {
+    entry: Arc<Entry<K, W, V>>,
+    left: Link<K, W, V>,
+    right: Link<K, W, V>,
+}
+
+/// Copying a path only copies the `Arc`s to the entries, so keys and values
+/// never have to be cloned.
+struct Entry<K, W, V> {
+    key: K,
+    weight: W,
+    value: V,
+}
+
+impl<K, W, V> Treap<K, W, V> {
+    pub fn new() -> Self {
+        Self {
+            root: RwLock::new(None),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Returns the current version of the treap. Later inserts do not
+    /// change it.
+    pub fn snapshot(&self) -> Snapshot<K, W, V> {
+        // The root is only ever replaced by a finished tree, so it is valid
+        // even if a thread panicked while holding a lock.
+        Snapshot(self.root.read().unwrap_or_else(PoisonError::into_inner).clone())
+    }
+}
+
+impl<K, W, V> Default for Treap<K, W, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, W: Ord, V> Treap<K, W, V> {
+    /// Inserts an entry unless the key is already present. Readers see the
+    /// new entry as soon as they take their next snapshot.
+    pub fn insert(&self, key: K, weight: W, value: V) {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let Snapshot(root) = self.snapshot();
+        let entry = Entry { key, weight, value };
+        if let Some(new_root) = Node::insert(&root, entry) {
+            *self.root.write().unwrap_or_else(PoisonError::into_inner) = Some(new_root);
+        }
+    }
+
+    /// Calls `f` with the value stored for `key` and returns its result.
+    pub fn find_with<R, F>(&self, key: &K, f: F) -> Option<R>
+    where
+        F: FnOnce(&V) -> R,
+    {
+        self.snapshot().find(key).map(f)
+    }
+
+    pub fn find_cloned(&self, key: &K) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.find_with(key, V::clone)
+    }
+}
+
+impl<K: Debug, W, V: Debug> Debug for Treap<K, W, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.snapshot().fmt(f)
+    }
+}
+
+/// An immutable version of a [`Treap`], returned by [`Treap::snapshot`].
+pub struct Snapshot<K, W, V>(Link<K, W, V>);
+
+impl<K: Ord, W, V> Snapshot<K, W, V> {
+    pub fn find(&self, key: &K) -> Option<&V> {
+        let mut current = self.0.as_deref();
+        while let Some(node) = current {
+            current = match key.cmp(&node.entry.key) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return Some(&node.entry.value),
+            };
+        }
+        None
+    }
+}
+
+impl<K: Ord, W: Ord, V> Snapshot<K, W, V> {
+    /// Checks the search tree order of the keys and the heap order of the
+    /// weights.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut trail = Trail::default();
+        let mut stack = vec![(self.0.as_deref(), 0, None, None, None, None)];
+        while let Some((node, depth, side, parent_weight, lower, upper)) = stack.pop() {
+            trail.enter(depth, side);
+            let Some(node) = node else { continue };
+            let Entry { key, weight, .. } = &*node.entry;
+            if !validate::in_bounds(key, lower, upper) {
+                return Err(trail.error(Violation::KeyOrder));
+            }
+            if parent_weight.is_some_and(|pw| weight < pw) {
+                return Err(trail.error(Violation::HeapOrder));
+            }
+            let child_depth = depth + 1;
+            stack.push((
+                node.right.as_deref(),
+                child_depth,
+                Some(Side::Right),
+                Some(weight),
+                Some(key),
+                upper,
+            ));
+            stack.push((
+                node.left.as_deref(),
+                child_depth,
+                Some(Side::Left),
+                Some(weight),
+                lower,
+                Some(key),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<K, W, V> Snapshot<K, W, V> {
+    /// Returns an iterator over all entries in ascending key order.
+    pub fn iter(&self) -> Iter<'_, K, W, V> {
+        let mut iter = Iter { stack: Vec::new() };
+        iter.push_left(self.0.as_deref());
+        iter
+    }
+
+    /// Returns the keys and weights in pre-order, see
+    /// [`crate::Treap::shape`].
+    pub fn shape(&self) -> Vec<(K, W)>
+    where
+        K: Clone,
+        W: Clone,
+    {
+        let mut shape = Vec::new();
+        let mut stack = vec![self.0.as_deref()];
+        while let Some(node) = stack.pop() {
+            let Some(node) = node else { continue };
+            shape.push((node.entry.key.clone(), node.entry.weight.clone()));
+            stack.push(node.right.as_deref());
+            stack.push(node.left.as_deref());
+        }
+        shape
+    }
+}
+
+impl<K, W, V> Clone for Snapshot<K, W, V> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<K: Debug, W, V: Debug> Debug for Snapshot<K, W, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator returned by [`Snapshot::iter`].
+pub struct Iter<'a, K, W, V> {
+    stack: Vec<&'a Node<K, W, V>>,
+}
+
+impl<'a, K, W, V> Iter<'a, K, W, V> {
+    fn push_left(&mut self, mut node: Option<&'a Node<K, W, V>>) {
+        while let Some(n) = node {
+            self.stack.push(n);
+            node = n.left.as_deref();
+        }
+    }
+}
+
+impl<'a, K, W, V> Iterator for Iter<'a, K, W, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left(node.right.as_deref());
+        Some((&node.entry.key, &node.entry.value))
+    }
+}
+
+impl<'a, K, W, V> IntoIterator for &'a Snapshot<K, W, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, W, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Ord, W: Ord, V> Node<K, W, V> {
+    /// Returns the root of a copy of the subtree below `link` that also
+    /// contains `entry`, or `None` if the key is already present. Only the
+    /// nodes on the search path are copied; all other subtrees are shared.
+    fn insert(link: &Link<K, W, V>, entry: Entry<K, W, V>) -> Option<Arc<Self>> {
+        let Some(node) = link else {
+            return Some(Self::new(Arc::new(entry), None, None));
+        };
+        match entry.key.cmp(&node.entry.key) {
+            Ordering::Equal => None,
+            Ordering::Less => {
+                let left = Self::insert(&node.left, entry)?;
+                Some(if left.entry.weight < node.entry.weight {
+                    let right =
+                        Self::new(node.entry.clone(), left.right.clone(), node.right.clone());
+                    Self::new(left.entry.clone(), left.left.clone(), Some(right))
+                } else {
+                    Self::new(node.entry.clone(), Some(left), node.right.clone())
+                })
+            }
+            Ordering::Greater => {
+                let right = Self::insert(&node.right, entry)?;
+                Some(if right.entry.weight < node.entry.weight {
+                    let left =
+                        Self::new(node.entry.clone(), node.left.clone(), right.left.clone());
+                    Self::new(right.entry.clone(), Some(left), right.right.clone())
+                } else {
+                    Self::new(node.entry.clone(), node.left.clone(), Some(right))
+                })
+            }
+        }
+    }
+}
+
+impl<K, W, V> Node<K, W, V> {
+    fn new(entry: Arc<Entry<K, W, V>>, left: Link<K, W, V>, right: Link<K, W, V>) -> Arc<Self> {
+        Arc::new(Self { entry, left, right })
+    }
+}
+
+impl<K, W, V> Drop for Node<K, W, V> {
+    fn drop(&mut self) {
+        // Descendants shared with another version stay alive.
+        let mut stack: Vec<_> = self.left.take().into_iter().chain(self.right.take()).collect();
+        while let Some(node) = stack.pop() {
+            if let Some(mut node) = Arc::into_inner(node) {
+                stack.extend(node.left.take());
+                stack.extend(node.right.take());
+            }
+        }
+    }
+}
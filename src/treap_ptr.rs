@@ -0,0 +1,416 @@
+//! A treap linked through raw pointers.
+//!
+//! It runs the same algorithm as [`crate::treap::Treap`]: descend to a new
+//! leaf, then walk the parent links back up to the root and rotate wherever
+//! the heap order is violated. The nodes are linked through `NonNull`
+//! instead of `Rc<RefCell<..>>`, so there are no reference counts and borrow
+//! flags to update, and no `Empty` placeholder nodes. Comparing both shows
+//! how much of the cost of `treap::Treap` is that bookkeeping.
+//!
+//! This is the only module that dereferences raw pointers; apart from it,
+//! the crate uses `unsafe` only for a cache prefetch hint in
+//! [`crate::treap_vec::frozen`]. The public API is safe: the treap owns its
+//! nodes like a tree of `Box`es, and references handed out are bound to a
+//! borrow of the treap.
+
+use std::cmp::Ordering;
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+use crate::stats::Depths;
+use crate::validate::{self, Side, Trail, ValidationError, Violation};
+
+pub struct Treap<K, W, V> {
+    root: Link<K, W, V>,
+    /// Tells the drop checker that the nodes are owned.
+    _nodes: PhantomData<Box<Node<K, W, V>>>,
+}
+
+type Link<K, W, V> = Option<NonNull<Node<K, W, V>>>;
+
+/// Every link in the tree points to a node allocated with `Box::new` that
+/// is owned by exactly one treap and freed only by `remove` or `drop`.
+struct Node<K, W, V> {
+    parent: Link<K, W, V>,
+    left: Link<K, W, V>,
+    right: Link<K, W, V>,
+    key: K,
+    weight: W,
+    value: V,
+}
+
+// SAFETY: The nodes are only reachable through the treap, so sending or
+// sharing it is the same as sending or sharing a tree of `Box`es.
+unsafe impl<K: Send, W: Send, V: Send> Send for Treap<K, W, V> {}
+unsafe impl<K: Sync, W: Sync, V: Sync> Sync for Treap<K, W, V> {}
+
+impl<K, W, V> Treap<K, W, V> {
+    pub fn new() -> Self {
+        Self {
+            root: None,
+            _nodes: PhantomData,
+        }
+    }
+
+    /// Returns the node behind a link of this treap.
+    fn node(&self, node: NonNull<Node<K, W, V>>) -> &Node<K, W, V> {
+        // SAFETY: Links of the treap always point to live nodes, and the
+        // shared borrow of `self` keeps them from being modified or freed.
+        unsafe { node.as_ref() }
+    }
+
+    /// Points the link from `parent` on the given side, or the root if
+    /// `parent` is `None`, to `child`.
+    ///
+    /// # Safety
+    ///
+    /// `parent` must be a node of this treap and no reference to it may be
+    /// alive.
+    unsafe fn set_child(&mut self, parent: Link<K, W, V>, on_left: bool, child: Link<K, W, V>) {
+        match parent {
+            None => self.root = child,
+            Some(p) if on_left => (*p.as_ptr()).left = child,
+            Some(p) => (*p.as_ptr()).right = child,
+        }
+    }
+
+    /// Replaces the link from `parent` to `old` by a link to `new`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`Treap::set_child`].
+    unsafe fn replace_child(
+        &mut self,
+        parent: Link<K, W, V>,
+        old: NonNull<Node<K, W, V>>,
+        new: Link<K, W, V>,
+    ) {
+        let on_left = parent.is_some_and(|p| (*p.as_ptr()).left == Some(old));
+        self.set_child(parent, on_left, new);
+    }
+
+    /// # Safety
+    ///
+    /// `node`, if any, must be a node of this treap and no reference to it
+    /// may be alive.
+    unsafe fn set_parent(node: Link<K, W, V>, parent: Link<K, W, V>) {
+        if let Some(n) = node {
+            (*n.as_ptr()).parent = parent;
+        }
+    }
+}
+
+impl<K, W, V> Default for Treap<K, W, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, W: Ord, V> Treap<K, W, V> {
+    /// Inserts an entry unless the key is already present.
+    pub fn insert(&mut self, ikey: K, iweight: W, ival: V) {
+        let mut current = self.root;
+        let mut parent = None;
+        let mut on_left = false;
+        while let Some(node) = current {
+            let node = self.node(node);
+            parent = current;
+            match ikey.cmp(&node.key) {
+                Ordering::Less => {
+                    on_left = true;
+                    current = node.left;
+                }
+                Ordering::Greater => {
+                    on_left = false;
+                    current = node.right;
+                }
+                Ordering::Equal => return,
+            }
+        }
+        let new = NonNull::from(Box::leak(Box::new(Node {
+            parent,
+            left: None,
+            right: None,
+            key: ikey,
+            weight: iweight,
+            value: ival,
+        })));
+        // SAFETY: `parent` is a node of this treap and the shared reference
+        // to it from the descent is gone.
+        unsafe { self.set_child(parent, on_left, Some(new)) };
+
+        // Like `treap::Treap`, every ancestor up to the root is checked, even
+        // after the new node has stopped rising.
+        let mut current = new;
+        while let Some(parent) = self.node(current).parent {
+            if self.node(current).weight < self.node(parent).weight {
+                // SAFETY: `current` is a node of this treap with a parent,
+                // and no references into the treap are alive.
+                unsafe { self.rotate_up(current) };
+            } else {
+                current = parent;
+            }
+        }
+    }
+
+    /// Lifts `node` above its parent.
+    ///
+    /// # Safety
+    ///
+    /// `node` must be a node of this treap that has a parent, and no
+    /// reference into the treap may be alive.
+    unsafe fn rotate_up(&mut self, node: NonNull<Node<K, W, V>>) {
+        let n = node.as_ptr();
+        let parent = (*n).parent.expect("rotate_up needs a parent");
+        let p = parent.as_ptr();
+        let grandparent = (*p).parent;
+        if (*p).left == Some(node) {
+            (*p).left = (*n).right;
+            Self::set_parent((*n).right, Some(parent));
+            (*n).right = Some(parent);
+        } else {
+            (*p).right = (*n).left;
+            Self::set_parent((*n).left, Some(parent));
+            (*n).left = Some(parent);
+        }
+        (*p).parent = Some(node);
+        (*n).parent = grandparent;
+        self.replace_child(grandparent, parent, Some(node));
+    }
+
+    /// Removes the entry for `key` and returns its value. The subtrees of
+    /// the removed node are merged and take its place.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let node = self.find_node(key)?;
+        // SAFETY: `node` is a node of this treap. It is unlinked before it
+        // is turned back into a `Box`, so nothing points to it afterwards,
+        // and no references into the treap are alive.
+        unsafe {
+            let n = node.as_ptr();
+            let (parent, left, right) = ((*n).parent, (*n).left, (*n).right);
+            let top = self.merge(left, right);
+            Self::set_parent(top, parent);
+            self.replace_child(parent, node, top);
+            Some(Box::from_raw(n).value)
+        }
+    }
+
+    /// Merges the subtrees rooted at `a` and `b`, where all keys in `a` are
+    /// smaller than those in `b`, and returns the new root. Its parent link
+    /// is left to the caller.
+    ///
+    /// # Safety
+    ///
+    /// `a` and `b` must be disjoint subtrees of this treap, and no
+    /// reference into the treap may be alive.
+    unsafe fn merge(&mut self, mut a: Link<K, W, V>, mut b: Link<K, W, V>) -> Link<K, W, V> {
+        // `W::cmp` may panic, and a node that is linked to its new parent is
+        // still a child of the old one until the next step. So all weights
+        // are compared first, on the same path that the relinking follows.
+        let mut b_on_top = Vec::new();
+        let (mut x_link, mut y_link) = (a, b);
+        while let (Some(x), Some(y)) = (x_link, y_link) {
+            let take_b = (*y.as_ptr()).weight < (*x.as_ptr()).weight;
+            b_on_top.push(take_b);
+            if take_b {
+                y_link = (*y.as_ptr()).left;
+            } else {
+                x_link = (*x.as_ptr()).right;
+            }
+        }
+        let mut b_on_top = b_on_top.into_iter();
+
+        let mut root = None;
+        // Node whose child link the next merged root is written to, and
+        // whether that is its left link.
+        let mut hook = None;
+        loop {
+            let top = match (a, b) {
+                (Some(_), Some(_)) if b_on_top.next() == Some(true) => b,
+                (Some(_), _) => a,
+                (None, _) => b,
+            };
+            match hook {
+                Some((parent, on_left)) => {
+                    self.set_child(Some(parent), on_left, top);
+                    Self::set_parent(top, Some(parent));
+                }
+                None => root = top,
+            }
+            let (Some(x), Some(y)) = (a, b) else {
+                return root;
+            };
+            if top == b {
+                hook = Some((y, true));
+                b = (*y.as_ptr()).left;
+            } else {
+                hook = Some((x, false));
+                a = (*x.as_ptr()).right;
+            }
+        }
+    }
+}
+
+impl<K: Ord, W, V> Treap<K, W, V> {
+    pub fn find(&self, find_key: &K) -> Option<&V> {
+        Some(&self.node(self.find_node(find_key)?).value)
+    }
+
+    fn find_node(&self, find_key: &K) -> Link<K, W, V> {
+        let mut current = self.root;
+        while let Some(node) = current {
+            let n = self.node(node);
+            current = match find_key.cmp(&n.key) {
+                Ordering::Less => n.left,
+                Ordering::Greater => n.right,
+                Ordering::Equal => return Some(node),
+            };
+        }
+        None
+    }
+}
+
+impl<K: Ord, W: Ord, V> Treap<K, W, V> {
+    /// Checks the search tree order of the keys, the heap order of the
+    /// weights and the parent link of every node.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut trail = Trail::default();
+        let mut stack = vec![(self.root, 0, None, None, None, None)];
+        while let Some((link, depth, side, parent, lower, upper)) = stack.pop() {
+            trail.enter(depth, side);
+            let Some(node) = link else { continue };
+            let n = self.node(node);
+            if n.parent != parent {
+                return Err(trail.error(Violation::ParentLink));
+            }
+            if !validate::in_bounds(&n.key, lower, upper) {
+                return Err(trail.error(Violation::KeyOrder));
+            }
+            if parent.is_some_and(|p| n.weight < self.node(p).weight) {
+                return Err(trail.error(Violation::HeapOrder));
+            }
+            let child_depth = depth + 1;
+            stack.push((n.right, child_depth, Some(Side::Right), link, Some(&n.key), upper));
+            stack.push((n.left, child_depth, Some(Side::Left), link, lower, Some(&n.key)));
+        }
+        Ok(())
+    }
+}
+
+impl<K, W, V> Treap<K, W, V> {
+    pub fn height(&self) -> usize {
+        self.depths().height()
+    }
+
+    pub fn average_depth(&self) -> f64 {
+        self.depths().average_depth()
+    }
+
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        self.depths().into_histogram()
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.depths().leaves()
+    }
+
+    fn depths(&self) -> Depths {
+        let mut depths = Depths::default();
+        let mut stack = vec![(self.root, 0)];
+        while let Some((link, depth)) = stack.pop() {
+            let Some(node) = link else { continue };
+            let n = self.node(node);
+            depths.add(depth, n.left.is_none() && n.right.is_none());
+            stack.push((n.left, depth + 1));
+            stack.push((n.right, depth + 1));
+        }
+        depths
+    }
+
+    /// Returns an iterator over all entries in ascending key order.
+    pub fn iter(&self) -> Iter<'_, K, W, V> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            _treap: PhantomData,
+        };
+        iter.push_left(self.root);
+        iter
+    }
+
+    /// Returns the keys and weights in pre-order, see
+    /// [`crate::Treap::shape`].
+    pub fn shape(&self) -> Vec<(K, W)>
+    where
+        K: Clone,
+        W: Clone,
+    {
+        let mut shape = Vec::new();
+        let mut stack = vec![self.root];
+        while let Some(link) = stack.pop() {
+            let Some(node) = link else { continue };
+            let n = self.node(node);
+            shape.push((n.key.clone(), n.weight.clone()));
+            stack.push(n.right);
+            stack.push(n.left);
+        }
+        shape
+    }
+}
+
+impl<K, W, V> Drop for Treap<K, W, V> {
+    fn drop(&mut self) {
+        let mut stack: Vec<_> = self.root.take().into_iter().collect();
+        while let Some(node) = stack.pop() {
+            // SAFETY: Every node is reachable through exactly one link, so
+            // each is turned back into a `Box` exactly once.
+            let node = unsafe { Box::from_raw(node.as_ptr()) };
+            stack.extend(node.left);
+            stack.extend(node.right);
+        }
+    }
+}
+
+impl<K: Debug, W, V: Debug> Debug for Treap<K, W, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator returned by [`Treap::iter`].
+pub struct Iter<'a, K, W, V> {
+    stack: Vec<NonNull<Node<K, W, V>>>,
+    _treap: PhantomData<&'a Treap<K, W, V>>,
+}
+
+impl<'a, K, W, V> Iter<'a, K, W, V> {
+    fn push_left(&mut self, mut link: Link<K, W, V>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            // SAFETY: The iterator borrows the treap, see `Treap::node`.
+            link = unsafe { node.as_ref() }.left;
+        }
+    }
+}
+
+impl<'a, K, W, V> Iterator for Iter<'a, K, W, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        // SAFETY: The treap is borrowed for `'a`, so the node stays alive
+        // and unmodified for that long.
+        let node: &'a Node<K, W, V> = unsafe { node.as_ref() };
+        self.push_left(node.right);
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<'a, K, W, V> IntoIterator for &'a Treap<K, W, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, W, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
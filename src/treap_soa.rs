@@ -0,0 +1,646 @@
+//! A treap with the same API as [`crate::treap_vec::Treap`], but with a
+//! struct-of-arrays layout.
+//!
+//! Keys, child links, parent links, weights and values each live in their
+//! own `Vec`, all indexed by the same node index. A search only reads keys
+//! and child links, so it touches fewer cache lines than in `treap_vec`,
+//! where every node carries its weight and value along. Rotations rewire
+//! links instead of moving entries, since an entry is spread over five
+//! vectors.
+//!
+//! There are no snapshots and no serde support.
+
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::ops::Index;
+
+use crate::ascii;
+use crate::dot::Dot;
+use crate::stats::Depths;
+use crate::treap_vec::{ArenaIndex, CapacityError};
+use crate::validate::{self, Side, Trail, ValidationError, Violation};
+
+const LEFT: usize = 0;
+const RIGHT: usize = 1;
+
+#[derive(Debug, Clone)]
+pub struct Treap<K, W, V, I = u32> {
+    keys: Vec<K>,
+    /// Left and right child of every node, `I::NIL` if missing.
+    children: Vec<[I; 2]>,
+    parents: Vec<I>,
+    weights: Vec<W>,
+    values: Vec<V>,
+    /// Index of the root node, `I::NIL` if the treap is empty.
+    root: I,
+}
+
+impl<K: Ord, W: Ord, V, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Inserts an entry unless the key is already present.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the treap is full, see [`Treap::try_insert`].
+    pub fn insert(&mut self, ikey: K, iweight: W, ival: V) {
+        if let Err(err) = self.try_insert(ikey, iweight, ival) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Like [`Treap::insert`], but returns an error instead of panicking if
+    /// the key is new and the arena already holds `I::MAX_NODES` entries.
+    pub fn try_insert(&mut self, ikey: K, iweight: W, ival: V) -> Result<(), CapacityError> {
+        let mut current = self.root;
+        let mut parent = I::NIL;
+        let mut side = LEFT;
+        while current != I::NIL {
+            parent = current;
+            side = match ikey.cmp(self.key(current)) {
+                Ordering::Less => LEFT,
+                Ordering::Greater => RIGHT,
+                Ordering::Equal => return Ok(()),
+            };
+            current = self.children[current.to_usize()][side];
+        }
+        let idx = self.push(parent, ikey, iweight, ival)?;
+        self.set_child(parent, side, idx);
+
+        // Only the new node can violate the heap order, so it is rotated up
+        // until its parent is not heavier.
+        loop {
+            let parent = self.parent(idx);
+            if parent == I::NIL || self.weight(parent) <= self.weight(idx) {
+                return Ok(());
+            }
+            self.rotate_up(idx);
+        }
+    }
+
+    /// Lifts `idx` above its parent.
+    fn rotate_up(&mut self, idx: I) {
+        let parent = self.parent(idx);
+        let grandparent = self.parent(parent);
+        let side = self.side_of(parent, idx);
+        let inner = self.children[idx.to_usize()][1 - side];
+        self.children[parent.to_usize()][side] = inner;
+        self.set_parent(inner, parent);
+        self.children[idx.to_usize()][1 - side] = parent;
+        self.parents[parent.to_usize()] = idx;
+        self.replace_child(grandparent, parent, idx);
+        self.parents[idx.to_usize()] = grandparent;
+    }
+}
+
+impl<K: Ord, W, V, I: ArenaIndex> Treap<K, W, V, I> {
+    pub fn find<'a>(&'a self, find_key: &K) -> Option<&'a V> {
+        Some(&self.values[self.find_idx(find_key)?.to_usize()])
+    }
+
+    fn find_idx(&self, find_key: &K) -> Option<I> {
+        let mut current = self.root;
+        while current != I::NIL {
+            let side = match find_key.cmp(self.key(current)) {
+                Ordering::Less => LEFT,
+                Ordering::Greater => RIGHT,
+                Ordering::Equal => return Some(current),
+            };
+            current = self.children[current.to_usize()][side];
+        }
+        None
+    }
+}
+
+impl<K: Ord, W: Ord, V, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Removes the entry for `key` and returns its value. The subtrees of
+    /// the removed node are merged and take its place.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let idx = self.find_idx(key)?;
+        let [left, right] = self.children[idx.to_usize()];
+        let parent = self.parent(idx);
+        let top = self.merge(left, right);
+        self.replace_child(parent, idx, top);
+        self.set_parent(top, parent);
+        Some(self.free(idx, |_, _| {}).1)
+    }
+}
+
+impl<K, W, V> Treap<K, W, V> {
+    pub fn new() -> Self {
+        Self::with_index()
+    }
+
+    /// Creates an empty treap with room for `cap` entries.
+    pub fn with_capacity(cap: usize) -> Self {
+        Self::with_index_and_capacity(cap)
+    }
+}
+
+impl<K, W, V, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Creates an empty treap with the index type `I`, see
+    /// [`crate::treap_vec::Treap::with_index`].
+    pub fn with_index() -> Self {
+        Self::with_index_and_capacity(0)
+    }
+
+    /// Creates an empty treap with the index type `I` and room for `cap`
+    /// entries, but never more than `I::MAX_NODES`.
+    pub fn with_index_and_capacity(cap: usize) -> Self {
+        let cap = cap.min(I::MAX_NODES);
+        Self {
+            keys: Vec::with_capacity(cap),
+            children: Vec::with_capacity(cap),
+            parents: Vec::with_capacity(cap),
+            weights: Vec::with_capacity(cap),
+            values: Vec::with_capacity(cap),
+            root: I::NIL,
+        }
+    }
+
+    fn key(&self, idx: I) -> &K {
+        &self.keys[idx.to_usize()]
+    }
+
+    fn weight(&self, idx: I) -> &W {
+        &self.weights[idx.to_usize()]
+    }
+
+    fn parent(&self, idx: I) -> I {
+        self.parents[idx.to_usize()]
+    }
+
+    fn push(&mut self, parent: I, key: K, weight: W, value: V) -> Result<I, CapacityError> {
+        let idx = self.keys.len();
+        if idx >= I::MAX_NODES {
+            return Err(CapacityError::for_index::<I>());
+        }
+        self.keys.push(key);
+        self.children.push([I::NIL; 2]);
+        self.parents.push(parent);
+        self.weights.push(weight);
+        self.values.push(value);
+        Ok(I::from_usize(idx))
+    }
+
+    fn set_parent(&mut self, idx: I, parent: I) {
+        if idx != I::NIL {
+            self.parents[idx.to_usize()] = parent;
+        }
+    }
+
+    /// Links `child` below `parent`, or makes it the root if `parent` is
+    /// `I::NIL`.
+    fn set_child(&mut self, parent: I, side: usize, child: I) {
+        if parent == I::NIL {
+            self.root = child;
+        } else {
+            self.children[parent.to_usize()][side] = child;
+        }
+    }
+
+    /// Returns on which side of `parent` the node `child` hangs.
+    fn side_of(&self, parent: I, child: I) -> usize {
+        if parent != I::NIL && self.children[parent.to_usize()][LEFT] == child {
+            LEFT
+        } else {
+            RIGHT
+        }
+    }
+
+    /// Replaces the link from `parent` to `old` by a link to `new`.
+    fn replace_child(&mut self, parent: I, old: I, new: I) {
+        let side = self.side_of(parent, old);
+        self.set_child(parent, side, new);
+    }
+
+    /// Removes the node `idx`, which must not be linked from any other node,
+    /// by moving the last node of the arena into its place. `moved` is
+    /// called with the old and new index of that node.
+    fn free<F>(&mut self, idx: I, mut moved: F) -> (K, V)
+    where
+        F: FnMut(I, I),
+    {
+        let i = idx.to_usize();
+        let key = self.keys.swap_remove(i);
+        self.children.swap_remove(i);
+        self.parents.swap_remove(i);
+        self.weights.swap_remove(i);
+        let value = self.values.swap_remove(i);
+        let from = I::from_usize(self.keys.len());
+        if from != idx {
+            let parent = self.parent(idx);
+            if parent != I::NIL || self.root == from {
+                self.replace_child(parent, from, idx);
+            }
+            let [left, right] = self.children[i];
+            self.set_parent(left, idx);
+            self.set_parent(right, idx);
+            moved(from, idx);
+        }
+        (key, value)
+    }
+}
+
+impl<K, W, V, I: ArenaIndex> Default for Treap<K, W, V, I> {
+    fn default() -> Self {
+        Self::with_index()
+    }
+}
+
+impl<K, W, V, I: ArenaIndex> Treap<K, W, V, I> {
+    pub fn height(&self) -> usize {
+        self.depths().height()
+    }
+
+    pub fn average_depth(&self) -> f64 {
+        self.depths().average_depth()
+    }
+
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        self.depths().into_histogram()
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.depths().leaves()
+    }
+
+    fn depths(&self) -> Depths {
+        let mut depths = Depths::default();
+        let mut stack = vec![(self.root, 0)];
+        while let Some((idx, depth)) = stack.pop() {
+            if idx == I::NIL {
+                continue;
+            }
+            let [left, right] = self.children[idx.to_usize()];
+            depths.add(depth, left == I::NIL && right == I::NIL);
+            stack.push((left, depth + 1));
+            stack.push((right, depth + 1));
+        }
+        depths
+    }
+}
+
+impl<K: Ord, W: Ord, V, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Checks the search tree order of the keys, the heap order of the
+    /// weights and the parent link of every node. Every node of the arena
+    /// must be reachable from the root exactly once.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut visited = vec![false; self.keys.len()];
+        let mut trail = Trail::default();
+        let mut stack = vec![(self.root, 0, None, I::NIL, None, None)];
+        while let Some((idx, depth, side, parent, lower, upper)) = stack.pop() {
+            trail.enter(depth, side);
+            if idx == I::NIL {
+                continue;
+            }
+            let i = idx.to_usize();
+            if i >= self.keys.len() {
+                return Err(trail.error(Violation::IndexOutOfBounds(i)));
+            }
+            if std::mem::replace(&mut visited[i], true) {
+                return Err(trail.error(Violation::SharedNode));
+            }
+            if self.parents[i] != parent {
+                return Err(trail.error(Violation::ParentLink));
+            }
+            let key = &self.keys[i];
+            if !validate::in_bounds(key, lower, upper) {
+                return Err(trail.error(Violation::KeyOrder));
+            }
+            if parent != I::NIL && self.weights[i] < *self.weight(parent) {
+                return Err(trail.error(Violation::HeapOrder));
+            }
+            let [left, right] = self.children[i];
+            let child_depth = depth + 1;
+            stack.push((right, child_depth, Some(Side::Right), idx, Some(key), upper));
+            stack.push((left, child_depth, Some(Side::Left), idx, lower, Some(key)));
+        }
+        match visited.iter().position(|v| !v) {
+            Some(idx) => Err(ValidationError {
+                violation: Violation::Unreachable(idx),
+                path: Vec::new(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<K, W: Ord, V, I: ArenaIndex> Treap<K, W, V, I> {
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.extract_if(|k, v| !f(k, v)).for_each(drop);
+    }
+
+    /// Returns an iterator that removes and yields every entry for which
+    /// `pred` returns `true`, in ascending key order. Works like
+    /// [`crate::treap_vec::Treap::extract_if`].
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, W, V, I, F>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let root = self.root;
+        ExtractIf {
+            treap: self,
+            stack: vec![Visit::Enter(root)],
+            pred,
+        }
+    }
+
+    /// Merges the subtrees rooted at `a` and `b`, where all keys in `a` are
+    /// smaller than those in `b`, and returns the index of the new root.
+    /// The parent link of the new root is left to the caller.
+    fn merge(&mut self, mut a: I, mut b: I) -> I {
+        let mut root = I::NIL;
+        // Node whose child link the next merged root is written to, and the
+        // side of that link.
+        let mut hook = None;
+        loop {
+            let top = if a == I::NIL {
+                b
+            } else if b == I::NIL || self.weight(a) <= self.weight(b) {
+                a
+            } else {
+                b
+            };
+            match hook {
+                Some((parent, side)) => {
+                    self.set_child(parent, side, top);
+                    self.set_parent(top, parent);
+                }
+                None => root = top,
+            }
+            if a == I::NIL || b == I::NIL {
+                return root;
+            }
+            if top == b {
+                hook = Some((b, LEFT));
+                b = self.children[b.to_usize()][LEFT];
+            } else {
+                hook = Some((a, RIGHT));
+                a = self.children[a.to_usize()][RIGHT];
+            }
+        }
+    }
+}
+
+/// Iterator returned by [`Treap::extract_if`].
+pub struct ExtractIf<'a, K, W: Ord, V, I: ArenaIndex, F> {
+    treap: &'a mut Treap<K, W, V, I>,
+    stack: Vec<Visit<I>>,
+    pred: F,
+}
+
+enum Visit<I> {
+    Enter(I),
+    Check(I),
+    /// Merges the detached subtree `left` into the subtree hanging from
+    /// `parent` (or the root if that is `I::NIL`) on the given side.
+    Merge { parent: I, side: usize, left: I },
+}
+
+impl<'a, K, W: Ord, V, I: ArenaIndex, F> ExtractIf<'a, K, W, V, I, F> {
+    fn merge_back(&mut self, parent: I, side: usize, left: I) {
+        let treap = &mut *self.treap;
+        let right = if parent == I::NIL {
+            treap.root
+        } else {
+            treap.children[parent.to_usize()][side]
+        };
+        let top = treap.merge(left, right);
+        treap.set_child(parent, side, top);
+        treap.set_parent(top, parent);
+    }
+
+    /// Unlinks the node at `idx` like [`crate::treap_vec::Treap::extract_if`]
+    /// describes and returns its entry.
+    fn extract(&mut self, idx: I) -> (K, V) {
+        let treap = &mut *self.treap;
+        let [left, right] = treap.children[idx.to_usize()];
+        let parent = treap.parent(idx);
+        let side = treap.side_of(parent, idx);
+        treap.set_child(parent, side, right);
+        treap.set_parent(right, parent);
+        treap.set_parent(left, I::NIL);
+        self.stack.push(Visit::Merge { parent, side, left });
+        self.stack.push(Visit::Enter(right));
+        // The last node of the arena moves into the freed place, so pending
+        // visits have to follow it.
+        let stack = &mut self.stack;
+        treap.free(idx, |from, to| {
+            let rename = |idx: &mut I| {
+                if *idx == from {
+                    *idx = to;
+                }
+            };
+            for visit in stack.iter_mut() {
+                match visit {
+                    Visit::Enter(idx) | Visit::Check(idx) => rename(idx),
+                    Visit::Merge { parent, left, .. } => {
+                        rename(parent);
+                        rename(left);
+                    }
+                }
+            }
+        })
+    }
+}
+
+impl<'a, K, W: Ord, V, I: ArenaIndex, F> Iterator for ExtractIf<'a, K, W, V, I, F>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(visit) = self.stack.pop() {
+            match visit {
+                Visit::Enter(idx) if idx == I::NIL => {}
+                Visit::Enter(idx) => {
+                    self.stack.push(Visit::Check(idx));
+                    self.stack.push(Visit::Enter(self.treap.children[idx.to_usize()][LEFT]));
+                }
+                Visit::Check(idx) => {
+                    let i = idx.to_usize();
+                    let treap = &mut *self.treap;
+                    if !(self.pred)(&treap.keys[i], &mut treap.values[i]) {
+                        self.stack.push(Visit::Enter(treap.children[i][RIGHT]));
+                        continue;
+                    }
+                    return Some(self.extract(idx));
+                }
+                Visit::Merge { parent, side, left } => self.merge_back(parent, side, left),
+            }
+        }
+        None
+    }
+}
+
+impl<'a, K, W: Ord, V, I: ArenaIndex, F> Drop for ExtractIf<'a, K, W, V, I, F> {
+    fn drop(&mut self) {
+        while let Some(visit) = self.stack.pop() {
+            if let Visit::Merge { parent, side, left } = visit {
+                self.merge_back(parent, side, left);
+            }
+        }
+    }
+}
+
+impl<K, W, V, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Returns an iterator over all entries in ascending key order.
+    pub fn iter(&self) -> Iter<'_, K, V, I> {
+        let mut iter = Iter {
+            keys: &self.keys,
+            values: &self.values,
+            children: &self.children,
+            stack: Vec::new(),
+        };
+        iter.push_left(self.root);
+        iter
+    }
+
+    /// Returns the keys and weights in pre-order, see
+    /// [`crate::treap_vec::Treap::shape`].
+    pub fn shape(&self) -> Vec<(K, W)>
+    where
+        K: Clone,
+        W: Clone,
+    {
+        let mut shape = Vec::with_capacity(self.keys.len());
+        let mut stack = vec![self.root];
+        while let Some(idx) = stack.pop() {
+            if idx == I::NIL {
+                continue;
+            }
+            let i = idx.to_usize();
+            shape.push((self.keys[i].clone(), self.weights[i].clone()));
+            let [left, right] = self.children[i];
+            stack.push(right);
+            stack.push(left);
+        }
+        shape
+    }
+}
+
+/// Iterator returned by [`Treap::iter`].
+pub struct Iter<'a, K, V, I = u32> {
+    keys: &'a [K],
+    values: &'a [V],
+    children: &'a [[I; 2]],
+    stack: Vec<I>,
+}
+
+impl<'a, K, V, I: ArenaIndex> Iter<'a, K, V, I> {
+    fn push_left(&mut self, mut idx: I) {
+        while idx != I::NIL {
+            self.stack.push(idx);
+            idx = self.children[idx.to_usize()][LEFT];
+        }
+    }
+}
+
+impl<'a, K, V, I: ArenaIndex> Iterator for Iter<'a, K, V, I> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let i = self.stack.pop()?.to_usize();
+        self.push_left(self.children[i][RIGHT]);
+        Some((&self.keys[i], &self.values[i]))
+    }
+}
+
+impl<'a, K, W, V, I: ArenaIndex> IntoIterator for &'a Treap<K, W, V, I> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, I>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Display, W: Display, V, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Renders the treap as an indented text tree with one `key:weight` line per
+    /// node. Subtrees below `max_depth` (the root has depth `0`) and all
+    /// nodes after the first `max_nodes` in pre-order are shown as `...`.
+    pub fn to_ascii(&self, max_depth: usize, max_nodes: usize) -> String {
+        ascii::render(self.root, max_depth, max_nodes, |&idx| {
+            let i = idx.to_usize();
+            let [left, right] = *self.children.get(i)?;
+            Some((format!("{}:{}", self.keys[i], self.weights[i]), left, right))
+        })
+    }
+}
+
+impl<K: Display, W: Display, V: Display, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Writes the treap in Graphviz DOT format. Empty children are drawn as
+    /// small grey dots.
+    pub fn to_dot<T: Write>(&self, writer: T) -> io::Result<()> {
+        self.to_dot_with_max_depth(writer, usize::MAX)
+    }
+
+    /// Like [`Treap::to_dot`], but replaces every subtree below `max_depth`
+    /// by a `...` node. The root has depth `0`.
+    pub fn to_dot_with_max_depth<T: Write>(&self, writer: T, max_depth: usize) -> io::Result<()> {
+        let mut dot = Dot::begin(writer)?;
+        let mut stack = vec![(self.root, 0, None)];
+        while let Some((idx, depth, parent)) = stack.pop() {
+            if idx == I::NIL {
+                dot.empty(parent)?;
+            } else if depth > max_depth {
+                dot.cut(parent)?;
+            } else {
+                let i = idx.to_usize();
+                let lines = [
+                    format!("{}: {}", self.keys[i], self.values[i]),
+                    format!("w = {}", self.weights[i]),
+                ];
+                let id = dot.node(parent, &lines)?;
+                let [left, right] = self.children[i];
+                stack.push((right, depth + 1, Some(id)));
+                stack.push((left, depth + 1, Some(id)));
+            }
+        }
+        dot.end()
+    }
+}
+
+impl<K: PartialEq, W, V: PartialEq, I: ArenaIndex> PartialEq for Treap<K, W, V, I> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Eq, W, V: Eq, I: ArenaIndex> Eq for Treap<K, W, V, I> {}
+
+impl<K: PartialOrd, W, V: PartialOrd, I: ArenaIndex> PartialOrd for Treap<K, W, V, I> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<K: Ord, W, V: Ord, I: ArenaIndex> Ord for Treap<K, W, V, I> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<K: Hash, W, V: Hash, I: ArenaIndex> Hash for Treap<K, W, V, I> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut len = 0;
+        for entry in self {
+            entry.hash(state);
+            len += 1;
+        }
+        state.write_usize(len);
+    }
+}
+
+impl<K: Ord, W, V, I: ArenaIndex> Index<&K> for Treap<K, W, V, I> {
+    type Output = V;
+
+    fn index(&self, key: &K) -> &V {
+        self.find(key).expect("no entry found for key")
+    }
+}
@@ -1,211 +1,921 @@
+pub mod frozen;
+pub mod snapshot;
+
 use std::cmp::Ordering;
+use std::fmt::{self, Debug, Display};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::ops::Index;
 
-#[derive(Debug)]
-pub struct Treap<K, W, V>(Vec<TreapNode<K, W, V>>);
-
-#[derive(Debug)]
-enum TreapNode<K, W, V> {
-    Empty(Option<usize>),
-    Node {
-        parent: Option<usize>,
-        key: K,
-        weight: W,
-        value: V,
-        left: usize,
-        right: usize,
-    },
+use crate::ascii;
+use crate::dot::Dot;
+use crate::stats::Depths;
+use crate::validate::{self, Side, Trail, ValidationError, Violation};
+
+/// Unsigned integer type of the links between the nodes of the arena.
+///
+/// Narrower indices make the nodes smaller, but limit the number of entries
+/// a treap can hold to [`ArenaIndex::MAX_NODES`].
+pub trait ArenaIndex: Copy + Ord + Debug {
+    /// Reserved for a missing parent or child.
+    const NIL: Self;
+    /// Number of nodes that can be addressed without using `NIL`.
+    const MAX_NODES: usize;
+
+    /// Converts an arena position below `MAX_NODES`.
+    fn from_usize(idx: usize) -> Self;
+
+    fn to_usize(self) -> usize;
+}
+
+macro_rules! arena_index {
+    ($($t:ty),*) => {$(
+        impl ArenaIndex for $t {
+            const NIL: Self = <$t>::MAX;
+            const MAX_NODES: usize = <$t>::MAX as usize;
+
+            fn from_usize(idx: usize) -> Self {
+                debug_assert!(idx < Self::MAX_NODES);
+                idx as $t
+            }
+
+            fn to_usize(self) -> usize {
+                self as usize
+            }
+        }
+    )*};
 }
 
-impl<K: Ord, W: Ord, V> Treap<K, W, V> {
+arena_index!(u16, u32, usize);
+
+/// Error returned by [`Treap::try_insert`] when the arena already holds as
+/// many nodes as its index type can address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacityError {
+    pub max_nodes: usize,
+    pub index_type: &'static str,
+}
+
+impl CapacityError {
+    pub(crate) fn for_index<I: ArenaIndex>() -> Self {
+        Self {
+            max_nodes: I::MAX_NODES,
+            index_type: std::any::type_name::<I>(),
+        }
+    }
+}
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "treap_vec::Treap with {} indices cannot hold more than {} entries",
+            self.index_type, self.max_nodes
+        )
+    }
+}
+
+impl std::error::Error for CapacityError {}
+
+/// A treap stored in a single `Vec`. The index type `I` defaults to `usize`,
+/// see [`ArenaIndex`].
+#[derive(Clone)]
+pub struct Treap<K, W, V, I = usize> {
+    nodes: Vec<TreapNode<K, W, V, I>>,
+    /// Index of the root node, `I::NIL` if the treap is empty.
+    root: I,
+}
+
+/// How [`Treap::insert`] restores the heap order of the weights. Both
+/// strategies build exactly the same tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InsertStrategy {
+    /// Descends only to the first node that is heavier than the new entry
+    /// and splits that subtree around the new key. Parent links are only
+    /// written, never followed, and no node above the new one is touched.
+    #[default]
+    TopDown,
+    /// Adds the new entry as a leaf, then follows the parent links back up
+    /// to the root and rotates wherever the heap order is violated.
+    BottomUp,
+}
+
+/// The arena holds exactly one slot per entry. Links are indices into the
+/// arena, with `I::NIL` where there is no parent or child.
+#[derive(Debug, Clone)]
+struct TreapNode<K, W, V, I> {
+    parent: I,
+    left: I,
+    right: I,
+    key: K,
+    weight: W,
+    value: V,
+}
+
+impl<K: Ord, W: Ord, V, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Inserts an entry unless the key is already present.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the treap is full, see [`Treap::try_insert`].
     pub fn insert(&mut self, ikey: K, iweight: W, ival: V) {
-        let mut current = 0;
-        let mut curr_parent = None;
-        loop {
-            if self.0[current].is_empty() {
-                self.0.push(TreapNode::Empty(Some(current)));
-                self.0.push(TreapNode::Empty(Some(current)));
-                let left = self.0.len() - 1;
-                let right = self.0.len() - 2;
-                self.0[current] = TreapNode::Node {
-                    parent: curr_parent,
-                    key: ikey,
-                    weight: iweight,
-                    value: ival,
-                    left,
-                    right,
-                };
+        self.insert_with(InsertStrategy::default(), ikey, iweight, ival);
+    }
+
+    /// Like [`Treap::insert`], but returns an error instead of panicking if
+    /// the key is new and the arena already holds `I::MAX_NODES` entries.
+    pub fn try_insert(&mut self, ikey: K, iweight: W, ival: V) -> Result<(), CapacityError> {
+        self.try_insert_with(InsertStrategy::default(), ikey, iweight, ival)
+    }
+
+    /// Like [`Treap::insert`], but with the given strategy.
+    pub fn insert_with(&mut self, strategy: InsertStrategy, ikey: K, iweight: W, ival: V) {
+        if let Err(err) = self.try_insert_with(strategy, ikey, iweight, ival) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Like [`Treap::try_insert`], but with the given strategy.
+    pub fn try_insert_with(
+        &mut self,
+        strategy: InsertStrategy,
+        ikey: K,
+        iweight: W,
+        ival: V,
+    ) -> Result<(), CapacityError> {
+        match strategy {
+            InsertStrategy::TopDown => self.insert_top_down(ikey, iweight, ival),
+            InsertStrategy::BottomUp => self.insert_bottom_up(ikey, iweight, ival),
+        }
+    }
+
+    fn insert_top_down(&mut self, ikey: K, iweight: W, ival: V) -> Result<(), CapacityError> {
+        // The weights grow along every path, so all nodes above the new one
+        // are found on the way down.
+        let mut current = self.root;
+        let mut parent = I::NIL;
+        let mut on_left = false;
+        while current != I::NIL {
+            let node = self.node(current);
+            if iweight < node.weight {
                 break;
             }
-            let (ord, left, right) = {
-                let c = &self.0[current];
-                let TreapNode::Node {
-                    key,
-                    left,
-                    right,
-                    ..
-                } = &*c else { unreachable!() };
-                let ord = ikey.cmp(key);
-                (ord, *left, *right)
-            };
-            curr_parent = Some(current);
-            match ord {
-                Ordering::Less => current = left,
-                Ordering::Greater => current = right,
-                Ordering::Equal => return,
+            parent = current;
+            match ikey.cmp(&node.key) {
+                Ordering::Less => {
+                    on_left = true;
+                    current = node.left;
+                }
+                Ordering::Greater => {
+                    on_left = false;
+                    current = node.right;
+                }
+                Ordering::Equal => return Ok(()),
             }
         }
+        // `K::cmp` may panic, so the keys below are compared before anything
+        // is pushed or relinked.
+        let Some(smaller) = self.split_path(current, &ikey) else {
+            return Ok(());
+        };
+        let idx = self.push(TreapNode {
+            parent,
+            left: I::NIL,
+            right: I::NIL,
+            key: ikey,
+            weight: iweight,
+            value: ival,
+        })?;
+        self.set_child(parent, on_left, idx);
+        self.split(current, idx, smaller);
+        Ok(())
+    }
 
-        loop {
-            let parent = self.0[current].parent_idx();
-            let parent_idx = if let Some(p) = parent {
-                p
+    /// Returns for each node on the search path for `key` below `current`
+    /// whether its key is smaller, or `None` if `key` is present.
+    fn split_path(&self, mut current: I, key: &K) -> Option<Vec<bool>> {
+        let mut smaller = Vec::new();
+        while current != I::NIL {
+            let node = self.node(current);
+            match key.cmp(&node.key) {
+                Ordering::Less => {
+                    smaller.push(false);
+                    current = node.left;
+                }
+                Ordering::Greater => {
+                    smaller.push(true);
+                    current = node.right;
+                }
+                Ordering::Equal => return None,
+            }
+        }
+        Some(smaller)
+    }
+
+    /// Distributes the subtree rooted at `current` over the two children of
+    /// the new node `idx`: smaller keys to the left, larger to the right.
+    /// `smaller` holds the decisions of [`Self::split_path`].
+    fn split(&mut self, mut current: I, idx: I, smaller: Vec<bool>) {
+        let mut left_hook = (idx, true);
+        let mut right_hook = (idx, false);
+        for smaller in smaller {
+            let node = self.node(current);
+            let (hook, next) = if smaller {
+                let next = node.right;
+                (std::mem::replace(&mut left_hook, (current, false)), next)
             } else {
-                break;
+                let next = node.left;
+                (std::mem::replace(&mut right_hook, (current, true)), next)
             };
-            self.restore_heap_property(parent_idx);
-            current = parent_idx;
+            self.set_child(hook.0, hook.1, current);
+            self.set_parent(current, hook.0);
+            current = next;
+        }
+        self.set_child(left_hook.0, left_hook.1, I::NIL);
+        self.set_child(right_hook.0, right_hook.1, I::NIL);
+    }
+
+    fn insert_bottom_up(&mut self, ikey: K, iweight: W, ival: V) -> Result<(), CapacityError> {
+        let mut current = self.root;
+        let mut parent = I::NIL;
+        let mut on_left = false;
+        while current != I::NIL {
+            let node = self.node(current);
+            parent = current;
+            match ikey.cmp(&node.key) {
+                Ordering::Less => {
+                    on_left = true;
+                    current = node.left;
+                }
+                Ordering::Greater => {
+                    on_left = false;
+                    current = node.right;
+                }
+                Ordering::Equal => return Ok(()),
+            }
+        }
+        let idx = self.push(TreapNode {
+            parent,
+            left: I::NIL,
+            right: I::NIL,
+            key: ikey,
+            weight: iweight,
+            value: ival,
+        })?;
+        self.set_child(parent, on_left, idx);
+
+        let mut current = parent;
+        while current != I::NIL {
+            self.restore_heap_property(current);
+            current = self.node(current).parent;
         }
+        Ok(())
     }
 
-    fn restore_heap_property(&mut self, parent_idx: usize) -> ShouldRestore {
-        if self.0[parent_idx].is_empty() {
+    fn restore_heap_property(&mut self, idx: I) -> ShouldRestore {
+        let node = self.node(idx);
+        let lighter = |child: I| child != I::NIL && self.node(child).weight < node.weight;
+        let sr = if lighter(node.left) {
+            ShouldRestore::Left
+        } else if lighter(node.right) {
+            ShouldRestore::Right
+        } else {
             ShouldRestore::Nothing
+        };
+        match sr {
+            ShouldRestore::Left => self.rotate_right(idx),
+            ShouldRestore::Right => self.rotate_left(idx),
+            ShouldRestore::Nothing => {}
+        }
+        sr
+    }
+
+    /// Lifts the left child of `idx` above it. The entries move instead of
+    /// the nodes, so `idx` stays the root of the subtree and the link from
+    /// its parent does not change.
+    fn rotate_right(&mut self, idx: I) {
+        let x = self.node(idx).left;
+        self.swap_entries(idx, x);
+        let (xleft, xright) = (self.node(x).left, self.node(x).right);
+        let zright = self.node(idx).right;
+        let top = self.node_mut(idx);
+        top.left = xleft;
+        top.right = x;
+        let z = self.node_mut(x);
+        z.left = xright;
+        z.right = zright;
+        self.set_parent(xleft, idx);
+        self.set_parent(zright, x);
+    }
+
+    /// Lifts the right child of `idx` above it, see [`Treap::rotate_right`].
+    fn rotate_left(&mut self, idx: I) {
+        let x = self.node(idx).right;
+        self.swap_entries(idx, x);
+        let (xleft, xright) = (self.node(x).left, self.node(x).right);
+        let zleft = self.node(idx).left;
+        let top = self.node_mut(idx);
+        top.left = x;
+        top.right = xright;
+        let z = self.node_mut(x);
+        z.left = zleft;
+        z.right = xleft;
+        self.set_parent(xright, idx);
+        self.set_parent(zleft, x);
+    }
+}
+
+impl<K: Ord, W, V, I: ArenaIndex> Treap<K, W, V, I> {
+    pub fn find<'a>(&'a self, find_key: &K) -> Option<&'a V> {
+        Some(&self.node(self.find_idx(find_key)?).value)
+    }
+
+    fn find_idx(&self, find_key: &K) -> Option<I> {
+        self.find_idx_below(self.root, find_key)
+    }
+
+    /// Searches the subtree rooted at `current`.
+    fn find_idx_below(&self, mut current: I, find_key: &K) -> Option<I> {
+        while current != I::NIL {
+            let node = self.node(current);
+            current = match find_key.cmp(&node.key) {
+                Ordering::Less => node.left,
+                Ordering::Greater => node.right,
+                Ordering::Equal => return Some(current),
+            };
+        }
+        None
+    }
+}
+
+impl<K: Ord, W: Ord, V, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Removes the entry for `key` and returns its value. The subtrees of
+    /// the removed node are merged and take its place.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let idx = self.find_idx(key)?;
+        let TreapNode {
+            parent,
+            left,
+            right,
+            ..
+        } = *self.node(idx);
+        let top = self.merge(left, right);
+        self.replace_child(parent, idx, top);
+        self.set_parent(top, parent);
+        Some(self.free(idx, |_, _| {}).value)
+    }
+}
+
+impl<K, W, V> Treap<K, W, V> {
+    pub fn new() -> Self {
+        Self::with_index()
+    }
+
+    /// Creates an empty treap with room for `cap` entries.
+    pub fn with_capacity(cap: usize) -> Self {
+        Self::with_index_and_capacity(cap)
+    }
+}
+
+impl<K, W, V, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Creates an empty treap with the index type `I`, for example
+    /// `Treap::<K, W, V, u16>::with_index()`.
+    pub fn with_index() -> Self {
+        Self {
+            nodes: Vec::new(),
+            root: I::NIL,
+        }
+    }
+
+    /// Creates an empty treap with the index type `I` and room for `cap`
+    /// entries, but never more than `I::MAX_NODES`.
+    pub fn with_index_and_capacity(cap: usize) -> Self {
+        Self {
+            nodes: Vec::with_capacity(cap.min(I::MAX_NODES)),
+            root: I::NIL,
+        }
+    }
+
+    fn node(&self, idx: I) -> &TreapNode<K, W, V, I> {
+        &self.nodes[idx.to_usize()]
+    }
+
+    fn node_mut(&mut self, idx: I) -> &mut TreapNode<K, W, V, I> {
+        &mut self.nodes[idx.to_usize()]
+    }
+
+    fn push(&mut self, node: TreapNode<K, W, V, I>) -> Result<I, CapacityError> {
+        let idx = self.nodes.len();
+        if idx >= I::MAX_NODES {
+            return Err(CapacityError::for_index::<I>());
+        }
+        self.nodes.push(node);
+        Ok(I::from_usize(idx))
+    }
+
+    fn set_parent(&mut self, idx: I, parent: I) {
+        if idx != I::NIL {
+            self.node_mut(idx).parent = parent;
+        }
+    }
+
+    /// Links `child` below `parent`, or makes it the root if `parent` is
+    /// `I::NIL`.
+    fn set_child(&mut self, parent: I, on_left: bool, child: I) {
+        if parent == I::NIL {
+            self.root = child;
+        } else if on_left {
+            self.node_mut(parent).left = child;
         } else {
-            let sr = {
-                let weight = self.0[parent_idx].weight().unwrap();
-                let (left, right) = self.0[parent_idx].children_idx().unwrap();
-                let left_weight = self.0[left].weight();
-                let right_weight = self.0[right].weight();
-                if left_weight.map_or(false, |w| w < weight) {
-                    ShouldRestore::Left
-                } else if right_weight.map_or(false, |w| w < weight) {
-                    ShouldRestore::Right
-                } else {
-                    ShouldRestore::Nothing
-                }
+            self.node_mut(parent).right = child;
+        }
+    }
+
+    /// Replaces the link from `parent` to `old` by a link to `new`.
+    fn replace_child(&mut self, parent: I, old: I, new: I) {
+        let on_left = parent != I::NIL && self.node(parent).left == old;
+        self.set_child(parent, on_left, new);
+    }
+
+    fn swap_entries(&mut self, a: I, b: I) {
+        let (low, high) = (a.min(b).to_usize(), a.max(b).to_usize());
+        let (head, tail) = self.nodes.split_at_mut(high);
+        let (x, y) = (&mut head[low], &mut tail[0]);
+        std::mem::swap(&mut x.key, &mut y.key);
+        std::mem::swap(&mut x.weight, &mut y.weight);
+        std::mem::swap(&mut x.value, &mut y.value);
+    }
+
+    /// Removes the slot `idx`, which must not be linked from any other node,
+    /// by moving the last node of the arena into it. `moved` is called with
+    /// the old and new index of that node.
+    fn free<F>(&mut self, idx: I, mut moved: F) -> TreapNode<K, W, V, I>
+    where
+        F: FnMut(I, I),
+    {
+        let node = self.nodes.swap_remove(idx.to_usize());
+        let from = I::from_usize(self.nodes.len());
+        if from != idx {
+            let TreapNode {
+                parent,
+                left,
+                right,
+                ..
+            } = *self.node(idx);
+            if parent != I::NIL || self.root == from {
+                self.replace_child(parent, from, idx);
+            }
+            self.set_parent(left, idx);
+            self.set_parent(right, idx);
+            moved(from, idx);
+        }
+        node
+    }
+}
+
+impl<K, W, V, I: ArenaIndex> Default for Treap<K, W, V, I> {
+    fn default() -> Self {
+        Self::with_index()
+    }
+}
+
+impl<K, W, V, I: ArenaIndex> Treap<K, W, V, I> {
+    pub fn height(&self) -> usize {
+        self.depths().height()
+    }
+
+    pub fn average_depth(&self) -> f64 {
+        self.depths().average_depth()
+    }
+
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        self.depths().into_histogram()
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.depths().leaves()
+    }
+
+    fn depths(&self) -> Depths {
+        let mut depths = Depths::default();
+        let mut stack = vec![(self.root, 0)];
+        while let Some((idx, depth)) = stack.pop() {
+            if idx == I::NIL {
+                continue;
+            }
+            let TreapNode { left, right, .. } = *self.node(idx);
+            depths.add(depth, left == I::NIL && right == I::NIL);
+            stack.push((left, depth + 1));
+            stack.push((right, depth + 1));
+        }
+        depths
+    }
+}
+
+impl<K: Ord, W: Ord, V, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Checks the search tree order of the keys, the heap order of the
+    /// weights and the parent link of every node. Every slot of the arena
+    /// must be reachable from the root exactly once.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut trail = Trail::default();
+        let mut stack = vec![(self.root, 0, None, I::NIL, None, None)];
+        while let Some((idx, depth, side, parent, lower, upper)) = stack.pop() {
+            trail.enter(depth, side);
+            if idx == I::NIL {
+                continue;
+            }
+            let Some(node) = self.nodes.get(idx.to_usize()) else {
+                return Err(trail.error(Violation::IndexOutOfBounds(idx.to_usize())));
             };
+            if std::mem::replace(&mut visited[idx.to_usize()], true) {
+                return Err(trail.error(Violation::SharedNode));
+            }
+            if node.parent != parent {
+                return Err(trail.error(Violation::ParentLink));
+            }
+            if !validate::in_bounds(&node.key, lower, upper) {
+                return Err(trail.error(Violation::KeyOrder));
+            }
+            if parent != I::NIL && node.weight < self.node(parent).weight {
+                return Err(trail.error(Violation::HeapOrder));
+            }
+            let child_depth = depth + 1;
+            stack.push((
+                node.right,
+                child_depth,
+                Some(Side::Right),
+                idx,
+                Some(&node.key),
+                upper,
+            ));
+            stack.push((
+                node.left,
+                child_depth,
+                Some(Side::Left),
+                idx,
+                lower,
+                Some(&node.key),
+            ));
+        }
+        match visited.iter().position(|v| !v) {
+            Some(idx) => Err(ValidationError {
+                violation: Violation::Unreachable(idx),
+                path: Vec::new(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<K, W: Ord, V, I: ArenaIndex> Treap<K, W, V, I> {
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.extract_if(|k, v| !f(k, v)).for_each(drop);
+    }
 
-            match sr {
-                ShouldRestore::Nothing => {},
-                ShouldRestore::Left => {
-                    let TreapNode::Node {
-                        parent: zparent,
-                        key: zkey,
-                        weight: zweight,
-                        value: zvalue,
-                        left: zleft,
-                        right: zright,
-                    } = self.0[parent_idx].take() else { unreachable!() };
-                    let TreapNode::Node {
-                        parent: _xparent,
-                        key: xkey,
-                        weight: xweight,
-                        value: xvalue,
-                        left: xleft,
-                        right: xright,
-                    } = self.0[zleft].take() else { unreachable!() };
-                    let zidx = zleft;
-                    self.0[parent_idx] = TreapNode::Node {
-                        parent: zparent,
-                        key: xkey,
-                        weight: xweight,
-                        value: xvalue,
-                        left: xleft,
-                        right: zidx,
-                    };
-                    self.0[zidx] = TreapNode::Node {
-                        parent: Some(parent_idx),
-                        key: zkey,
-                        weight: zweight,
-                        value: zvalue,
-                        left: xright,
-                        right: zright,
-                    };
-                    *self.0[xleft].parent_mut() = Some(parent_idx);
-                    *self.0[xright].parent_mut() = Some(zidx);
-                    *self.0[zright].parent_mut() = Some(zidx);
-                },
-                ShouldRestore::Right => {
-                    let TreapNode::Node {
-                        parent: zparent,
-                        key: zkey,
-                        weight: zweight,
-                        value: zvalue,
-                        left: zleft,
-                        right: zright,
-                    } = self.0[parent_idx].take() else { unreachable!() };
-                    let TreapNode::Node {
-                        parent: _xparent,
-                        key: xkey,
-                        weight: xweight,
-                        value: xvalue,
-                        left: xleft,
-                        right: xright,
-                    } = self.0[zright].take() else { unreachable!() };
-                    let zidx = zright;
-                    self.0[zidx] = TreapNode::Node {
-                        key: zkey,
-                        parent: Some(parent_idx),
-                        weight: zweight,
-                        value: zvalue,
-                        left: zleft,
-                        right: xleft,
-                    };
-                    self.0[parent_idx] = TreapNode::Node {
-                        parent: zparent,
-                        key: xkey,
-                        weight: xweight,
-                        value: xvalue,
-                        left: zidx,
-                        right: xright,
-                    };
-                    *self.0[zleft].parent_mut() = Some(zidx);
-                    *self.0[xleft].parent_mut() = Some(zidx);
-                    *self.0[xright].parent_mut() = Some(parent_idx);
+    /// Returns an iterator that removes and yields every entry for which
+    /// `pred` returns `true`, in ascending key order.
+    ///
+    /// A removed node is replaced by its right subtree, which is still to
+    /// be visited. Its left subtree, which has already been visited, is
+    /// merged back in once the right subtree is done. If the iterator is
+    /// dropped early, all entries not yet visited are kept.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, W, V, I, F>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let root = self.root;
+        ExtractIf {
+            treap: self,
+            stack: vec![Visit::Enter(root)],
+            pred,
+        }
+    }
+
+    /// Merges the subtrees rooted at `a` and `b`, where all keys in `a` are
+    /// smaller than those in `b`, and returns the index of the new root.
+    /// The parent link of the new root is left to the caller.
+    fn merge(&mut self, mut a: I, mut b: I) -> I {
+        let mut root = I::NIL;
+        // Node whose child link the next merged root is written to, and
+        // whether that is its left link.
+        let mut hook = None;
+        loop {
+            let top = if a == I::NIL {
+                b
+            } else if b == I::NIL || self.node(a).weight <= self.node(b).weight {
+                a
+            } else {
+                b
+            };
+            match hook {
+                Some((parent, on_left)) => {
+                    self.set_child(parent, on_left, top);
+                    self.set_parent(top, parent);
                 }
+                None => root = top,
+            }
+            if a == I::NIL || b == I::NIL {
+                return root;
+            }
+            if top == b {
+                hook = Some((b, true));
+                b = self.node(b).left;
+            } else {
+                hook = Some((a, false));
+                a = self.node(a).right;
             }
-            sr
         }
     }
 }
 
-impl<K: Ord, W, V> Treap<K, W, V> {
-    pub fn find<'a, 'b>(&'a self, find_key: &'b K) -> Option<&'a V> {
-        let mut current = 0;
-        loop {
-            let next;
-            match &self.0[current] {
-                TreapNode::Empty(_) => return None,
-                TreapNode::Node {
-                    key,
-                    left,
-                    right,
-                    value,
-                    ..
-                } => {
-                    next = if *find_key < *key {
-                        *left
-                    } else if *key < *find_key {
-                        *right
-                    } else {
-                        return Some(value);
+/// Iterator returned by [`Treap::extract_if`].
+pub struct ExtractIf<'a, K, W: Ord, V, I: ArenaIndex, F> {
+    treap: &'a mut Treap<K, W, V, I>,
+    stack: Vec<Visit<I>>,
+    pred: F,
+}
+
+enum Visit<I> {
+    Enter(I),
+    Check(I),
+    /// Merges the detached subtree `left` into the subtree hanging from
+    /// `parent` (or the root if that is `I::NIL`) on the given side.
+    Merge {
+        parent: I,
+        on_left: bool,
+        left: I,
+    },
+}
+
+impl<'a, K, W: Ord, V, I: ArenaIndex, F> ExtractIf<'a, K, W, V, I, F> {
+    fn merge_back(&mut self, parent: I, on_left: bool, left: I) {
+        let treap = &mut *self.treap;
+        let right = match parent {
+            _ if parent == I::NIL => treap.root,
+            _ if on_left => treap.node(parent).left,
+            _ => treap.node(parent).right,
+        };
+        let top = treap.merge(left, right);
+        treap.set_child(parent, on_left, top);
+        treap.set_parent(top, parent);
+    }
+
+    /// Unlinks the node at `idx` as described in [`Treap::extract_if`] and
+    /// returns it.
+    fn extract(&mut self, idx: I) -> TreapNode<K, W, V, I> {
+        let treap = &mut *self.treap;
+        let TreapNode {
+            parent,
+            left,
+            right,
+            ..
+        } = *treap.node(idx);
+        let on_left = parent != I::NIL && treap.node(parent).left == idx;
+        treap.set_child(parent, on_left, right);
+        treap.set_parent(right, parent);
+        treap.set_parent(left, I::NIL);
+        self.stack.push(Visit::Merge {
+            parent,
+            on_left,
+            left,
+        });
+        self.stack.push(Visit::Enter(right));
+        // The last node of the arena moves into the freed slot, so pending
+        // visits have to follow it.
+        let stack = &mut self.stack;
+        treap.free(idx, |from, to| {
+            let rename = |idx: &mut I| {
+                if *idx == from {
+                    *idx = to;
+                }
+            };
+            for visit in stack.iter_mut() {
+                match visit {
+                    Visit::Enter(idx) | Visit::Check(idx) => rename(idx),
+                    Visit::Merge { parent, left, .. } => {
+                        rename(parent);
+                        rename(left);
                     }
                 }
             }
-            current = next;
+        })
+    }
+}
+
+impl<'a, K, W: Ord, V, I: ArenaIndex, F> Iterator for ExtractIf<'a, K, W, V, I, F>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(visit) = self.stack.pop() {
+            match visit {
+                Visit::Enter(idx) if idx == I::NIL => {}
+                Visit::Enter(idx) => {
+                    self.stack.push(Visit::Check(idx));
+                    self.stack.push(Visit::Enter(self.treap.node(idx).left));
+                }
+                Visit::Check(idx) => {
+                    let node = self.treap.node_mut(idx);
+                    if !(self.pred)(&node.key, &mut node.value) {
+                        self.stack.push(Visit::Enter(node.right));
+                        continue;
+                    }
+                    let TreapNode { key, value, .. } = self.extract(idx);
+                    return Some((key, value));
+                }
+                Visit::Merge {
+                    parent,
+                    on_left,
+                    left,
+                } => self.merge_back(parent, on_left, left),
+            }
         }
+        None
     }
 }
 
-impl<K, W, V> Treap<K, W, V> {
-    pub fn new() -> Self {
-        Self(vec![TreapNode::Empty(None)])
+impl<'a, K, W: Ord, V, I: ArenaIndex, F> Drop for ExtractIf<'a, K, W, V, I, F> {
+    fn drop(&mut self) {
+        while let Some(visit) = self.stack.pop() {
+            if let Visit::Merge {
+                parent,
+                on_left,
+                left,
+            } = visit
+            {
+                self.merge_back(parent, on_left, left);
+            }
+        }
     }
+}
 
-    pub fn with_capacity(cap: usize) -> Self {
-        let mut v = Vec::with_capacity(cap);
-        v.push(TreapNode::Empty(None));
-        Self(v)
+impl<K, W, V, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Returns an iterator over all entries in ascending key order.
+    pub fn iter(&self) -> Iter<'_, K, W, V, I> {
+        let mut iter = Iter {
+            nodes: &self.nodes,
+            stack: Vec::new(),
+        };
+        iter.push_left(self.root);
+        iter
+    }
+}
+
+impl<K, W, V, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Returns the keys and weights in pre-order.
+    ///
+    /// Comparable with [`crate::Treap::shape`], independent of how the nodes
+    /// are laid out in the arena.
+    pub fn shape(&self) -> Vec<(K, W)>
+    where
+        K: Clone,
+        W: Clone,
+    {
+        self.preorder()
+            .map(|(key, weight, _)| (key.clone(), weight.clone()))
+            .collect()
+    }
+
+    fn preorder(&self) -> impl Iterator<Item = (&K, &W, &V)> {
+        let mut stack = vec![self.root];
+        std::iter::from_fn(move || loop {
+            let idx = stack.pop()?;
+            if idx != I::NIL {
+                let node = self.node(idx);
+                stack.push(node.right);
+                stack.push(node.left);
+                return Some((&node.key, &node.weight, &node.value));
+            }
+        })
+    }
+}
+
+/// Iterator returned by [`Treap::iter`].
+pub struct Iter<'a, K, W, V, I = usize> {
+    nodes: &'a [TreapNode<K, W, V, I>],
+    stack: Vec<I>,
+}
+
+impl<'a, K, W, V, I: ArenaIndex> Iter<'a, K, W, V, I> {
+    fn push_left(&mut self, mut idx: I) {
+        while idx != I::NIL {
+            self.stack.push(idx);
+            idx = self.nodes[idx.to_usize()].left;
+        }
+    }
+}
+
+impl<'a, K, W, V, I: ArenaIndex> Iterator for Iter<'a, K, W, V, I> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = &self.nodes[self.stack.pop()?.to_usize()];
+        self.push_left(node.right);
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<'a, K, W, V, I: ArenaIndex> IntoIterator for &'a Treap<K, W, V, I> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, W, V, I>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Debug, W, V: Debug, I: ArenaIndex> Debug for Treap<K, W, V, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Display, W: Display, V, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Renders the treap as an indented text tree with one `key:weight` line per
+    /// node. Subtrees below `max_depth` (the root has depth `0`) and all
+    /// nodes after the first `max_nodes` in pre-order are shown as `...`.
+    pub fn to_ascii(&self, max_depth: usize, max_nodes: usize) -> String {
+        ascii::render(self.root, max_depth, max_nodes, |&idx| {
+            let node = self.nodes.get(idx.to_usize())?;
+            Some((format!("{}:{}", node.key, node.weight), node.left, node.right))
+        })
+    }
+}
+
+impl<K: Display, W: Display, V: Display, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Writes the treap in Graphviz DOT format. Empty children are drawn as
+    /// small grey dots.
+    pub fn to_dot<T: Write>(&self, writer: T) -> io::Result<()> {
+        self.to_dot_with_max_depth(writer, usize::MAX)
+    }
+
+    /// Like [`Treap::to_dot`], but replaces every subtree below `max_depth`
+    /// by a `...` node. The root has depth `0`.
+    pub fn to_dot_with_max_depth<T: Write>(&self, writer: T, max_depth: usize) -> io::Result<()> {
+        let mut dot = Dot::begin(writer)?;
+        let mut stack = vec![(self.root, 0, None)];
+        while let Some((idx, depth, parent)) = stack.pop() {
+            if idx == I::NIL {
+                dot.empty(parent)?;
+            } else if depth > max_depth {
+                dot.cut(parent)?;
+            } else {
+                let node = self.node(idx);
+                let lines = [
+                    format!("{}: {}", node.key, node.value),
+                    format!("w = {}", node.weight),
+                ];
+                let id = dot.node(parent, &lines)?;
+                stack.push((node.right, depth + 1, Some(id)));
+                stack.push((node.left, depth + 1, Some(id)));
+            }
+        }
+        dot.end()
+    }
+}
+
+impl<K: PartialEq, W, V: PartialEq, I: ArenaIndex> PartialEq for Treap<K, W, V, I> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Eq, W, V: Eq, I: ArenaIndex> Eq for Treap<K, W, V, I> {}
+
+impl<K: PartialOrd, W, V: PartialOrd, I: ArenaIndex> PartialOrd for Treap<K, W, V, I> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<K: Ord, W, V: Ord, I: ArenaIndex> Ord for Treap<K, W, V, I> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<K: Hash, W, V: Hash, I: ArenaIndex> Hash for Treap<K, W, V, I> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut len = 0;
+        for entry in self {
+            entry.hash(state);
+            len += 1;
+        }
+        state.write_usize(len);
+    }
+}
+
+impl<K: Ord, W, V, I: ArenaIndex> Index<&K> for Treap<K, W, V, I> {
+    type Output = V;
+
+    fn index(&self, key: &K) -> &V {
+        self.find(key).expect("no entry found for key")
     }
 }
 
@@ -215,47 +925,76 @@ enum ShouldRestore {
     Nothing,
 }
 
-impl<K, W, V> TreapNode<K, W, V> {
-    pub fn is_empty(&self) -> bool {
-        match self {
-            TreapNode::Empty(_) => true,
-            _ => false,
-        }
-    }
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::{ArenaIndex, Treap};
+    use crate::Weighted;
+    use rand::distributions::{Distribution, Standard};
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
 
-    pub fn parent_idx(&self) -> Option<usize> {
-        match self {
-            Self::Empty(p) => *p,
-            Self::Node { parent, .. } => *parent,
+    impl<K: Serialize, W, V: Serialize, I: ArenaIndex> Serialize for Treap<K, W, V, I> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(self.iter())
         }
     }
 
-    pub fn parent_mut(&mut self) -> &mut Option<usize> {
-        match self {
-            Self::Empty(p) => p,
-            Self::Node { parent, .. } => parent,
+    impl<'de, K, W, V, I> Deserialize<'de> for Treap<K, W, V, I>
+    where
+        K: Deserialize<'de> + Ord,
+        W: Ord,
+        V: Deserialize<'de>,
+        I: ArenaIndex,
+        Standard: Distribution<W>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let entries = BTreeMap::<K, V>::deserialize(deserializer)?;
+            let mut treap = Treap::with_index_and_capacity(entries.len());
+            for (key, value) in entries {
+                treap.try_insert(key, rand::random(), value).map_err(de::Error::custom)?;
+            }
+            Ok(treap)
         }
     }
 
-    pub fn children_idx(&self) -> Option<(usize, usize)> {
-        match self {
-            Self::Empty(_) => None,
-            Self::Node {
-                left,
-                right,
-                ..
-            } => Some((*left, *right))
+    impl<K, W, V, I> Serialize for Weighted<&Treap<K, W, V, I>>
+    where
+        K: Serialize,
+        W: Serialize,
+        V: Serialize,
+        I: ArenaIndex,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.0.preorder())
         }
     }
 
-    pub fn weight(&self) -> Option<&W> {
-        match self {
-            TreapNode::Empty(_) => None,
-            TreapNode::Node { weight, .. } => Some(weight),
+    impl<K, W, V, I> Serialize for Weighted<Treap<K, W, V, I>>
+    where
+        K: Serialize,
+        W: Serialize,
+        V: Serialize,
+        I: ArenaIndex,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Weighted(&self.0).serialize(serializer)
         }
     }
 
-    pub fn take(&mut self) -> Self {
-        std::mem::replace(self, Self::Empty(None))
+    impl<'de, K, W, V, I> Deserialize<'de> for Weighted<Treap<K, W, V, I>>
+    where
+        K: Deserialize<'de> + Ord,
+        W: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+        I: ArenaIndex,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let entries = Vec::<(K, W, V)>::deserialize(deserializer)?;
+            let mut treap = Treap::with_index_and_capacity(entries.len());
+            for (key, weight, value) in entries {
+                treap.try_insert(key, weight, value).map_err(de::Error::custom)?;
+            }
+            Ok(Weighted(treap))
+        }
     }
 }
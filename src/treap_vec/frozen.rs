@@ -0,0 +1,185 @@
+//! Read-only treaps in Eytzinger layout.
+//!
+//! [`Treap::freeze`] sorts the entries into a complete binary search tree
+//! that is stored level by level: the children of slot `i` are the slots
+//! `2i + 1` and `2i + 2`. The first levels of every search share a few cache
+//! lines, no links have to be loaded, and the descent needs no branch on the
+//! comparison. The weights are kept, so [`Frozen::thaw`] can build the treap
+//! again.
+
+use super::{ArenaIndex, Treap, TreapNode};
+use std::marker::PhantomData;
+
+/// An immutable treap created by [`Treap::freeze`]. Keys, weights and values
+/// are stored in separate vectors, so a search only reads keys.
+#[derive(Debug, Clone)]
+pub struct Frozen<K, W, V, I = usize> {
+    keys: Vec<K>,
+    weights: Vec<W>,
+    values: Vec<V>,
+    /// Index type of the treap that [`Frozen::thaw`] returns.
+    index: PhantomData<I>,
+}
+
+impl<K, W, V, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Moves all entries into a [`Frozen`] treap that can only be searched.
+    pub fn freeze(self) -> Frozen<K, W, V, I> {
+        let len = self.nodes.len();
+        let mut inorder = Vec::with_capacity(len);
+        let mut stack = Vec::new();
+        let mut idx = self.root;
+        loop {
+            while idx != I::NIL {
+                stack.push(idx);
+                idx = self.node(idx).left;
+            }
+            let Some(next) = stack.pop() else { break };
+            inorder.push(next);
+            idx = self.node(next).right;
+        }
+
+        let mut ranks = vec![0; len];
+        for (rank, slot) in inorder_slots(len).enumerate() {
+            ranks[slot] = rank;
+        }
+        let mut nodes: Vec<Option<TreapNode<K, W, V, I>>> =
+            self.nodes.into_iter().map(Some).collect();
+        let mut frozen = Frozen {
+            keys: Vec::with_capacity(len),
+            weights: Vec::with_capacity(len),
+            values: Vec::with_capacity(len),
+            index: PhantomData,
+        };
+        for rank in ranks {
+            let node = nodes[inorder[rank].to_usize()].take().unwrap();
+            frozen.keys.push(node.key);
+            frozen.weights.push(node.weight);
+            frozen.values.push(node.value);
+        }
+        frozen
+    }
+}
+
+impl<K, W: Ord, V, I: ArenaIndex> Frozen<K, W, V, I> {
+    /// Builds a treap from the entries again, in linear time. With pairwise
+    /// distinct weights this is exactly the treap that was frozen.
+    pub fn thaw(self) -> Treap<K, W, V, I> {
+        let len = self.keys.len();
+        let mut keys: Vec<Option<K>> = self.keys.into_iter().map(Some).collect();
+        let mut weights: Vec<Option<W>> = self.weights.into_iter().map(Some).collect();
+        let mut values: Vec<Option<V>> = self.values.into_iter().map(Some).collect();
+
+        // The entries arrive in key order, so every new node becomes the
+        // rightmost one. `spine` holds the current right spine, root first.
+        let mut treap = Treap::with_index_and_capacity(len);
+        let mut spine: Vec<I> = Vec::new();
+        for slot in inorder_slots(len) {
+            let weight = weights[slot].take().unwrap();
+            let mut left = I::NIL;
+            while let Some(&top) = spine.last() {
+                if treap.node(top).weight <= weight {
+                    break;
+                }
+                left = top;
+                spine.pop();
+            }
+            let parent = spine.last().copied().unwrap_or(I::NIL);
+            let idx = treap
+                .push(TreapNode {
+                    parent,
+                    left,
+                    right: I::NIL,
+                    key: keys[slot].take().unwrap(),
+                    weight,
+                    value: values[slot].take().unwrap(),
+                })
+                .expect("a frozen treap fits into its index type");
+            treap.set_parent(left, idx);
+            if parent != I::NIL {
+                treap.node_mut(parent).right = idx;
+            }
+            spine.push(idx);
+        }
+        treap.root = spine.first().copied().unwrap_or(I::NIL);
+        treap
+    }
+}
+
+impl<K: Ord, W, V, I> Frozen<K, W, V, I> {
+    pub fn find(&self, find_key: &K) -> Option<&V> {
+        let slot = self.lower_bound(find_key, |_| {})?;
+        (self.keys[slot] == *find_key).then(|| &self.values[slot])
+    }
+
+    /// Like [`Frozen::find`], but asks the CPU to load the keys a few levels
+    /// further down while the current ones are compared. This only pays off
+    /// once the keys no longer fit into the cache.
+    pub fn find_with_prefetch(&self, find_key: &K) -> Option<&V> {
+        // Slots `(i + 1) * stride - 1 ..` are the descendants of slot `i`
+        // `log2(stride)` levels down and lie next to each other.
+        let stride = (64 / std::mem::size_of::<K>().max(1)).next_power_of_two();
+        let keys = self.keys.as_ptr();
+        let slot = self.lower_bound(find_key, |slot| {
+            prefetch(keys.wrapping_add((slot + 1) * stride - 1));
+        })?;
+        (self.keys[slot] == *find_key).then(|| &self.values[slot])
+    }
+
+    /// Returns the slot of the smallest key that is not less than
+    /// `find_key`. `visit` is called with every slot on the way down.
+    fn lower_bound<F: FnMut(usize)>(&self, find_key: &K, mut visit: F) -> Option<usize> {
+        let mut slot = 0;
+        while slot < self.keys.len() {
+            visit(slot);
+            slot = 2 * slot + 1 + usize::from(self.keys[slot] < *find_key);
+        }
+        // Every step to the right appended a one bit to `slot + 1`. The
+        // last step to the left happened at the lower bound, so dropping
+        // the trailing ones and that zero leads back to it.
+        let path = slot + 1;
+        let path = path >> (path.trailing_ones() + 1);
+        path.checked_sub(1)
+    }
+}
+
+impl<K, W, V, I> Frozen<K, W, V, I> {
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns an iterator over all entries in ascending key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        inorder_slots(self.keys.len()).map(|slot| (&self.keys[slot], &self.values[slot]))
+    }
+}
+
+/// Slots of a complete tree with `len` slots in Eytzinger order, visited in
+/// order, i.e. the slot of the smallest key first.
+fn inorder_slots(len: usize) -> impl Iterator<Item = usize> {
+    let mut stack = Vec::new();
+    let mut slot = 0;
+    std::iter::from_fn(move || {
+        while slot < len {
+            stack.push(slot);
+            slot = 2 * slot + 1;
+        }
+        let next = stack.pop()?;
+        slot = 2 * next + 2;
+        Some(next)
+    })
+}
+
+#[cfg(all(target_arch = "x86_64", not(miri)))]
+fn prefetch<T>(ptr: *const T) {
+    use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+    // SAFETY: prefetching never faults, not even for addresses outside of
+    // the allocation, and SSE is part of every x86_64 target.
+    unsafe { _mm_prefetch::<_MM_HINT_T0>(ptr.cast()) }
+}
+
+#[cfg(not(all(target_arch = "x86_64", not(miri))))]
+fn prefetch<T>(_ptr: *const T) {}
@@ -0,0 +1,453 @@
+//! Binary snapshots of a [`Treap`] arena.
+//!
+//! A snapshot stores the arena node by node, so loading it restores the exact
+//! layout without inserting anything. The layout is:
+//!
+//! | field          | size          |                                         |
+//! | -------------- | ------------- | --------------------------------------- |
+//! | magic          | 4             | `b"TRPV"`                               |
+//! | version        | 2             | currently `2`                           |
+//! | flags          | 2             | bit 0: all nodes have the same size     |
+//! | key size       | 4             | `Codec::FIXED_SIZE` of the keys or `0`  |
+//! | weight size    | 4             | same for the weights                    |
+//! | value size     | 4             | same for the values                     |
+//! | node count     | 8             |                                         |
+//! | root           | 8             | index of the root node                  |
+//! | nodes          | ...           | see below                               |
+//! | checksum       | 8             | FNV-1a over everything before it        |
+//!
+//! Every node consists of the parent, left and right index followed by the
+//! encoded key, weight and value. Indices are `u64`, `u64::MAX` stands for
+//! a missing root, parent or child, and all integers are little endian. If
+//! keys, weights and values all have a fixed size, so do the nodes, which
+//! lets [`SnapshotView`] address them directly.
+
+use super::{ArenaIndex, CapacityError, Treap, TreapNode};
+use std::cmp::Ordering;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+const MAGIC: &[u8; 4] = b"TRPV";
+const VERSION: u16 = 2;
+const FLAG_FIXED: u16 = 1;
+const HEADER_LEN: usize = 4 + 2 + 2 + 4 + 4 + 4 + 8 + 8;
+const CHECKSUM_LEN: usize = 8;
+const NONE: u64 = u64::MAX;
+
+/// Conversion of keys, weights and values to and from snapshot bytes.
+pub trait Codec: Sized {
+    /// Number of bytes every encoded value takes, if it is the same for all
+    /// values of the type.
+    const FIXED_SIZE: Option<usize> = None;
+
+    /// Appends the encoded value to `out`.
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Decodes a value from the front of `input` and advances it. Returns
+    /// `None` if the bytes are not a valid encoding.
+    fn decode(input: &mut &[u8]) -> Option<Self>;
+}
+
+macro_rules! int_codec {
+    ($($t:ty),*) => {$(
+        impl Codec for $t {
+            const FIXED_SIZE: Option<usize> = Some(std::mem::size_of::<$t>());
+
+            fn encode(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn decode(input: &mut &[u8]) -> Option<Self> {
+                let bytes = take(input, std::mem::size_of::<$t>())?;
+                Some(<$t>::from_le_bytes(bytes.try_into().unwrap()))
+            }
+        }
+    )*};
+}
+
+int_codec!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl Codec for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u64).encode(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let len = usize::try_from(u64::decode(input)?).ok()?;
+        let bytes = take(input, len)?;
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if input.len() < len {
+        return None;
+    }
+    let (head, tail) = input.split_at(len);
+    *input = tail;
+    Some(head)
+}
+
+/// Errors that can occur while reading a snapshot.
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    /// The data does not start with the snapshot magic bytes.
+    BadMagic,
+    UnsupportedVersion(u16),
+    ChecksumMismatch,
+    /// The data passed the checksum but does not describe a valid arena.
+    Corrupt(&'static str),
+    /// The snapshot has more nodes than the index type of the treap can
+    /// address.
+    Capacity(CapacityError),
+    /// A [`SnapshotView`] was requested for a snapshot whose nodes do not
+    /// all have the same size, or whose sizes do not match the view's types.
+    NotFixedSize,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "i/o error: {}", err),
+            SnapshotError::BadMagic => write!(f, "not a treap snapshot"),
+            SnapshotError::UnsupportedVersion(v) => {
+                write!(f, "unsupported snapshot version {}", v)
+            }
+            SnapshotError::ChecksumMismatch => write!(f, "snapshot checksum mismatch"),
+            SnapshotError::Corrupt(what) => write!(f, "corrupt snapshot: {}", what),
+            SnapshotError::Capacity(err) => err.fmt(f),
+            SnapshotError::NotFixedSize => {
+                write!(f, "snapshot nodes do not have the requested fixed size")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SnapshotError::Io(err) => Some(err),
+            SnapshotError::Capacity(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> Self {
+        SnapshotError::Io(err)
+    }
+}
+
+/// 64 bit FNV-1a hash, used as the snapshot checksum.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= u64::from(*b);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+struct Header {
+    fixed: bool,
+    sizes: [u32; 3],
+    count: u64,
+    root: u64,
+}
+
+impl Header {
+    fn for_types<K: Codec, W: Codec, V: Codec>(count: usize, root: u64) -> Self {
+        let sizes = [K::FIXED_SIZE, W::FIXED_SIZE, V::FIXED_SIZE];
+        Self {
+            fixed: sizes.iter().all(Option::is_some),
+            sizes: sizes.map(|s| s.map_or(0, |s| s as u32)),
+            count: count as u64,
+            root,
+        }
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(MAGIC);
+        VERSION.encode(out);
+        (if self.fixed { FLAG_FIXED } else { 0 }).encode(out);
+        for size in self.sizes {
+            size.encode(out);
+        }
+        self.count.encode(out);
+        self.root.encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+        if take(input, MAGIC.len()).ok_or(SnapshotError::BadMagic)? != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let version = u16::decode(input).ok_or(SnapshotError::Corrupt("truncated header"))?;
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let flags = u16::decode(input).ok_or(SnapshotError::Corrupt("truncated header"))?;
+        let mut sizes = [0; 3];
+        for size in &mut sizes {
+            *size = u32::decode(input).ok_or(SnapshotError::Corrupt("truncated header"))?;
+        }
+        let count = u64::decode(input).ok_or(SnapshotError::Corrupt("truncated header"))?;
+        let root = u64::decode(input).ok_or(SnapshotError::Corrupt("truncated header"))?;
+        Ok(Self {
+            fixed: flags & FLAG_FIXED != 0,
+            sizes,
+            count,
+            root,
+        })
+    }
+
+    /// Size of a node in the fixed layout.
+    fn node_len(&self) -> usize {
+        3 * 8 + self.sizes.iter().map(|s| *s as usize).sum::<usize>()
+    }
+}
+
+/// Checks the magic bytes and checksum and returns the header together with
+/// the node bytes.
+fn open(bytes: &[u8]) -> Result<(Header, &[u8]), SnapshotError> {
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(SnapshotError::BadMagic);
+    }
+    if bytes.len() < HEADER_LEN + CHECKSUM_LEN {
+        return Err(SnapshotError::Corrupt("truncated header"));
+    }
+    let (data, mut trailer) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+    let mut fnv = Fnv::new();
+    fnv.update(data);
+    if u64::decode(&mut trailer) != Some(fnv.0) {
+        return Err(SnapshotError::ChecksumMismatch);
+    }
+    let mut input = data;
+    let header = Header::decode(&mut input)?;
+    if header.count >= <usize as ArenaIndex>::MAX_NODES as u64 {
+        return Err(SnapshotError::Corrupt("too many nodes"));
+    }
+    check_index::<usize>(header.root, header.count)?;
+    if (header.count == 0) != (header.root == NONE) {
+        return Err(SnapshotError::Corrupt("root does not match node count"));
+    }
+    Ok((header, input))
+}
+
+fn encode_index<I: ArenaIndex>(idx: I) -> u64 {
+    if idx == I::NIL {
+        NONE
+    } else {
+        idx.to_usize() as u64
+    }
+}
+
+/// Accepts indices below `count` and [`NONE`]. `count` must not exceed
+/// `I::MAX_NODES`.
+fn check_index<I: ArenaIndex>(idx: u64, count: u64) -> Result<I, SnapshotError> {
+    match idx {
+        NONE => Ok(I::NIL),
+        idx if idx < count => Ok(I::from_usize(idx as usize)),
+        _ => Err(SnapshotError::Corrupt("index out of bounds")),
+    }
+}
+
+/// Checks that the parent, left and right links returned by `links` form a
+/// single tree below `root`: every node is reached exactly once, and its
+/// parent link points to the node it was reached from. All indices must
+/// already have passed [`check_index`].
+fn check_tree(
+    root: u64,
+    count: usize,
+    links: impl Fn(usize) -> [u64; 3],
+) -> Result<(), SnapshotError> {
+    let mut seen = vec![false; count];
+    let mut reached = 0;
+    let mut stack = vec![(root, NONE)];
+    while let Some((idx, parent)) = stack.pop() {
+        if idx == NONE {
+            continue;
+        }
+        let idx_usize = idx as usize;
+        if std::mem::replace(&mut seen[idx_usize], true) {
+            return Err(SnapshotError::Corrupt("node linked more than once"));
+        }
+        reached += 1;
+        let [node_parent, left, right] = links(idx_usize);
+        if node_parent != parent {
+            return Err(SnapshotError::Corrupt("parent link does not match"));
+        }
+        stack.push((right, idx));
+        stack.push((left, idx));
+    }
+    if reached != count {
+        return Err(SnapshotError::Corrupt("node not reachable from the root"));
+    }
+    Ok(())
+}
+
+fn decode_index<I: ArenaIndex>(input: &mut &[u8], count: u64) -> Result<I, SnapshotError> {
+    let idx = u64::decode(input).ok_or(SnapshotError::Corrupt("truncated node"))?;
+    check_index(idx, count)
+}
+
+impl<K: Codec, W: Codec, V: Codec, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Writes the arena to `writer` in the snapshot format.
+    pub fn save_to<T: Write>(&self, mut writer: T) -> io::Result<()> {
+        let header = Header::for_types::<K, W, V>(self.nodes.len(), encode_index(self.root));
+        let mut fnv = Fnv::new();
+        let mut buf = Vec::new();
+        header.encode(&mut buf);
+        fnv.update(&buf);
+        writer.write_all(&buf)?;
+        for node in &self.nodes {
+            buf.clear();
+            for idx in [node.parent, node.left, node.right] {
+                encode_index(idx).encode(&mut buf);
+            }
+            node.key.encode(&mut buf);
+            node.weight.encode(&mut buf);
+            node.value.encode(&mut buf);
+            fnv.update(&buf);
+            writer.write_all(&buf)?;
+        }
+        writer.write_all(&fnv.0.to_le_bytes())?;
+        writer.flush()
+    }
+}
+
+impl<K: Codec + Ord, W: Codec + Ord, V: Codec, I: ArenaIndex> Treap<K, W, V, I> {
+    /// Reads an arena written by [`Treap::save_to`]. The nodes are restored
+    /// as they were saved, nothing is inserted again. Links that do not form
+    /// a single tree, such as cycles or shared children, and keys or weights
+    /// out of order are rejected as [`SnapshotError::Corrupt`].
+    pub fn load_from<R: Read>(mut reader: R) -> Result<Self, SnapshotError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let (header, mut input) = open(&bytes)?;
+        let count = header.count;
+        if count > I::MAX_NODES as u64 {
+            return Err(SnapshotError::Capacity(CapacityError::for_index::<I>()));
+        }
+        // Every node takes at least 24 bytes, which bounds the allocation
+        // for a bogus count.
+        let mut nodes = Vec::with_capacity((count as usize).min(input.len() / 24));
+        for _ in 0..count {
+            nodes.push(TreapNode {
+                parent: decode_index(&mut input, count)?,
+                left: decode_index(&mut input, count)?,
+                right: decode_index(&mut input, count)?,
+                key: K::decode(&mut input).ok_or(SnapshotError::Corrupt("bad key"))?,
+                weight: W::decode(&mut input).ok_or(SnapshotError::Corrupt("bad weight"))?,
+                value: V::decode(&mut input).ok_or(SnapshotError::Corrupt("bad value"))?,
+            });
+        }
+        if !input.is_empty() {
+            return Err(SnapshotError::Corrupt("trailing bytes after nodes"));
+        }
+        check_tree(header.root, nodes.len(), |idx| {
+            let node = &nodes[idx];
+            [node.parent, node.left, node.right].map(encode_index)
+        })?;
+        let treap = Self {
+            nodes,
+            root: check_index(header.root, count)?,
+        };
+        treap
+            .validate()
+            .map_err(|_| SnapshotError::Corrupt("keys or weights out of order"))?;
+        Ok(treap)
+    }
+}
+
+/// Read-only view of a snapshot with fixed-size keys, weights and values.
+///
+/// The view works directly on the snapshot bytes, for example a file read
+/// into memory once. Lookups decode only the nodes on the search path.
+pub struct SnapshotView<'a, K, W, V> {
+    nodes: &'a [u8],
+    count: usize,
+    root: u64,
+    node_len: usize,
+    weight_len: usize,
+    _phantom: PhantomData<(K, W, V)>,
+}
+
+impl<'a, K: Codec, W: Codec, V: Codec> SnapshotView<'a, K, W, V> {
+    /// Checks the snapshot in `bytes`, including that its links form a single
+    /// tree, and creates a view on it.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, SnapshotError> {
+        let (header, nodes) = open(bytes)?;
+        let expected = Header::for_types::<K, W, V>(0, NONE);
+        if !header.fixed || !expected.fixed || header.sizes != expected.sizes {
+            return Err(SnapshotError::NotFixedSize);
+        }
+        let node_len = header.node_len();
+        let count = header.count as usize;
+        if Some(nodes.len()) != count.checked_mul(node_len) {
+            return Err(SnapshotError::Corrupt("node area has the wrong length"));
+        }
+        for node in nodes.chunks_exact(node_len) {
+            let mut input = node;
+            for _ in 0..3 {
+                decode_index::<usize>(&mut input, header.count)?;
+            }
+        }
+        check_tree(header.root, count, |idx| {
+            let mut input = &nodes[idx * node_len..];
+            [(); 3].map(|_| u64::decode(&mut input).unwrap())
+        })?;
+        Ok(Self {
+            nodes,
+            count,
+            root: header.root,
+            node_len,
+            weight_len: header.sizes[1] as usize,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Number of entries in the snapshot.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    fn node(&self, idx: usize) -> &'a [u8] {
+        &self.nodes[idx * self.node_len..(idx + 1) * self.node_len]
+    }
+}
+
+impl<'a, K: Codec + Ord, W: Codec, V: Codec> SnapshotView<'a, K, W, V> {
+    /// Looks up `find_key` and decodes its value.
+    pub fn find(&self, find_key: &K) -> Option<V> {
+        let mut current = self.root;
+        while current != NONE {
+            let mut input = &self.node(current as usize)[8..];
+            let left = u64::decode(&mut input)?;
+            let right = u64::decode(&mut input)?;
+            let key = K::decode(&mut input)?;
+            current = match find_key.cmp(&key) {
+                Ordering::Less => left,
+                Ordering::Greater => right,
+                Ordering::Equal => {
+                    let mut input = &input[self.weight_len..];
+                    return V::decode(&mut input);
+                }
+            };
+        }
+        None
+    }
+}
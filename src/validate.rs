@@ -0,0 +1,116 @@
+//! Invariant checks shared by all trees.
+//!
+//! Every tree has a `validate` method that walks it in pre-order and reports
+//! the first node that breaks one of its invariants.
+
+use std::fmt;
+
+/// A step from a node to one of its children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// The invariant broken by a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// The key is not between the keys of the ancestors it hangs under, so
+    /// an in-order walk would not see the keys strictly increasing.
+    KeyOrder,
+    /// The weight is smaller than the weight of the parent.
+    HeapOrder,
+    /// The stored parent link does not point to the node's actual parent.
+    ParentLink,
+    /// A child index points outside the arena.
+    IndexOutOfBounds(usize),
+    /// The node is reached a second time, so it is linked from two places.
+    SharedNode,
+    /// The arena slot with this index is not reachable from the root.
+    Unreachable(usize),
+    /// The stored height is not one more than the larger stored height of
+    /// the children.
+    Height,
+    /// The heights of the two subtrees differ by more than one.
+    Unbalanced,
+    /// The root of a red-black tree is red.
+    RedRoot,
+    /// A red node has a red parent.
+    RedRed,
+    /// A red node is the right child of its parent in a left-leaning
+    /// red-black tree.
+    RedRightChild,
+    /// The path to this empty child passes a different number of black
+    /// nodes than the path to the leftmost one.
+    BlackHeight,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::KeyOrder => write!(f, "key out of search tree order"),
+            Violation::HeapOrder => write!(f, "weight smaller than the parent's weight"),
+            Violation::ParentLink => write!(f, "parent link does not point to the parent"),
+            Violation::IndexOutOfBounds(idx) => write!(f, "child index {} out of bounds", idx),
+            Violation::SharedNode => write!(f, "node linked from more than one parent"),
+            Violation::Unreachable(idx) => write!(f, "slot {} unreachable from the root", idx),
+            Violation::Height => write!(f, "stored height does not match the subtrees"),
+            Violation::Unbalanced => write!(f, "subtree heights differ by more than one"),
+            Violation::RedRoot => write!(f, "red root"),
+            Violation::RedRed => write!(f, "red node with a red parent"),
+            Violation::RedRightChild => write!(f, "red right child"),
+            Violation::BlackHeight => write!(f, "black height differs from the leftmost path"),
+        }
+    }
+}
+
+/// The first invariant violation found by a `validate` method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub violation: Violation,
+    /// Path from the root to the offending node. It is empty for the root
+    /// and for [`Violation::Unreachable`], which has no path.
+    pub path: Vec<Side>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at root", self.violation)?;
+        for side in &self.path {
+            match side {
+                Side::Left => write!(f, ".left")?,
+                Side::Right => write!(f, ".right")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Path to the node currently visited by a pre-order walk.
+///
+/// The walks push `(depth, side)` together with each node, where `depth` is
+/// the length of the node's path. Entering a node cuts the trail back to its
+/// parent, so the path never has to be cloned per node.
+#[derive(Default)]
+pub(crate) struct Trail(Vec<Side>);
+
+impl Trail {
+    pub fn enter(&mut self, depth: usize, side: Option<Side>) {
+        self.0.truncate(depth.saturating_sub(1));
+        self.0.extend(side);
+    }
+
+    pub fn error(&self, violation: Violation) -> ValidationError {
+        ValidationError {
+            violation,
+            path: self.0.clone(),
+        }
+    }
+}
+
+/// Returns `true` if `key` lies strictly between the given bounds.
+pub(crate) fn in_bounds<K: Ord>(key: &K, lower: Option<&K>, upper: Option<&K>) -> bool {
+    lower.is_none_or(|l| l < key) && upper.is_none_or(|u| key < u)
+}
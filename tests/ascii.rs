@@ -0,0 +1,79 @@
+//! Text rendering used by the REPL's `print` command.
+
+use treap_rust::{avl::AVL, bst::BST, rbtree::RBTree, treap, treap_vec, Treap};
+
+const INSERTS: [(i32, i32); 6] = [(4, 5), (2, 3), (6, 4), (1, 1), (3, 6), (5, 0)];
+
+fn treap_rec() -> Treap<i32, i32, ()> {
+    let mut treap = Treap::new();
+    for (key, weight) in INSERTS {
+        treap.insert(key, weight, ());
+    }
+    treap
+}
+
+#[test]
+fn renders_full_tree() {
+    let expected = "\
+5:0
+├── 1:1
+│   ├── ·
+│   └── 2:3
+│       ├── ·
+│       └── 4:5
+│           ├── 3:6
+│           └── ·
+└── 6:4
+";
+    let mut treap = treap::Treap::new();
+    let mut treap_vec = treap_vec::Treap::new();
+    for (key, weight) in INSERTS {
+        treap.insert(key, weight, ());
+        treap_vec.insert(key, weight, ());
+    }
+    assert_eq!(treap_rec().to_ascii(usize::MAX, usize::MAX), expected);
+    assert_eq!(treap.to_ascii(usize::MAX, usize::MAX), expected);
+    assert_eq!(treap_vec.to_ascii(usize::MAX, usize::MAX), expected);
+}
+
+#[test]
+fn truncates_by_depth_and_budget() {
+    let treap = treap_rec();
+    assert_eq!(treap.to_ascii(1, usize::MAX), "5:0\n├── 1:1\n│   ├── ·\n│   └── ...\n└── 6:4\n");
+    assert_eq!(treap.to_ascii(usize::MAX, 2), "5:0\n├── 1:1\n│   ├── ·\n│   └── ...\n└── ...\n");
+    assert_eq!(treap.to_ascii(0, 0), "...\n");
+}
+
+#[test]
+fn bst_and_empty_trees() {
+    let mut bst = BST::new();
+    for key in [2, 1, 3] {
+        bst.insert(key, ());
+    }
+    assert_eq!(bst.to_ascii(usize::MAX, usize::MAX), "2\n├── 1\n└── 3\n");
+    assert_eq!(BST::<i32, ()>::new().to_ascii(5, 5), "·\n");
+}
+
+#[test]
+fn avl_shows_heights() {
+    let mut avl = AVL::new();
+    for key in [1, 2, 3, 4] {
+        avl.insert(key, ());
+    }
+    assert_eq!(
+        avl.to_ascii(usize::MAX, usize::MAX),
+        "2:3\n├── 1:1\n└── 3:2\n    ├── ·\n    └── 4:1\n"
+    );
+}
+
+#[test]
+fn rbtree_shows_colours() {
+    let mut rbtree = RBTree::new();
+    for key in [1, 2, 3, 4] {
+        rbtree.insert(key, ());
+    }
+    assert_eq!(
+        rbtree.to_ascii(usize::MAX, usize::MAX),
+        "2:B\n├── 1:B\n└── 4:B\n    ├── 3:R\n    └── ·\n"
+    );
+}
@@ -0,0 +1,71 @@
+//! `avl::AVL` must stay balanced under every kind of input, including the
+//! sorted word list that turns `BST` into a linked list.
+
+use std::collections::BTreeMap;
+
+use treap_rust::avl::AVL;
+
+/// Height bound of an AVL tree with `n` nodes.
+fn max_height(n: usize) -> usize {
+    (1.45 * ((n + 2) as f64).log2()) as usize
+}
+
+#[test]
+fn sorted_inserts_stay_balanced() {
+    let mut avl = AVL::new();
+    for key in 0..5000u32 {
+        avl.insert(key, key);
+        assert!(avl.height() <= max_height(key as usize + 1));
+    }
+    avl.validate().unwrap();
+    // Inserting 2^k - 1 sorted keys builds a perfect tree.
+    let mut perfect = AVL::new();
+    for key in 0..4095u32 {
+        perfect.insert(key, ());
+    }
+    assert_eq!(perfect.height(), 12);
+    assert_eq!(perfect.leaf_count(), 2048);
+}
+
+#[test]
+fn removes_keep_the_tree_balanced() {
+    let rng = fastrand::Rng::with_seed(4);
+    let mut avl = AVL::new();
+    let mut model = BTreeMap::new();
+    for step in 0..20_000 {
+        let key = rng.u16(..2048);
+        if rng.u8(..3) == 0 {
+            assert_eq!(avl.remove(&key), model.remove(&key));
+        } else {
+            avl.insert(key, step);
+            model.entry(key).or_insert(step);
+        }
+        if step % 97 == 0 {
+            avl.validate().unwrap();
+            assert!(avl.height() <= max_height(model.len()));
+        }
+    }
+    assert!(avl.iter().eq(model.iter()));
+    for key in model.keys() {
+        assert_eq!(avl.remove(key), model.get(key).copied());
+    }
+    assert_eq!(avl.height(), 0);
+    assert_eq!(avl.remove(&0), None);
+}
+
+#[test]
+fn extract_if_rebuilds_a_balanced_tree() {
+    let mut avl = AVL::new();
+    for key in 0..1000u32 {
+        avl.insert(key, key);
+    }
+    let extracted: Vec<_> = avl.extract_if(|k, _| k % 3 == 0).take(100).collect();
+    assert_eq!(extracted.len(), 100);
+    assert_eq!(extracted.last(), Some(&(297, 297)));
+    avl.validate().unwrap();
+    assert_eq!(avl.iter().count(), 900);
+    assert_eq!(avl.height(), 10);
+    avl.retain(|k, _| k % 2 == 0);
+    avl.validate().unwrap();
+    assert!(avl.iter().all(|(k, _)| k % 2 == 0 && (*k >= 300 || k % 3 != 0)));
+}
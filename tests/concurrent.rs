@@ -0,0 +1,98 @@
+//! `treap_arc::Treap` read by many threads while one thread inserts.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use treap_rust::{treap_arc, Treap};
+
+const READERS: usize = 4;
+const INSERTS: u32 = 20_000;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn treap_is_send_and_sync() {
+    assert_send_sync::<treap_arc::Treap<String, i32, String>>();
+    assert_send_sync::<treap_arc::Snapshot<String, i32, String>>();
+}
+
+#[test]
+fn readers_see_consistent_snapshots() {
+    let treap = treap_arc::Treap::new();
+    let done = AtomicBool::new(false);
+    let rng = fastrand::Rng::with_seed(11);
+    let inserts: Vec<_> = (0..INSERTS).map(|_| (rng.u32(..INSERTS * 2), rng.i32(..))).collect();
+
+    thread::scope(|s| {
+        for reader in 0..READERS {
+            let (treap, done) = (&treap, &done);
+            s.spawn(move || {
+                let rng = fastrand::Rng::with_seed(reader as u64);
+                let mut last_len = 0;
+                let mut rounds = 0;
+                while !done.load(Ordering::Acquire) || rounds == 0 {
+                    rounds += 1;
+                    let snapshot = treap.snapshot();
+                    if let Err(err) = snapshot.validate() {
+                        panic!("reader {} saw an invalid treap: {}", reader, err);
+                    }
+                    // Every value is derived from its key, and a version
+                    // never loses entries of an earlier one.
+                    let mut len = 0;
+                    for (key, value) in &snapshot {
+                        assert_eq!(*value, u64::from(*key) * 3);
+                        len += 1;
+                    }
+                    assert!(len >= last_len, "snapshot shrank from {} to {}", last_len, len);
+                    last_len = len;
+                    for _ in 0..100 {
+                        let key = rng.u32(..INSERTS * 2);
+                        if let Some(value) = treap.find_cloned(&key) {
+                            assert_eq!(value, u64::from(key) * 3);
+                        }
+                    }
+                }
+            });
+        }
+
+        s.spawn(|| {
+            for &(key, weight) in &inserts {
+                treap.insert(key, weight, u64::from(key) * 3);
+            }
+            done.store(true, Ordering::Release);
+        });
+    });
+
+    let mut expected = Treap::new();
+    for &(key, weight) in &inserts {
+        expected.insert(key, weight, u64::from(key) * 3);
+    }
+    let snapshot = treap.snapshot();
+    assert_eq!(snapshot.shape(), expected.shape());
+    assert!(snapshot.iter().eq(expected.iter()));
+}
+
+#[test]
+fn snapshots_are_not_affected_by_later_inserts() {
+    let treap = treap_arc::Treap::new();
+    for key in 0..10 {
+        treap.insert(key, -key, key);
+    }
+    let before = treap.snapshot();
+    thread::scope(|s| {
+        for offset in 0..READERS as i32 {
+            let treap = &treap;
+            s.spawn(move || {
+                for key in (10 + offset..1000).step_by(READERS) {
+                    treap.insert(key, key % 7, key);
+                }
+            });
+        }
+    });
+    assert_eq!(before.iter().count(), 10);
+    assert_eq!(before.find(&10), None);
+    let after = treap.snapshot();
+    assert_eq!(after.iter().count(), 1000);
+    assert!(after.validate().is_ok());
+    assert!(after.iter().map(|(k, _)| *k).eq(0..1000));
+}
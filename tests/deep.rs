@@ -0,0 +1,138 @@
+//! Dropping, cloning and formatting degenerate trees must not recurse once
+//! per level.
+//!
+//! Every test runs on a thread with a small stack, so recursion over the
+//! depth of the tree overflows even for trees far smaller than the ones
+//! built here.
+
+use std::thread;
+
+use treap_rust::{bst::BST, splay::Splay, treap, treap_ptr, treap_vec, Treap};
+
+const STACK_SIZE: usize = 256 * 1024;
+/// Ascending keys with falling weights make every new node the root, with
+/// the previous root as its left child. Each insert only looks at the top
+/// of the tree, so building the chain takes linear time.
+const TREAP_NODES: u32 = 1_000_000;
+/// A BST chain can only be built by walking all the way down for every
+/// insert, which takes quadratic time.
+const BST_NODES: u32 = 20_000;
+
+fn on_small_stack(f: impl FnOnce() + Send + 'static) {
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(f)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+fn chain() -> impl Iterator<Item = (u32, i64)> {
+    (0..TREAP_NODES).map(|key| (key, -i64::from(key)))
+}
+
+#[test]
+fn drop_degenerate_bst() {
+    on_small_stack(|| {
+        let mut bst = BST::new();
+        for key in 0..BST_NODES {
+            bst.insert(key, key);
+        }
+        assert_eq!(bst.height(), BST_NODES as usize);
+        let debug = format!("{:?}", bst);
+        assert!(debug.starts_with("{0: 0, 1: 1, "));
+        assert!(debug.ends_with(&format!("{0}: {0}}}", BST_NODES - 1)));
+        let copy = bst.clone();
+        assert_eq!(copy.height(), BST_NODES as usize);
+        assert!(copy == bst);
+        drop(bst);
+        drop(copy);
+    });
+}
+
+#[test]
+fn drop_degenerate_splay() {
+    on_small_stack(|| {
+        // Every sorted insert puts the new key at the root, with the old
+        // root as its left child.
+        let mut splay = Splay::new();
+        for key in 0..TREAP_NODES {
+            splay.insert(key, key);
+        }
+        assert_eq!(splay.height(), TREAP_NODES as usize);
+        let copy = splay.clone();
+        assert_eq!(copy.height(), TREAP_NODES as usize);
+        assert!(copy == splay);
+        // Splaying the deepest key roughly halves the depth of the path.
+        assert_eq!(splay.find(&0), Some(&0));
+        assert!(splay.height() <= TREAP_NODES as usize / 2 + 2);
+        let debug = format!("{:?}", splay);
+        assert!(debug.starts_with("{0: 0, 1: 1, "));
+        drop(splay);
+        drop(copy);
+    });
+}
+
+#[test]
+fn drop_degenerate_treap() {
+    on_small_stack(|| {
+        let mut treap = Treap::new();
+        for (key, weight) in chain() {
+            treap.insert(key, weight, ());
+        }
+        assert_eq!(treap.height(), TREAP_NODES as usize);
+        let debug = format!("{:?}", treap);
+        assert!(debug.starts_with("{0: (), 1: (), "));
+        let copy = treap.clone();
+        assert_eq!(copy.height(), TREAP_NODES as usize);
+        assert!(copy == treap);
+        drop(treap);
+        drop(copy);
+    });
+}
+
+#[test]
+fn drop_degenerate_rc_treap() {
+    on_small_stack(|| {
+        let mut treap = treap::Treap::new();
+        for (key, weight) in chain() {
+            treap.insert(key, weight, ());
+        }
+        assert_eq!(treap.height(), TREAP_NODES as usize);
+        let debug = format!("{:?}", treap);
+        assert!(debug.starts_with("{0: (), 1: (), "));
+        let copy = treap.clone();
+        assert_eq!(copy.height(), TREAP_NODES as usize);
+        copy.validate().unwrap();
+        drop(treap);
+        drop(copy);
+    });
+}
+
+#[test]
+fn drop_degenerate_treap_vec() {
+    on_small_stack(|| {
+        let mut treap = treap_vec::Treap::new();
+        for (key, weight) in chain() {
+            treap.insert(key, weight, ());
+        }
+        assert_eq!(treap.height(), TREAP_NODES as usize);
+        let debug = format!("{:?}", treap);
+        assert!(debug.starts_with("{0: (), 1: (), "));
+        drop(treap);
+    });
+}
+
+#[test]
+fn drop_degenerate_treap_ptr() {
+    on_small_stack(|| {
+        let mut treap = treap_ptr::Treap::new();
+        for (key, weight) in chain() {
+            treap.insert(key, weight, ());
+        }
+        assert_eq!(treap.height(), TREAP_NODES as usize);
+        let debug = format!("{:?}", treap);
+        assert!(debug.starts_with("{0: (), 1: (), "));
+        drop(treap);
+    });
+}
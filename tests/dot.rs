@@ -0,0 +1,58 @@
+//! All treaps with the same keys and weights must produce the same DOT
+//! output, since nodes are numbered in pre-order.
+
+use treap_rust::{bst::BST, treap, treap_vec, Treap};
+
+const INSERTS: [(i32, i32); 6] = [(4, 5), (2, 3), (6, 4), (1, 1), (3, 6), (5, 0)];
+
+fn dot(write: impl FnOnce(&mut Vec<u8>) -> std::io::Result<()>) -> String {
+    let mut out = Vec::new();
+    write(&mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn treaps_write_identical_graphs() {
+    let mut treap = treap::Treap::new();
+    let mut treap_vec = treap_vec::Treap::new();
+    let mut treap_rec = Treap::new();
+    for (key, weight) in INSERTS {
+        treap.insert(key, weight, key * 10);
+        treap_vec.insert(key, weight, key * 10);
+        treap_rec.insert(key, weight, key * 10);
+    }
+    let expected = dot(|w| treap_rec.to_dot(w));
+    assert_eq!(dot(|w| treap.to_dot(w)), expected);
+    assert_eq!(dot(|w| treap_vec.to_dot(w)), expected);
+
+    assert!(expected.starts_with("digraph tree {\n"));
+    assert!(expected.ends_with("}\n"));
+    assert!(expected.contains("n0 [label=\"5: 50\\nw = 0\"];"));
+    // six nodes and seven empty children
+    assert_eq!(expected.matches("label=").count(), 6);
+    assert_eq!(expected.matches("shape=point").count(), 7);
+    assert_eq!(expected.matches(" -> ").count(), 12);
+}
+
+#[test]
+fn max_depth_cuts_subtrees() {
+    let mut treap = Treap::new();
+    for (key, weight) in INSERTS {
+        treap.insert(key, weight, key);
+    }
+    // shape: 5 -> (1 -> (-, 2 -> (-, 4 -> (3, -))), 6)
+    let out = dot(|w| treap.to_dot_with_max_depth(w, 1));
+    assert_eq!(out.matches("\\nw = ").count(), 3);
+    assert_eq!(out.matches("label=\"...\"").count(), 1);
+
+    let out = dot(|w| treap.to_dot_with_max_depth(w, 0));
+    assert_eq!(out.matches("label=\"...\"").count(), 2);
+}
+
+#[test]
+fn labels_are_escaped() {
+    let mut bst = BST::new();
+    bst.insert("say \"hi\"".to_string(), "back\\slash".to_string());
+    let out = dot(|w| bst.to_dot(w));
+    assert!(out.contains(r#"n0 [label="say \"hi\": back\\slash"];"#), "{}", out);
+}
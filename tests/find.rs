@@ -0,0 +1,55 @@
+//! Lookups in `treap::Treap` interleaved with inserts that rotate the found
+//! nodes. Small enough to run under Miri:
+//!
+//! ```sh
+//! cargo +nightly miri test --test find
+//! ```
+
+use std::collections::BTreeMap;
+
+use treap_rust::treap::Treap;
+
+const STEPS: u32 = if cfg!(miri) { 60 } else { 2_000 };
+
+#[test]
+fn lookups_between_inserts() {
+    let rng = fastrand::Rng::with_seed(7);
+    let mut treap = Treap::new();
+    let mut model = BTreeMap::new();
+    for step in 0..STEPS {
+        let key = rng.u32(..STEPS / 2);
+        let value = format!("value {}", step);
+        treap.insert(key, rng.i32(..), value.clone());
+        model.entry(key).or_insert(value);
+
+        let probe = rng.u32(..STEPS / 2);
+        let expected = model.get(&probe);
+        let found = treap.find(&probe);
+        assert_eq!(found.as_ref().map(|f| f.get().clone()).as_ref(), expected);
+        drop(found);
+        assert_eq!(treap.find_with(&probe, String::len), expected.map(String::len));
+        assert_eq!(treap.find_cloned(&probe).as_ref(), expected);
+    }
+    for (key, value) in &model {
+        assert_eq!(treap.find_cloned(key).as_ref(), Some(value));
+    }
+}
+
+#[test]
+fn guards_see_values_moved_by_rotations() {
+    // Every insert has a lighter weight than all earlier ones and rotates
+    // all the way up, so each found node is moved around afterwards.
+    let mut treap = Treap::new();
+    for key in 0..STEPS {
+        treap.insert(key, -(key as i64), vec![key; 3]);
+        for probe in [0, key / 2, key] {
+            let found = treap.find(&probe).unwrap();
+            let first = found.get();
+            let second = found.get();
+            assert_eq!(*first, vec![probe; 3]);
+            assert_eq!(*first, *second);
+        }
+    }
+    assert!(treap.find(&STEPS).is_none());
+    assert_eq!(treap.find_with(&STEPS, Vec::len), None);
+}
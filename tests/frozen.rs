@@ -0,0 +1,65 @@
+//! `treap_vec::Treap::freeze` and `Frozen::thaw`: a frozen treap must find
+//! exactly what the live one finds, and thawing must restore the same tree.
+
+use treap_rust::treap_vec::frozen::Frozen;
+use treap_rust::treap_vec::Treap;
+
+#[test]
+fn finds_the_same_entries_for_every_size() {
+    let rng = fastrand::Rng::with_seed(5);
+    // Covers complete trees as well as every way of filling the last level.
+    for len in 0..70u32 {
+        let mut treap = Treap::new();
+        for key in 0..len {
+            treap.insert(2 * key + 1, rng.u32(..), key);
+        }
+        let frozen = treap.clone().freeze();
+        assert_eq!(frozen.len(), len as usize);
+        assert_eq!(frozen.is_empty(), len == 0);
+        for probe in 0..=2 * len + 1 {
+            assert_eq!(frozen.find(&probe), treap.find(&probe), "len {}", len);
+            assert_eq!(frozen.find_with_prefetch(&probe), treap.find(&probe));
+        }
+        assert!(frozen.iter().eq(treap.iter()));
+    }
+}
+
+#[test]
+fn thaw_restores_the_shape() {
+    let rng = fastrand::Rng::with_seed(9);
+    let mut treap = Treap::<String, u64, usize, u16>::with_index();
+    for value in 0..3000 {
+        treap.insert(format!("{:x}", rng.u32(..)), rng.u64(..), value);
+        if value % 3 == 0 {
+            treap.remove(&format!("{:x}", rng.u32(..)));
+        }
+    }
+    let shape = treap.shape();
+    let entries: Vec<_> = treap.iter().map(|(k, v)| (k.clone(), *v)).collect();
+
+    let frozen: Frozen<String, u64, usize, u16> = treap.freeze();
+    for (key, value) in &entries {
+        assert_eq!(frozen.find(key), Some(value));
+    }
+    assert_eq!(frozen.find(&"not hex".to_string()), None);
+
+    let mut thawed = frozen.thaw();
+    thawed.validate().unwrap();
+    assert_eq!(thawed.shape(), shape);
+    assert!(thawed.iter().map(|(k, v)| (k.clone(), *v)).eq(entries));
+    // The thawed arena is a regular treap again.
+    thawed.insert("new".to_string(), 0, 0);
+    assert_eq!(thawed.remove(&"new".to_string()), Some(0));
+    thawed.validate().unwrap();
+}
+
+#[test]
+fn thaw_with_equal_weights_is_still_a_treap() {
+    let mut treap = Treap::new();
+    for key in (0..500).rev() {
+        treap.insert(key, key % 4, key);
+    }
+    let thawed = treap.freeze().thaw();
+    thawed.validate().unwrap();
+    assert!(thawed.iter().map(|(k, _)| *k).eq(0..500));
+}
@@ -0,0 +1,86 @@
+//! `treap_vec::Treap` with `u16`, `u32` and `usize` indices must behave the
+//! same until the arena is full, and report a full arena as an error.
+
+use treap_rust::treap_vec::snapshot::SnapshotError;
+use treap_rust::treap_vec::{ArenaIndex, CapacityError, Treap};
+
+const U16_FULL: u32 = <u16 as ArenaIndex>::MAX_NODES as u32;
+
+fn full_u16_treap() -> Treap<u32, u32, u32, u16> {
+    let rng = fastrand::Rng::with_seed(7);
+    let mut treap = Treap::with_index_and_capacity(U16_FULL as usize);
+    for key in 0..U16_FULL {
+        treap.try_insert(key, rng.u32(..), key).unwrap();
+    }
+    treap
+}
+
+#[test]
+fn all_widths_build_the_same_shape() {
+    let rng = fastrand::Rng::with_seed(1);
+    let mut narrow = Treap::<u16, i32, u32, u16>::with_index();
+    let mut default = Treap::<u16, i32, u32>::new();
+    let mut wide = Treap::<u16, i32, u32, usize>::with_index();
+    for _ in 0..2000 {
+        let (key, weight) = (rng.u16(..1024), rng.i32(0..64));
+        if rng.u8(..4) == 0 {
+            let expected = default.remove(&key);
+            assert_eq!(narrow.remove(&key), expected);
+            assert_eq!(wide.remove(&key), expected);
+        } else {
+            narrow.insert(key, weight, u32::from(key));
+            default.insert(key, weight, u32::from(key));
+            wide.insert(key, weight, u32::from(key));
+        }
+        assert_eq!(narrow.shape(), default.shape());
+        assert_eq!(wide.shape(), default.shape());
+    }
+    narrow.validate().unwrap();
+    wide.validate().unwrap();
+}
+
+#[test]
+fn full_arena_reports_an_error() {
+    let mut treap = full_u16_treap();
+    let err = treap.try_insert(U16_FULL, 0, 0).unwrap_err();
+    assert_eq!(
+        err,
+        CapacityError {
+            max_nodes: 65535,
+            index_type: "u16",
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "treap_vec::Treap with u16 indices cannot hold more than 65535 entries"
+    );
+    // Keys that are already present never need a new node.
+    assert_eq!(treap.try_insert(0, 0, 0), Ok(()));
+    assert_eq!(treap.remove(&0), Some(0));
+    assert_eq!(treap.try_insert(U16_FULL, 0, 0), Ok(()));
+    treap.validate().unwrap();
+}
+
+#[test]
+#[should_panic(expected = "cannot hold more than 65535 entries")]
+fn insert_into_full_arena_panics() {
+    full_u16_treap().insert(U16_FULL, 0, 0);
+}
+
+#[test]
+fn snapshot_too_large_for_the_index_type() {
+    let rng = fastrand::Rng::with_seed(3);
+    let mut treap = Treap::<u32, u32, u32>::new();
+    for key in 0..=U16_FULL {
+        treap.insert(key, rng.u32(..), key);
+    }
+    let mut bytes = Vec::new();
+    treap.save_to(&mut bytes).unwrap();
+
+    match Treap::<u32, u32, u32, u16>::load_from(&bytes[..]) {
+        Err(SnapshotError::Capacity(err)) => assert_eq!(err.max_nodes, 65535),
+        other => panic!("expected a capacity error, got {:?}", other.map(|_| ())),
+    }
+    let wide = Treap::<u32, u32, u32, usize>::load_from(&bytes[..]).unwrap();
+    assert_eq!(wide.shape(), treap.shape());
+}
@@ -0,0 +1,279 @@
+//! Differential tests of all trees against `std::collections::BTreeMap`.
+//!
+//! Each case is a random sequence of operations applied to every tree and to
+//! the model at once. A failing sequence is shrunk to a minimal one before
+//! the test panics. Set `TREAP_MODEL_SEED` to the printed seed to replay a
+//! single case.
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::panic::{self, AssertUnwindSafe};
+
+use treap_rust::{avl::AVL, bst::BST, rbtree::RBTree, splay::Splay};
+use treap_rust::{treap, treap_ptr, treap_soa, treap_vec, Treap};
+
+const CASES: usize = 200;
+const OPS_PER_CASE: usize = 400;
+const KEY_SPACE: u16 = 512;
+
+#[derive(Debug, Clone)]
+enum Op {
+    Insert(u16, i32, u32),
+    Find(u16),
+    Remove(u16),
+    /// Keep only the keys with `key % modulus != rem`.
+    Retain { modulus: u16, rem: u16 },
+    /// Extract at most `limit` keys with `key % modulus == rem`.
+    ExtractIf { modulus: u16, rem: u16, limit: usize },
+    Iter,
+}
+
+fn generate(seed: u64) -> Vec<Op> {
+    let rng = fastrand::Rng::with_seed(seed);
+    // A narrow weight range forces ties, and runs of ascending keys build
+    // the degenerate shapes the README talks about.
+    let weight_range = if rng.bool() { 0..8 } else { i32::MIN..i32::MAX };
+    let mut next_ascending = rng.u16(..KEY_SPACE);
+    (0..OPS_PER_CASE)
+        .map(|_| match rng.u8(..100) {
+            0..=34 => Op::Insert(
+                rng.u16(..KEY_SPACE),
+                rng.i32(weight_range.clone()),
+                rng.u32(..),
+            ),
+            35..=49 => {
+                next_ascending = (next_ascending + 1) % KEY_SPACE;
+                Op::Insert(next_ascending, rng.i32(weight_range.clone()), rng.u32(..))
+            }
+            50..=84 => Op::Find(rng.u16(..KEY_SPACE)),
+            85..=89 => Op::Remove(rng.u16(..KEY_SPACE)),
+            90..=93 => {
+                let modulus = rng.u16(2..8);
+                Op::Retain {
+                    modulus,
+                    rem: rng.u16(..modulus),
+                }
+            }
+            94..=97 => {
+                let modulus = rng.u16(1..8);
+                Op::ExtractIf {
+                    modulus,
+                    rem: rng.u16(..modulus),
+                    limit: rng.usize(..16),
+                }
+            }
+            _ => Op::Iter,
+        })
+        .collect()
+}
+
+fn check<T>(step: usize, name: &str, expected: T, actual: T) -> Result<(), String>
+where
+    T: PartialEq + Debug,
+{
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(format!(
+            "step {}: {} returned {:?}, model returned {:?}",
+            step, name, actual, expected
+        ))
+    }
+}
+
+/// Applies `ops` to every tree and the model and returns the first mismatch.
+fn run(ops: &[Op]) -> Result<(), String> {
+    let mut model = BTreeMap::new();
+    // `treap::Treap` cannot remove entries, so it is checked against a
+    // model that only sees the inserts.
+    let mut insert_model = BTreeMap::new();
+    let mut bst = BST::new();
+    let mut avl = AVL::new();
+    let mut rbtree = RBTree::new();
+    let mut splay = Splay::new();
+    let mut treap = treap::Treap::new();
+    let mut treap_vec = treap_vec::Treap::new();
+    let mut treap_soa = treap_soa::Treap::new();
+    let mut treap_ptr = treap_ptr::Treap::new();
+    let mut treap_rec = Treap::new();
+
+    for (step, op) in ops.iter().enumerate() {
+        match *op {
+            Op::Insert(key, weight, value) => {
+                // The trees keep the first value inserted for a key.
+                model.entry(key).or_insert(value);
+                insert_model.entry(key).or_insert(value);
+                bst.insert(key, value);
+                avl.insert(key, value);
+                rbtree.insert(key, value);
+                splay.insert(key, value);
+                treap.insert(key, weight, value);
+                treap_vec.insert(key, weight, value);
+                treap_soa.insert(key, weight, value);
+                treap_ptr.insert(key, weight, value);
+                treap_rec.insert(key, weight, value);
+            }
+            Op::Find(key) => {
+                let expected = model.get(&key).copied();
+                check(step, "BST::find", expected, bst.find(&key).copied())?;
+                check(step, "AVL::find", expected, avl.find(&key).copied())?;
+                check(step, "RBTree::find", expected, rbtree.find(&key).copied())?;
+                check(step, "Splay::find", expected, splay.find(&key).copied())?;
+                let inserted = insert_model.get(&key).copied();
+                check(step, "treap::Treap::find", inserted, treap.find_cloned(&key))?;
+                check(step, "treap_vec::Treap::find", expected, treap_vec.find(&key).copied())?;
+                check(step, "treap_soa::Treap::find", expected, treap_soa.find(&key).copied())?;
+                check(step, "treap_ptr::Treap::find", expected, treap_ptr.find(&key).copied())?;
+                check(step, "Treap::find", expected, treap_rec.find(&key).copied())?;
+            }
+            Op::Remove(key) => {
+                let expected = model.remove(&key);
+                check(step, "treap_vec::Treap::remove", expected, treap_vec.remove(&key))?;
+                check(step, "treap_soa::Treap::remove", expected, treap_soa.remove(&key))?;
+                check(step, "treap_ptr::Treap::remove", expected, treap_ptr.remove(&key))?;
+                check(step, "AVL::remove", expected, avl.remove(&key))?;
+                check(step, "RBTree::remove", expected, rbtree.remove(&key))?;
+                check(step, "Splay::remove", expected, splay.remove(&key))?;
+                // The other trees can only remove through `extract_if`.
+                let pred = |k: &u16, _: &mut u32| *k == key;
+                let actual = bst.extract_if(pred).next().map(|(_, v)| v);
+                check(step, "BST::extract_if", expected, actual)?;
+                let actual = treap_rec.extract_if(pred).next().map(|(_, v)| v);
+                check(step, "Treap::extract_if", expected, actual)?;
+            }
+            Op::Retain { modulus, rem } => {
+                model.retain(|k, _| k % modulus != rem);
+                bst.retain(|k, _| k % modulus != rem);
+                avl.retain(|k, _| k % modulus != rem);
+                rbtree.retain(|k, _| k % modulus != rem);
+                splay.retain(|k, _| k % modulus != rem);
+                treap_vec.retain(|k, _| k % modulus != rem);
+                treap_soa.retain(|k, _| k % modulus != rem);
+                treap_rec.retain(|k, _| k % modulus != rem);
+                // `treap_ptr::Treap` has neither `retain` nor `extract_if`
+                // and removes the same keys one by one.
+                let keys: Vec<_> = treap_ptr.iter().map(|(k, _)| *k).collect();
+                for key in keys.iter().filter(|k| *k % modulus == rem) {
+                    treap_ptr.remove(key);
+                }
+            }
+            Op::ExtractIf {
+                modulus,
+                rem,
+                limit,
+            } => {
+                let expected: Vec<_> = model
+                    .iter()
+                    .filter(|(k, _)| *k % modulus == rem)
+                    .map(|(k, v)| (*k, *v))
+                    .take(limit)
+                    .collect();
+                for (k, _) in &expected {
+                    model.remove(k);
+                    treap_ptr.remove(k);
+                }
+                let pred = |k: &u16, _: &mut u32| k % modulus == rem;
+                let actual: Vec<_> = bst.extract_if(pred).take(limit).collect();
+                check(step, "BST::extract_if", &expected, &actual)?;
+                let actual: Vec<_> = avl.extract_if(pred).take(limit).collect();
+                check(step, "AVL::extract_if", &expected, &actual)?;
+                let actual: Vec<_> = rbtree.extract_if(pred).take(limit).collect();
+                check(step, "RBTree::extract_if", &expected, &actual)?;
+                let actual: Vec<_> = splay.extract_if(pred).take(limit).collect();
+                check(step, "Splay::extract_if", &expected, &actual)?;
+                let actual: Vec<_> = treap_vec.extract_if(pred).take(limit).collect();
+                check(step, "treap_vec::Treap::extract_if", &expected, &actual)?;
+                let actual: Vec<_> = treap_soa.extract_if(pred).take(limit).collect();
+                check(step, "treap_soa::Treap::extract_if", &expected, &actual)?;
+                let actual: Vec<_> = treap_rec.extract_if(pred).take(limit).collect();
+                check(step, "Treap::extract_if", &expected, &actual)?;
+            }
+            Op::Iter => {
+                let expected: Vec<_> = model.iter().collect();
+                check(step, "BST::iter", &expected, &bst.iter().collect())?;
+                check(step, "AVL::iter", &expected, &avl.iter().collect())?;
+                check(step, "RBTree::iter", &expected, &rbtree.iter().collect())?;
+                check(step, "Splay::iter", &expected, &splay.iter().collect())?;
+                check(step, "treap_vec::Treap::iter", &expected, &treap_vec.iter().collect())?;
+                check(step, "treap_soa::Treap::iter", &expected, &treap_soa.iter().collect())?;
+                check(step, "treap_ptr::Treap::iter", &expected, &treap_ptr.iter().collect())?;
+                check(step, "Treap::iter", &expected, &treap_rec.iter().collect())?;
+            }
+        }
+    }
+
+    let end = ops.len();
+    check(end, "BST::validate", Ok(()), bst.validate())?;
+    check(end, "AVL::validate", Ok(()), avl.validate())?;
+    check(end, "RBTree::validate", Ok(()), rbtree.validate())?;
+    check(end, "Splay::validate", Ok(()), splay.validate())?;
+    check(end, "treap_vec::Treap::validate", Ok(()), treap_vec.validate())?;
+    check(end, "treap_soa::Treap::validate", Ok(()), treap_soa.validate())?;
+    check(end, "treap_ptr::Treap::validate", Ok(()), treap_ptr.validate())?;
+    check(end, "Treap::validate", Ok(()), treap_rec.validate())?;
+    Ok(())
+}
+
+/// Like [`run`], but turns panics inside the trees into failures so that
+/// shrinking can continue.
+fn run_catching(ops: &[Op]) -> Result<(), String> {
+    panic::catch_unwind(AssertUnwindSafe(|| run(ops))).unwrap_or_else(|payload| {
+        let msg = payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_default();
+        Err(format!("panicked: {}", msg))
+    })
+}
+
+/// Removes chunks of operations as long as the sequence keeps failing.
+fn shrink(mut ops: Vec<Op>) -> Vec<Op> {
+    let mut chunk = ops.len() / 2;
+    while chunk > 0 {
+        let mut start = 0;
+        let mut removed_any = false;
+        while start + chunk <= ops.len() {
+            let mut candidate = ops.clone();
+            candidate.drain(start..start + chunk);
+            if run_catching(&candidate).is_err() {
+                ops = candidate;
+                removed_any = true;
+            } else {
+                start += chunk;
+            }
+        }
+        if !removed_any {
+            chunk /= 2;
+        }
+    }
+    ops
+}
+
+fn run_seed(seed: u64) {
+    let ops = generate(seed);
+    if run_catching(&ops).is_ok() {
+        return;
+    }
+    let minimal = shrink(ops);
+    let err = run_catching(&minimal).unwrap_err();
+    panic!(
+        "model mismatch for seed {seed}: {err}\n\
+         replay with TREAP_MODEL_SEED={seed} cargo test --test model\n\
+         minimal sequence ({} ops): {:#?}",
+        minimal.len(),
+        minimal
+    );
+}
+
+#[test]
+fn trees_match_btreemap() {
+    if let Ok(seed) = std::env::var("TREAP_MODEL_SEED") {
+        run_seed(seed.parse().expect("TREAP_MODEL_SEED must be a u64"));
+        return;
+    }
+    let base = fastrand::u64(..);
+    for case in 0..CASES as u64 {
+        run_seed(base.wrapping_add(case));
+    }
+}
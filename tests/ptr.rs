@@ -0,0 +1,157 @@
+//! `treap_ptr::Treap` against a `BTreeMap` and against `treap_vec::Treap`,
+//! which builds the same shapes. Small enough to run under Miri, which
+//! checks the pointer code for undefined behaviour and leaks:
+//!
+//! ```sh
+//! cargo +nightly miri test --test ptr
+//! ```
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::thread;
+
+use treap_rust::{treap_ptr::Treap, treap_vec};
+
+const STEPS: u32 = if cfg!(miri) { 300 } else { 5_000 };
+
+#[test]
+fn matches_model_and_treap_vec() {
+    let rng = fastrand::Rng::with_seed(11);
+    let mut treap = Treap::new();
+    let mut treap_vec = treap_vec::Treap::new();
+    let mut model = BTreeMap::new();
+    for step in 0..STEPS {
+        let key = rng.u32(..STEPS / 4);
+        match rng.u8(..10) {
+            0..=2 => {
+                assert_eq!(treap.remove(&key), model.remove(&key), "step {}", step);
+                treap_vec.remove(&key);
+            }
+            3 => assert_eq!(treap.find(&key), model.get(&key), "step {}", step),
+            _ => {
+                // Few distinct weights make ties common.
+                let weight = rng.i32(0..16);
+                treap.insert(key, weight, step);
+                treap_vec.insert(key, weight, step);
+                model.entry(key).or_insert(step);
+            }
+        }
+        assert_eq!(treap.shape(), treap_vec.shape(), "step {}", step);
+    }
+    treap.validate().unwrap();
+    assert!(treap.iter().eq(model.iter()));
+    assert_eq!(treap.shape(), treap_vec.shape());
+}
+
+#[test]
+fn rotations_to_the_root() {
+    // Falling weights rotate every new node up to the root, alternately
+    // from the left and the right.
+    let mut treap = Treap::new();
+    for i in 0..STEPS {
+        let key = if i % 2 == 0 { i } else { u32::MAX - i };
+        treap.insert(key, -i64::from(i), i);
+        assert_eq!(treap.find(&key), Some(&i));
+    }
+    treap.validate().unwrap();
+    for i in (0..STEPS).step_by(3) {
+        let key = if i % 2 == 0 { i } else { u32::MAX - i };
+        assert_eq!(treap.remove(&key), Some(i));
+    }
+    treap.validate().unwrap();
+}
+
+#[test]
+fn values_are_dropped_exactly_once() {
+    let value = Rc::new(());
+    let mut treap = Treap::new();
+    for key in 0..STEPS / 2 {
+        treap.insert(key % 64, key, Rc::clone(&value));
+    }
+    // Duplicate keys drop the rejected value right away.
+    assert_eq!(Rc::strong_count(&value), 65);
+    let removed = treap.remove(&3).unwrap();
+    assert_eq!(Rc::strong_count(&value), 65);
+    drop(removed);
+    assert_eq!(Rc::strong_count(&value), 64);
+    assert!(treap.remove(&3).is_none());
+    drop(treap);
+    assert_eq!(Rc::strong_count(&value), 1);
+}
+
+#[test]
+fn shared_between_threads() {
+    let mut treap = Treap::new();
+    for key in 0..64u32 {
+        treap.insert(key, key.wrapping_mul(2_654_435_761), key.to_string());
+    }
+    let treap = thread::spawn(move || {
+        assert_eq!(treap.find(&7).map(String::as_str), Some("7"));
+        treap
+    })
+    .join()
+    .unwrap();
+    thread::scope(|s| {
+        for probe in [1, 30, 63] {
+            let treap = &treap;
+            s.spawn(move || assert_eq!(treap.find(&probe), Some(&probe.to_string())));
+        }
+    });
+    assert_eq!(treap.iter().count(), 64);
+}
+
+/// Number of weight comparisons left before the next one panics, `0` for
+/// never.
+static COMPARISONS_LEFT: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Weight(u32);
+
+impl PartialOrd for Weight {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Weight {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let left = COMPARISONS_LEFT.load(Relaxed);
+        if left > 0 {
+            COMPARISONS_LEFT.store(left - 1, Relaxed);
+            assert!(left > 1, "weight comparison failed");
+        }
+        self.0.cmp(&other.0)
+    }
+}
+
+#[test]
+fn panicking_ord_in_remove_leaves_the_treap_intact() {
+    let rng = fastrand::Rng::with_seed(5);
+    let mut treap = Treap::new();
+    for key in 0..200u32 {
+        treap.insert(key, Weight(rng.u32(..)), key);
+    }
+    let shape = treap.shape();
+    let root = shape[0].0;
+    // Removing the root merges its two subtrees. Let each of the
+    // comparisons fail in turn, until the remove gets through.
+    for fail_at in 1.. {
+        COMPARISONS_LEFT.store(fail_at, Relaxed);
+        let removed = panic::catch_unwind(AssertUnwindSafe(|| treap.remove(&root)));
+        COMPARISONS_LEFT.store(0, Relaxed);
+        if let Ok(removed) = removed {
+            assert_eq!(removed, Some(root));
+            assert!(fail_at > 3, "only {} comparisons", fail_at - 1);
+            break;
+        }
+        assert_eq!(treap.shape(), shape, "comparison {} failed", fail_at);
+        treap.validate().unwrap();
+    }
+    treap.validate().unwrap();
+    assert_eq!(treap.iter().count(), 199);
+    // Every node is freed once.
+    drop(treap);
+}
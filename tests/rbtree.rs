@@ -0,0 +1,90 @@
+//! `rbtree::RBTree` must keep its colour rules under sorted inserts and
+//! arbitrary removes, and count the rotations it needs for that.
+
+use std::collections::BTreeMap;
+
+use treap_rust::rbtree::RBTree;
+
+/// Height bound of a red-black tree with `n` nodes.
+fn max_height(n: usize) -> usize {
+    (2.0 * ((n + 1) as f64).log2()) as usize
+}
+
+#[test]
+fn sorted_inserts_stay_balanced() {
+    let mut tree = RBTree::new();
+    for key in 0..5000u32 {
+        tree.insert(key, key);
+        assert!(tree.height() <= max_height(key as usize + 1));
+    }
+    tree.validate().unwrap();
+    assert!(tree.iter().map(|(k, _)| *k).eq(0..5000));
+    // Sorted inserts keep adding red right children, which costs about one
+    // rotation per insert.
+    assert_eq!(tree.rotations(), 4988);
+
+    let rotations = tree.rotations();
+    tree.insert(17, 0);
+    assert_eq!(tree[&17], 17);
+    assert_eq!(tree.rotations(), rotations);
+}
+
+#[test]
+fn removes_keep_the_colours() {
+    let rng = fastrand::Rng::with_seed(6);
+    let mut tree = RBTree::new();
+    let mut model = BTreeMap::new();
+    for step in 0..20_000 {
+        let key = rng.u16(..2048);
+        if rng.u8(..3) == 0 {
+            assert_eq!(tree.remove(&key), model.remove(&key));
+        } else {
+            tree.insert(key, step);
+            model.entry(key).or_insert(step);
+        }
+        if step % 97 == 0 {
+            tree.validate().unwrap();
+            assert!(tree.height() <= max_height(model.len()));
+        }
+    }
+    assert!(tree.iter().eq(model.iter()));
+    for key in model.keys() {
+        assert_eq!(tree.remove(key), model.get(key).copied());
+        tree.validate().unwrap();
+    }
+    assert_eq!(tree.height(), 0);
+    assert_eq!(tree.remove(&0), None);
+}
+
+#[test]
+fn extract_if_keeps_the_colours() {
+    let mut tree = RBTree::new();
+    for key in 0..1000u32 {
+        tree.insert(key, key);
+    }
+    let rotations = tree.rotations();
+    let extracted: Vec<_> = tree.extract_if(|k, _| k % 3 == 0).take(100).collect();
+    assert_eq!(extracted.len(), 100);
+    assert_eq!(extracted.last(), Some(&(297, 297)));
+    tree.validate().unwrap();
+    assert_eq!(tree.iter().count(), 900);
+    tree.retain(|k, _| k % 2 == 0);
+    tree.validate().unwrap();
+    assert!(tree.iter().all(|(k, _)| k % 2 == 0 && (*k >= 300 || k % 3 != 0)));
+    // The tree is built again without rotating.
+    assert_eq!(tree.rotations(), rotations);
+}
+
+#[test]
+fn retain_builds_a_valid_tree_of_every_size() {
+    for len in 0..400u32 {
+        let mut tree = RBTree::new();
+        for key in 0..2 * len {
+            tree.insert(key, key);
+        }
+        tree.retain(|k, _| k % 2 == 0);
+        tree.validate().unwrap();
+        assert!(tree.iter().map(|(k, _)| *k).eq((0..2 * len).step_by(2)));
+        assert!(tree.height() <= max_height(len as usize));
+    }
+}
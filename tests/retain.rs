@@ -0,0 +1,180 @@
+//! `retain` and `extract_if` of `BST`, `Treap` and `treap_vec::Treap`: the
+//! removed entries come out in key order, the rest stays findable, and an
+//! iterator dropped early keeps everything it has not visited, also when
+//! the predicate or the weight comparison panics.
+
+use std::cmp::Ordering;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+use treap_rust::{bst::BST, treap_vec, Treap};
+
+const KEYS: u32 = 1000;
+
+fn shuffled_keys(seed: u64) -> Vec<u32> {
+    let rng = fastrand::Rng::with_seed(seed);
+    let mut keys: Vec<u32> = (0..KEYS).collect();
+    rng.shuffle(&mut keys);
+    keys
+}
+
+#[test]
+fn bst_retain_and_extract_if() {
+    let mut bst = BST::new();
+    for key in shuffled_keys(1) {
+        bst.insert(key, key * 10);
+    }
+    bst.retain(|k, v| {
+        *v += 1;
+        k % 3 != 0
+    });
+    bst.validate().unwrap();
+    assert_eq!(bst.find(&3), None);
+    assert_eq!(bst.find(&4), Some(&41));
+
+    let extracted: Vec<_> = bst.extract_if(|k, _| k % 2 == 0).take(5).collect();
+    assert_eq!(extracted, [(2, 21), (4, 41), (8, 81), (10, 101), (14, 141)]);
+    bst.validate().unwrap();
+    assert_eq!(bst.find(&14), None);
+    // Dropped after the fifth match: later even keys are still there.
+    assert_eq!(bst.find(&16), Some(&161));
+    assert_eq!(bst.iter().count(), 666 - 5);
+}
+
+#[test]
+fn treap_retain_and_extract_if() {
+    let rng = fastrand::Rng::with_seed(2);
+    let mut treap = Treap::new();
+    for key in shuffled_keys(2) {
+        treap.insert(key, rng.u32(..), key * 10);
+    }
+    treap.retain(|k, v| {
+        *v += 1;
+        k % 3 != 0
+    });
+    treap.validate().unwrap();
+    assert_eq!(treap.find(&3), None);
+    assert_eq!(treap.find(&4), Some(&41));
+
+    let extracted: Vec<_> = treap.extract_if(|k, _| k % 2 == 0).take(5).collect();
+    assert_eq!(extracted, [(2, 21), (4, 41), (8, 81), (10, 101), (14, 141)]);
+    treap.validate().unwrap();
+    assert_eq!(treap.find(&14), None);
+    assert_eq!(treap.find(&16), Some(&161));
+    assert_eq!(treap.iter().count(), 666 - 5);
+}
+
+#[test]
+fn treap_vec_retain_and_extract_if() {
+    let rng = fastrand::Rng::with_seed(3);
+    let mut treap = treap_vec::Treap::new();
+    for key in shuffled_keys(3) {
+        treap.insert(key, rng.u32(..), key * 10);
+    }
+    treap.retain(|k, v| {
+        *v += 1;
+        k % 3 != 0
+    });
+    treap.validate().unwrap();
+    assert_eq!(treap.find(&3), None);
+    assert_eq!(treap.find(&4), Some(&41));
+
+    let extracted: Vec<_> = treap.extract_if(|k, _| k % 2 == 0).take(5).collect();
+    assert_eq!(extracted, [(2, 21), (4, 41), (8, 81), (10, 101), (14, 141)]);
+    treap.validate().unwrap();
+    assert_eq!(treap.find(&14), None);
+    assert_eq!(treap.find(&16), Some(&161));
+    assert_eq!(treap.iter().count(), 666 - 5);
+
+    // Removing everything leaves an empty treap that can be filled again.
+    treap.retain(|_, _| false);
+    assert_eq!(treap.iter().count(), 0);
+    treap.insert(1, 0, 1);
+    assert_eq!(treap.find(&1), Some(&1));
+}
+
+/// Lets `retain` panic in the predicate at several keys. The odd keys below
+/// the failing one are removed, everything else stays.
+macro_rules! assert_panicking_predicate_keeps {
+    ($tree:expr) => {{
+        let tree = &mut $tree;
+        for fail_at in [0, 1, 500, 998, 999] {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                tree.retain(|&k, _| {
+                    assert!(k != fail_at, "predicate failed");
+                    k % 2 == 0 || k > fail_at
+                })
+            }));
+            assert!(result.is_err());
+            tree.validate().unwrap();
+            let expected = (0..KEYS).filter(|&k| k % 2 == 0 || k >= fail_at);
+            assert!(tree.iter().map(|(k, _)| *k).eq(expected));
+        }
+    }};
+}
+
+#[test]
+fn panicking_predicate_keeps_the_tree() {
+    let rng = fastrand::Rng::with_seed(4);
+    let mut bst = BST::new();
+    let mut treap = Treap::new();
+    let mut treap_vec = treap_vec::Treap::new();
+    for key in shuffled_keys(4) {
+        let weight = rng.u32(..);
+        bst.insert(key, key);
+        treap.insert(key, weight, key);
+        treap_vec.insert(key, weight, key);
+    }
+    assert_panicking_predicate_keeps!(bst);
+    assert_panicking_predicate_keeps!(treap);
+    assert_panicking_predicate_keeps!(treap_vec);
+}
+
+/// Number of weight comparisons left before the next one panics, `0` for
+/// never.
+static COMPARISONS_LEFT: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Weight(u32);
+
+impl PartialOrd for Weight {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Weight {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let left = COMPARISONS_LEFT.load(Relaxed);
+        if left > 0 {
+            COMPARISONS_LEFT.store(left - 1, Relaxed);
+            assert!(left > 1, "weight comparison failed");
+        }
+        self.0.cmp(&other.0)
+    }
+}
+
+#[test]
+fn panicking_ord_in_retain_keeps_the_treap() {
+    let rng = fastrand::Rng::with_seed(5);
+    let mut treap = Treap::new();
+    for key in shuffled_keys(5) {
+        treap.insert(key, Weight(rng.u32(..)), key);
+    }
+    // Removing a key merges its two subtrees. Let each of the comparisons
+    // fail in turn, until the retain gets through.
+    for fail_at in 1.. {
+        COMPARISONS_LEFT.store(fail_at, Relaxed);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| treap.retain(|k, _| k % 3 != 0)));
+        COMPARISONS_LEFT.store(0, Relaxed);
+        treap.validate().unwrap();
+        // Keys that the predicate keeps are never lost.
+        let kept: Vec<_> = treap.iter().map(|(k, _)| *k).filter(|k| k % 3 != 0).collect();
+        assert_eq!(kept.len(), 666, "comparison {} failed", fail_at);
+        if result.is_ok() {
+            assert!(fail_at > 3, "only {} comparisons", fail_at - 1);
+            break;
+        }
+    }
+    assert_eq!(treap.iter().count(), 666);
+}
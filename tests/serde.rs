@@ -0,0 +1,124 @@
+//! Round trips through `serde_json`: every tree is written as an ordered
+//! map, and `Weighted` treaps come back with exactly the same shape.
+#![cfg(feature = "serde")]
+
+use treap_rust::{avl::AVL, bst::BST, rbtree::RBTree, splay::Splay};
+use treap_rust::{treap, treap_vec, Treap, Weighted};
+
+fn words() -> Vec<(String, u32)> {
+    let rng = fastrand::Rng::with_seed(11);
+    (0..300).map(|i| (format!("{:x}", rng.u32(..)), i)).collect()
+}
+
+/// The JSON object every tree holding `words()` is written as.
+fn expected_json() -> String {
+    let map: std::collections::BTreeMap<_, _> = words().into_iter().collect();
+    serde_json::to_string(&map).unwrap()
+}
+
+#[test]
+fn trees_round_trip_as_maps() {
+    let mut bst = BST::new();
+    let mut avl = AVL::new();
+    let mut rbtree = RBTree::new();
+    let mut splay = Splay::new();
+    for (key, value) in words() {
+        bst.insert(key.clone(), value);
+        avl.insert(key.clone(), value);
+        rbtree.insert(key.clone(), value);
+        splay.insert(key, value);
+    }
+    let json = expected_json();
+
+    assert_eq!(serde_json::to_string(&bst).unwrap(), json);
+    let back: BST<String, u32> = serde_json::from_str(&json).unwrap();
+    back.validate().unwrap();
+    assert!(back == bst);
+
+    assert_eq!(serde_json::to_string(&avl).unwrap(), json);
+    let back: AVL<String, u32> = serde_json::from_str(&json).unwrap();
+    back.validate().unwrap();
+    assert!(back == avl);
+
+    assert_eq!(serde_json::to_string(&rbtree).unwrap(), json);
+    let back: RBTree<String, u32> = serde_json::from_str(&json).unwrap();
+    back.validate().unwrap();
+    assert!(back == rbtree);
+
+    assert_eq!(serde_json::to_string(&splay).unwrap(), json);
+    let back: Splay<String, u32> = serde_json::from_str(&json).unwrap();
+    back.validate().unwrap();
+    assert!(back == splay);
+}
+
+#[test]
+fn treaps_round_trip_as_maps_with_new_weights() {
+    let rng = fastrand::Rng::with_seed(12);
+    let mut rec = Treap::new();
+    let mut vec = treap_vec::Treap::new();
+    let mut rc = treap::Treap::new();
+    for (key, value) in words() {
+        let weight = rng.i32(..);
+        rec.insert(key.clone(), weight, value);
+        vec.insert(key.clone(), weight, value);
+        rc.insert(key, weight, value);
+    }
+    let json = expected_json();
+
+    assert_eq!(serde_json::to_string(&rec).unwrap(), json);
+    let back: Treap<String, i32, u32> = serde_json::from_str(&json).unwrap();
+    back.validate().unwrap();
+    assert!(back.iter().eq(rec.iter()));
+
+    assert_eq!(serde_json::to_string(&vec).unwrap(), json);
+    let back: treap_vec::Treap<String, i32, u32> = serde_json::from_str(&json).unwrap();
+    back.validate().unwrap();
+    assert!(back.iter().eq(vec.iter()));
+
+    assert_eq!(serde_json::to_string(&rc).unwrap(), json);
+    let back: treap::Treap<String, i32, u32> = serde_json::from_str(&json).unwrap();
+    back.validate().unwrap();
+    assert_eq!(serde_json::to_string(&back).unwrap(), json);
+}
+
+#[test]
+fn weighted_treaps_keep_their_shape() {
+    let rng = fastrand::Rng::with_seed(13);
+    let mut rec = Treap::new();
+    let mut vec = treap_vec::Treap::new();
+    for (key, value) in words() {
+        let weight = rng.i32(..);
+        rec.insert(key.clone(), weight, value);
+        vec.insert(key, weight, value);
+    }
+
+    let json = serde_json::to_string(&Weighted(&rec)).unwrap();
+    let Weighted(back): Weighted<Treap<String, i32, u32>> = serde_json::from_str(&json).unwrap();
+    back.validate().unwrap();
+    assert_eq!(back.shape(), rec.shape());
+    assert!(back.iter().eq(rec.iter()));
+
+    let json = serde_json::to_string(&Weighted(&vec)).unwrap();
+    let Weighted(back): Weighted<treap_vec::Treap<String, i32, u32>> =
+        serde_json::from_str(&json).unwrap();
+    back.validate().unwrap();
+    assert_eq!(back.shape(), vec.shape());
+    assert!(back.iter().eq(vec.iter()));
+
+    // Both treaps hold the same entries and weights, so they write the same
+    // pre-order sequence.
+    assert_eq!(json, serde_json::to_string(&Weighted(&rec)).unwrap());
+}
+
+#[test]
+fn weighted_json_is_a_preorder_sequence() {
+    let mut treap = Treap::new();
+    for (key, weight) in [(2, 0), (1, 5), (3, 7)] {
+        treap.insert(key, weight, key * 10);
+    }
+    let json = serde_json::to_string(&Weighted(treap)).unwrap();
+    assert_eq!(json, "[[2,0,20],[1,5,10],[3,7,30]]");
+    let treap: treap_vec::Treap<i32, i32, i32> =
+        serde_json::from_str::<Weighted<_>>(&json).unwrap().0;
+    assert_eq!(treap.shape(), [(2, 0), (1, 5), (3, 7)]);
+}
@@ -0,0 +1,212 @@
+//! The shape of a treap is fully determined by its keys and weights, so all
+//! treap implementations must build the exact same tree from the same
+//! inserts. These tests compare the pre-order shapes after every operation.
+
+use std::cmp::Ordering;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+use treap_rust::treap_vec::InsertStrategy;
+use treap_rust::{treap, treap_soa, treap_vec, Treap};
+
+const CASES: u64 = 60;
+const OPS_PER_CASE: usize = 200;
+
+fn assert_same_shape(
+    seed: u64,
+    step: usize,
+    treap: &treap::Treap<u16, i32, u32>,
+    treap_vec: &treap_vec::Treap<u16, i32, u32>,
+    treap_soa: &treap_soa::Treap<u16, i32, u32>,
+    treap_rec: &Treap<u16, i32, u32>,
+) {
+    let expected = treap_rec.shape();
+    let context = format!("seed {seed}, step {step}");
+    assert_eq!(expected, treap.shape(), "treap::Treap differs ({context})");
+    assert_eq!(expected, treap_vec.shape(), "treap_vec::Treap differs ({context})");
+    assert_eq!(expected, treap_soa.shape(), "treap_soa::Treap differs ({context})");
+    if let Err(err) = treap.validate() {
+        panic!("treap::Treap invalid: {err} ({context})");
+    }
+    if let Err(err) = treap_vec.validate() {
+        panic!("treap_vec::Treap invalid: {err} ({context})");
+    }
+    if let Err(err) = treap_soa.validate() {
+        panic!("treap_soa::Treap invalid: {err} ({context})");
+    }
+    if let Err(err) = treap_rec.validate() {
+        panic!("Treap invalid: {err} ({context})");
+    }
+}
+
+fn random_insert(rng: &fastrand::Rng) -> (u16, i32, u32) {
+    // Few distinct weights make ties common, which is where the
+    // implementations are most likely to disagree.
+    (rng.u16(..256), rng.i32(0..16), rng.u32(..))
+}
+
+#[test]
+fn inserts_build_identical_shapes() {
+    for seed in 0..CASES {
+        let rng = fastrand::Rng::with_seed(seed);
+        let mut treap = treap::Treap::new();
+        let mut treap_vec = treap_vec::Treap::new();
+        let mut treap_soa = treap_soa::Treap::new();
+        let mut treap_rec = Treap::new();
+        for step in 0..OPS_PER_CASE {
+            let (key, weight, value) = random_insert(&rng);
+            treap.insert(key, weight, value);
+            treap_vec.insert(key, weight, value);
+            treap_soa.insert(key, weight, value);
+            treap_rec.insert(key, weight, value);
+            assert_same_shape(seed, step, &treap, &treap_vec, &treap_soa, &treap_rec);
+        }
+    }
+}
+
+#[test]
+fn removals_keep_shapes_identical() {
+    // `treap::Treap` cannot remove entries, so it is rebuilt from the shape
+    // of the recursive treap after every removal. Inserting a shape in
+    // pre-order reproduces it exactly.
+    for seed in 0..CASES {
+        let rng = fastrand::Rng::with_seed(seed);
+        let mut treap = treap::Treap::new();
+        let mut treap_vec = treap_vec::Treap::new();
+        let mut treap_soa = treap_soa::Treap::new();
+        let mut treap_rec = Treap::new();
+        for step in 0..OPS_PER_CASE {
+            match rng.u8(..10) {
+                0 => {
+                    let modulus = rng.u16(2..6);
+                    let keep = |k: &u16, _: &mut u32| !k.is_multiple_of(modulus);
+                    treap_vec.retain(keep);
+                    treap_soa.retain(keep);
+                    treap_rec.retain(keep);
+                }
+                1 => {
+                    let modulus = rng.u16(1..6);
+                    let limit = rng.usize(..8);
+                    let pred = |k: &u16, _: &mut u32| k.is_multiple_of(modulus);
+                    treap_vec.extract_if(pred).take(limit).for_each(drop);
+                    treap_soa.extract_if(pred).take(limit).for_each(drop);
+                    treap_rec.extract_if(pred).take(limit).for_each(drop);
+                }
+                2 => {
+                    let key = rng.u16(..256);
+                    let expected = treap_rec.extract_if(|k, _| *k == key).next();
+                    assert_eq!(treap_vec.remove(&key), expected.map(|(_, v)| v));
+                    assert_eq!(treap_soa.remove(&key), expected.map(|(_, v)| v));
+                }
+                _ => {
+                    let (key, weight, value) = random_insert(&rng);
+                    treap.insert(key, weight, value);
+                    treap_vec.insert(key, weight, value);
+                    treap_soa.insert(key, weight, value);
+                    treap_rec.insert(key, weight, value);
+                    assert_same_shape(seed, step, &treap, &treap_vec, &treap_soa, &treap_rec);
+                    continue;
+                }
+            }
+            treap = treap::Treap::new();
+            for (key, weight) in treap_rec.shape() {
+                treap.insert(key, weight, 0);
+            }
+            assert_same_shape(seed, step, &treap, &treap_vec, &treap_soa, &treap_rec);
+        }
+    }
+}
+
+#[test]
+fn insert_strategies_build_identical_shapes() {
+    for seed in 0..CASES {
+        let rng = fastrand::Rng::with_seed(seed);
+        let mut top_down = treap_vec::Treap::new();
+        let mut bottom_up = treap_vec::Treap::new();
+        for step in 0..OPS_PER_CASE {
+            if rng.u8(..5) == 0 {
+                let key = rng.u16(..256);
+                assert_eq!(top_down.remove(&key), bottom_up.remove(&key));
+            } else {
+                let (key, weight, value) = random_insert(&rng);
+                top_down.insert_with(InsertStrategy::TopDown, key, weight, value);
+                bottom_up.insert_with(InsertStrategy::BottomUp, key, weight, value);
+            }
+            let context = format!("seed {seed}, step {step}");
+            assert_eq!(top_down.shape(), bottom_up.shape(), "shapes differ ({context})");
+            if let Err(err) = top_down.validate() {
+                panic!("top-down treap invalid: {err} ({context})");
+            }
+        }
+    }
+}
+
+#[test]
+fn rotations_lift_lighter_nodes() {
+    let mut treap = treap::Treap::new();
+    let mut treap_vec = treap_vec::Treap::new();
+    let mut treap_soa = treap_soa::Treap::new();
+    let mut treap_rec = Treap::new();
+    for (key, weight) in [(4, 5), (2, 3), (6, 4), (1, 1), (3, 6), (5, 0)] {
+        treap.insert(key, weight, ());
+        treap_vec.insert(key, weight, ());
+        treap_soa.insert(key, weight, ());
+        treap_rec.insert(key, weight, ());
+    }
+    let expected = vec![(5, 0), (1, 1), (2, 3), (4, 5), (3, 6), (6, 4)];
+    assert_eq!(treap.shape(), expected);
+    assert_eq!(treap_vec.shape(), expected);
+    assert_eq!(treap_soa.shape(), expected);
+    assert_eq!(treap_rec.shape(), expected);
+}
+
+/// Number of key comparisons left before the next one panics, `0` for never.
+static COMPARISONS_LEFT: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Key(u32);
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let left = COMPARISONS_LEFT.load(Relaxed);
+        if left > 0 {
+            COMPARISONS_LEFT.store(left - 1, Relaxed);
+            assert!(left > 1, "key comparison failed");
+        }
+        self.0.cmp(&other.0)
+    }
+}
+
+#[test]
+fn panicking_ord_in_top_down_insert_keeps_the_treap() {
+    let rng = fastrand::Rng::with_seed(7);
+    let mut treap = treap_vec::Treap::new();
+    for key in (0..1000).step_by(2) {
+        treap.insert_with(InsertStrategy::TopDown, Key(key), rng.u32(1..), key);
+    }
+    // The lightest weight puts the new node at the root, so the whole
+    // search path is split. Let each of the comparisons fail in turn.
+    for fail_at in 1.. {
+        COMPARISONS_LEFT.store(fail_at, Relaxed);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            treap.insert_with(InsertStrategy::TopDown, Key(501), 0, 501)
+        }));
+        COMPARISONS_LEFT.store(0, Relaxed);
+        if let Err(err) = treap.validate() {
+            panic!("treap invalid after comparison {fail_at} failed: {err}");
+        }
+        if result.is_ok() {
+            assert!(fail_at > 3, "only {} comparisons", fail_at - 1);
+            break;
+        }
+        assert_eq!(treap.iter().count(), 500, "comparison {fail_at} failed");
+    }
+    assert_eq!(treap.iter().count(), 501);
+    assert_eq!(treap.find(&Key(501)), Some(&501));
+}
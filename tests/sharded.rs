@@ -0,0 +1,100 @@
+//! `ShardedTreap` against a `BTreeMap` model, single- and multi-threaded.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+use std::thread;
+
+use treap_rust::sharded::ShardedTreap;
+
+const THREADS: u32 = 4;
+const KEYS_PER_THREAD: u32 = 5_000;
+
+#[test]
+fn matches_btreemap() {
+    let rng = fastrand::Rng::with_seed(3);
+    let map = ShardedTreap::with_shards(5);
+    let mut model = BTreeMap::new();
+    for _ in 0..20_000 {
+        let key = rng.u16(..1024);
+        match rng.u8(..4) {
+            0 | 1 => {
+                let value = rng.u32(..);
+                map.insert(key, value);
+                model.entry(key).or_insert(value);
+            }
+            2 => assert_eq!(map.remove(&key), model.remove(&key)),
+            _ => assert_eq!(map.find_cloned(&key).as_ref(), model.get(&key)),
+        }
+    }
+    let shards = map.lock_all();
+    assert!(shards.iter().eq(model.iter()));
+}
+
+#[test]
+fn threads_share_the_map() {
+    let map = ShardedTreap::new();
+    thread::scope(|s| {
+        for thread in 0..THREADS {
+            let map = &map;
+            s.spawn(move || {
+                // Each thread owns the keys congruent to its number, inserts
+                // all of them and removes every third one again.
+                let keys = (0..KEYS_PER_THREAD).map(|i| i * THREADS + thread);
+                for key in keys.clone() {
+                    map.insert(key, u64::from(key) * 2);
+                    assert_eq!(map.find_cloned(&key), Some(u64::from(key) * 2));
+                }
+                for key in keys.filter(|key| key % 3 == 0) {
+                    assert_eq!(map.remove(&key), Some(u64::from(key) * 2));
+                    assert_eq!(map.find_with(&key, |_| ()), None);
+                }
+            });
+        }
+    });
+    let shards = map.lock_all();
+    let expected = (0..KEYS_PER_THREAD * THREADS).filter(|key| key % 3 != 0);
+    assert!(shards.iter().map(|(k, v)| (*k, *v)).eq(expected.map(|k| (k, u64::from(k) * 2))));
+}
+
+#[test]
+#[should_panic(expected = "at least one shard")]
+fn zero_shards_panics() {
+    ShardedTreap::<u32, u32>::with_shards(0);
+}
+
+static PANIC_IN_CMP: AtomicBool = AtomicBool::new(false);
+
+/// A key whose comparison panics while `PANIC_IN_CMP` is set.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct Key(u32);
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        assert!(!PANIC_IN_CMP.load(Relaxed), "comparison failed");
+        self.0.cmp(&other.0)
+    }
+}
+
+#[test]
+fn panicking_ord_poisons_the_shard() {
+    let map = ShardedTreap::with_shards(1);
+    for key in 0..100 {
+        map.insert(Key(key), key);
+    }
+    PANIC_IN_CMP.store(true, Relaxed);
+    let insert = panic::catch_unwind(AssertUnwindSafe(|| map.insert(Key(1000), 0)));
+    PANIC_IN_CMP.store(false, Relaxed);
+    assert!(insert.is_err());
+    // The treap may be half updated, so it is not used again.
+    let find = panic::catch_unwind(AssertUnwindSafe(|| map.find_cloned(&Key(1))));
+    let message = *find.unwrap_err().downcast::<&str>().unwrap();
+    assert_eq!(message, "a shard of the ShardedTreap is poisoned");
+}
@@ -0,0 +1,203 @@
+//! Snapshots of `treap_vec::Treap`: a saved arena loads back with the same
+//! layout, and damaged or foreign data is rejected with the matching error
+//! instead of producing a treap that loops or panics.
+
+use treap_rust::treap_vec::snapshot::{SnapshotError, SnapshotView};
+use treap_rust::treap_vec::Treap;
+
+const HEADER_LEN: usize = 36;
+/// Parent, left and right index plus a `u32` key, weight and value.
+const NODE_LEN: usize = 3 * 8 + 3 * 4;
+
+fn treap(len: u32) -> Treap<u32, u32, u32> {
+    let rng = fastrand::Rng::with_seed(len.into());
+    let mut treap = Treap::new();
+    for key in 0..len {
+        treap.insert(rng.u32(..1000), rng.u32(..), key);
+    }
+    treap
+}
+
+fn save(treap: &Treap<u32, u32, u32>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    treap.save_to(&mut bytes).unwrap();
+    bytes
+}
+
+fn load(bytes: &[u8]) -> Result<Treap<u32, u32, u32>, SnapshotError> {
+    Treap::load_from(bytes)
+}
+
+fn view(bytes: &[u8]) -> Result<SnapshotView<'_, u32, u32, u32>, SnapshotError> {
+    SnapshotView::new(bytes)
+}
+
+/// Writes a new FNV-1a checksum after changing `bytes`, so that only the
+/// change itself can be rejected.
+fn reseal(bytes: &mut [u8]) {
+    let (data, trailer) = bytes.split_at_mut(bytes.len() - 8);
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &byte in data.iter() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    trailer.copy_from_slice(&hash.to_le_bytes());
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+fn write_u64(bytes: &mut [u8], at: usize, value: u64) {
+    bytes[at..at + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+fn root(bytes: &[u8]) -> u64 {
+    read_u64(bytes, HEADER_LEN - 8)
+}
+
+/// Offset of the parent (`0`), left (`1`) or right (`2`) index of a node.
+fn link(node: u64, which: usize) -> usize {
+    HEADER_LEN + node as usize * NODE_LEN + which * 8
+}
+
+fn assert_corrupt(bytes: &[u8]) {
+    assert!(matches!(load(bytes), Err(SnapshotError::Corrupt(_))));
+    assert!(matches!(view(bytes), Err(SnapshotError::Corrupt(_))));
+}
+
+#[test]
+fn round_trip_keeps_the_layout() {
+    let treap = treap(500);
+    let bytes = save(&treap);
+    let loaded = load(&bytes).unwrap();
+    loaded.validate().unwrap();
+    assert_eq!(loaded.shape(), treap.shape());
+    assert!(loaded.iter().eq(treap.iter()));
+    assert_eq!(save(&loaded), bytes);
+
+    let view = view(&bytes).unwrap();
+    assert_eq!(view.len(), treap.iter().count());
+    for key in 0..1000 {
+        assert_eq!(view.find(&key), treap.find(&key).copied());
+    }
+
+    let empty = save(&Treap::new());
+    assert_eq!(load(&empty).unwrap().iter().count(), 0);
+    let empty = self::view(&empty).unwrap();
+    assert!(empty.is_empty());
+    assert_eq!(empty.find(&0), None);
+}
+
+#[test]
+fn rejects_bad_magic() {
+    let mut bytes = save(&treap(10));
+    bytes[0] = b'X';
+    assert!(matches!(load(&bytes), Err(SnapshotError::BadMagic)));
+    assert!(matches!(view(&bytes), Err(SnapshotError::BadMagic)));
+    assert!(matches!(load(b""), Err(SnapshotError::BadMagic)));
+}
+
+#[test]
+fn rejects_other_versions() {
+    let mut bytes = save(&treap(10));
+    bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+    reseal(&mut bytes);
+    assert!(matches!(load(&bytes), Err(SnapshotError::UnsupportedVersion(1))));
+    assert!(matches!(view(&bytes), Err(SnapshotError::UnsupportedVersion(1))));
+}
+
+#[test]
+fn rejects_checksum_mismatch() {
+    let mut bytes = save(&treap(10));
+    // The value of the first node.
+    bytes[HEADER_LEN + NODE_LEN - 1] ^= 1;
+    assert!(matches!(load(&bytes), Err(SnapshotError::ChecksumMismatch)));
+    assert!(matches!(view(&bytes), Err(SnapshotError::ChecksumMismatch)));
+}
+
+#[test]
+fn rejects_truncated_input() {
+    let bytes = save(&treap(10));
+    for len in [5, HEADER_LEN, HEADER_LEN + NODE_LEN, bytes.len() - 1] {
+        assert!(load(&bytes[..len]).is_err(), "{} bytes loaded", len);
+        assert!(view(&bytes[..len]).is_err(), "{} bytes viewed", len);
+    }
+    // Dropping a whole node and sealing again leaves a count that does not
+    // match the data.
+    let mut short = bytes[..bytes.len() - 8 - NODE_LEN].to_vec();
+    short.extend_from_slice(&[0; 8]);
+    reseal(&mut short);
+    assert_corrupt(&short);
+}
+
+#[test]
+fn view_needs_fixed_size_nodes() {
+    let mut treap = Treap::new();
+    treap.insert("key".to_string(), 1u32, 1u32);
+    let mut bytes = Vec::new();
+    treap.save_to(&mut bytes).unwrap();
+    let view = SnapshotView::<String, u32, u32>::new(&bytes);
+    assert!(matches!(view, Err(SnapshotError::NotFixedSize)));
+    // Fixed sizes that do not match the types of the view.
+    let bytes = save(&self::treap(10));
+    let view = SnapshotView::<u64, u32, u32>::new(&bytes);
+    assert!(matches!(view, Err(SnapshotError::NotFixedSize)));
+}
+
+#[test]
+fn rejects_links_that_are_not_a_tree() {
+    let bytes = save(&treap(50));
+    let root = root(&bytes);
+    let left = read_u64(&bytes, link(root, 1));
+    let right = read_u64(&bytes, link(root, 2));
+    assert!(left != u64::MAX && right != u64::MAX);
+
+    // A node that is its own child.
+    let mut cycle = bytes.clone();
+    write_u64(&mut cycle, link(root, 1), root);
+    reseal(&mut cycle);
+    assert_corrupt(&cycle);
+
+    // Both children of the root are the same node.
+    let mut shared = bytes.clone();
+    write_u64(&mut shared, link(root, 2), left);
+    reseal(&mut shared);
+    assert_corrupt(&shared);
+
+    // A parent link that points to the wrong node.
+    let mut parent = bytes.clone();
+    write_u64(&mut parent, link(left, 0), right);
+    reseal(&mut parent);
+    assert_corrupt(&parent);
+
+    // A right subtree that is not reachable from the root any more.
+    let mut unreachable = bytes.clone();
+    write_u64(&mut unreachable, link(root, 2), u64::MAX);
+    reseal(&mut unreachable);
+    assert_corrupt(&unreachable);
+}
+
+#[test]
+fn rejects_keys_and_weights_out_of_order() {
+    let bytes = save(&treap(50));
+    let root = root(&bytes);
+    let left = read_u64(&bytes, link(root, 1));
+    // The key and the weight follow the three links.
+    let key = |node| link(node, 0) + 3 * 8;
+    let weight = |node| key(node) + 4;
+
+    // The root and its left child swap keys, so the links still form a tree.
+    let mut keys = bytes.clone();
+    for (at, from) in [(key(root), key(left)), (key(left), key(root))] {
+        keys[at..at + 4].copy_from_slice(&bytes[from..from + 4]);
+    }
+    reseal(&mut keys);
+    assert!(matches!(load(&keys), Err(SnapshotError::Corrupt(_))));
+
+    // The left child of the root becomes lighter than the root.
+    let mut weights = bytes.clone();
+    weights[weight(left)..weight(left) + 4].copy_from_slice(&0u32.to_le_bytes());
+    reseal(&mut weights);
+    assert!(matches!(load(&weights), Err(SnapshotError::Corrupt(_))));
+}
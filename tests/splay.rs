@@ -0,0 +1,98 @@
+//! `splay::Splay` must move every key it looks up to the root, so that
+//! frequently requested keys stay near the top.
+
+use std::collections::BTreeMap;
+
+use treap_rust::splay::Splay;
+
+fn root(splay: &Splay<u32, u32>) -> Option<u32> {
+    splay.to_ascii(0, 1).lines().next()?.parse().ok()
+}
+
+/// Depth of every key, read off the rendered tree.
+fn depths(splay: &Splay<u32, u32>) -> BTreeMap<u32, usize> {
+    let ascii = splay.to_ascii(usize::MAX, usize::MAX);
+    let mut depths = BTreeMap::new();
+    for line in ascii.lines() {
+        let (prefix, key) = line.rsplit_once(' ').unwrap_or(("", line));
+        if let Ok(key) = key.parse() {
+            // Every level indents by four characters, like `├── `.
+            let indent = prefix.chars().count() + 1;
+            depths.insert(key, indent / 4);
+        }
+    }
+    depths
+}
+
+#[test]
+fn find_moves_the_key_to_the_root() {
+    let rng = fastrand::Rng::with_seed(2);
+    let mut keys: Vec<u32> = (0..2000).map(|key| 2 * key).collect();
+    rng.shuffle(&mut keys);
+    let mut splay = Splay::new();
+    for &key in &keys {
+        splay.insert(key, key);
+        assert_eq!(root(&splay), Some(key));
+    }
+    for _ in 0..2000 {
+        let key = rng.u32(..4000);
+        let found = splay.find(&key).copied();
+        assert_eq!(found, key.is_multiple_of(2).then_some(key));
+        // A missing key leaves one of its neighbours at the root.
+        let root = root(&splay).unwrap();
+        assert!(root.abs_diff(key) <= 1, "{} at the root after {}", root, key);
+    }
+    splay.validate().unwrap();
+    assert!(splay.iter().map(|(k, _)| *k).eq((0..2000).map(|key| 2 * key)));
+}
+
+#[test]
+fn hot_keys_stay_near_the_root() {
+    let rng = fastrand::Rng::with_seed(8);
+    let mut splay = Splay::new();
+    for _ in 0..10_000 {
+        let key = rng.u32(..);
+        splay.insert(key, key);
+    }
+    let hot: Vec<u32> = splay.iter().map(|(k, _)| *k).step_by(1000).collect();
+    for _ in 0..100 {
+        for key in &hot {
+            assert_eq!(splay.find(key), Some(key));
+        }
+    }
+    let depths = depths(&splay);
+    let deepest_hot = hot.iter().map(|key| depths[key]).max().unwrap();
+    // The ten keys take turns near the root, while the rest of the tree
+    // stays about as deep as the random inserts made it.
+    assert!(deepest_hot <= 10, "a hot key at depth {}", deepest_hot);
+    assert!(splay.average_depth() > 20.0);
+    splay.validate().unwrap();
+}
+
+#[test]
+fn removes_match_a_btree_map() {
+    let rng = fastrand::Rng::with_seed(3);
+    let mut splay = Splay::new();
+    let mut model = BTreeMap::new();
+    for step in 0..20_000 {
+        let key = rng.u16(..2048);
+        match rng.u8(..4) {
+            0 => assert_eq!(splay.remove(&key), model.remove(&key)),
+            1 => assert_eq!(splay.find(&key), model.get(&key)),
+            _ => {
+                splay.insert(key, step);
+                model.entry(key).or_insert(step);
+            }
+        }
+        if step % 97 == 0 {
+            splay.validate().unwrap();
+        }
+    }
+    assert!(splay.iter().eq(model.iter()));
+    for key in model.keys() {
+        assert_eq!(splay.remove(key), model.get(key).copied());
+    }
+    assert_eq!(splay.height(), 0);
+    assert_eq!(splay.remove(&0), None);
+    assert_eq!(splay.find(&0), None);
+}
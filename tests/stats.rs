@@ -0,0 +1,88 @@
+//! `height`, `average_depth`, `depth_histogram` and `leaf_count` on a small
+//! tree whose shape is known:
+//!
+//! ```text
+//!         4
+//!       /   \
+//!      2     6
+//!     / \   / \
+//!    1   3 5   7
+//!               \
+//!                8
+//! ```
+
+use treap_rust::{avl::AVL, bst::BST, treap, treap_ptr, treap_soa, treap_vec, Treap};
+
+/// Inserting in this order into a BST, or into a treap with the index as
+/// weight, builds the tree above.
+const KEYS: [u32; 8] = [4, 2, 6, 1, 3, 5, 7, 8];
+const HISTOGRAM: [usize; 4] = [1, 2, 4, 1];
+const LEAVES: usize = 4;
+const AVERAGE_DEPTH: f64 = (2.0 * 1.0 + 4.0 * 2.0 + 3.0) / 8.0;
+
+macro_rules! assert_stats {
+    ($tree:expr) => {{
+        let tree = &$tree;
+        assert_eq!(tree.height(), HISTOGRAM.len());
+        assert_eq!(tree.depth_histogram(), HISTOGRAM);
+        assert_eq!(tree.leaf_count(), LEAVES);
+        assert_eq!(tree.average_depth(), AVERAGE_DEPTH);
+    }};
+}
+
+macro_rules! assert_empty_stats {
+    ($tree:expr) => {{
+        let tree = &$tree;
+        assert_eq!(tree.height(), 0);
+        assert!(tree.depth_histogram().is_empty());
+        assert_eq!(tree.leaf_count(), 0);
+        assert_eq!(tree.average_depth(), 0.0);
+    }};
+}
+
+#[test]
+fn bst_stats() {
+    let mut bst = BST::new();
+    assert_empty_stats!(bst);
+    for key in KEYS {
+        bst.insert(key, ());
+    }
+    assert_stats!(bst);
+}
+
+#[test]
+fn treap_stats() {
+    let mut treap_rec = Treap::new();
+    let mut treap_vec = treap_vec::Treap::new();
+    let mut treap_soa = treap_soa::Treap::new();
+    let mut treap_ptr = treap_ptr::Treap::new();
+    let mut treap = treap::Treap::new();
+    assert_empty_stats!(treap_rec);
+    assert_empty_stats!(treap_vec);
+    assert_empty_stats!(treap_soa);
+    assert_empty_stats!(treap_ptr);
+    assert_empty_stats!(treap);
+    for (weight, key) in KEYS.into_iter().enumerate() {
+        treap_rec.insert(key, weight, ());
+        treap_vec.insert(key, weight, ());
+        treap_soa.insert(key, weight, ());
+        treap_ptr.insert(key, weight, ());
+        treap.insert(key, weight, ());
+    }
+    assert_stats!(treap_rec);
+    assert_stats!(treap_vec);
+    assert_stats!(treap_soa);
+    assert_stats!(treap_ptr);
+    assert_stats!(treap);
+}
+
+#[test]
+fn avl_stats() {
+    let mut avl = AVL::new();
+    assert_empty_stats!(avl);
+    // Sorted inserts rotate into the same shape.
+    for key in 1..=8 {
+        avl.insert(key, ());
+    }
+    assert_stats!(avl);
+}